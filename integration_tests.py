@@ -1,4 +1,3 @@
-from bs4 import BeautifulSoup
 import re
 import requests
 import sys
@@ -7,6 +6,9 @@ import time
 
 local_address = "http://0.0.0.0:3000/"
 
+# Ask for the grid as text rather than HTML
+text_headers = {"Accept": "text/plain"}
+
 def check_diff(received, expected):
     """
     Checks difference between two strings.
@@ -36,26 +38,20 @@ def check_diff(received, expected):
 
 def find_robot_grid(r):
     """
-    Find the table containing the robot using BeautifulSoup.
-    Table should have the class 'robot-grid'.
+    Read the grid of a response asked as plain text (`Accept: text/plain`):
+    one line of text per line of the grid, with '.' for an empty cell,
+    'R' for the robot, '#' for a wall, the number of items of a cell ('+'
+    for 10 or more) and '*' for a painted cell.
 
     Args:
-        r (request): request containing the table to analyze.
+        r (request): response containing the grid to analyze.
 
     Returns:
-        table (list): table as a list of lists.
+        table (list): table as a list of strings, one per line of the grid.
     """
-    soup = BeautifulSoup(r.text, "html.parser")
-    table_text = soup.find("table", id="robot-grid")
-    table = []
-    for line in table_text.find_all("tr"):
-        l = []
-        for col in line.find_all("td"):
-            l.append(col)
-        table.append(l)
-    return table
-
-def find_image_in_grid(table):
+    return r.text.splitlines()
+
+def find_robot_in_grid(table):
     """
     Find the robot in the grid parsed using find_robot_grid().
 
@@ -69,13 +65,13 @@ def find_image_in_grid(table):
     found = False
     for l, line in enumerate(table):
         for c, col in enumerate(line):
-            if len(col.find_all("img")) == 1:
+            if col == "R":
                 if not(found):
                     found = True
                     x = l
                     y = c
                 else:
-                    raise Exception(f"Image found twice! ({x}, {y}) and now ({l}, {c})")
+                    raise Exception(f"Robot found twice! ({x}, {y}) and now ({l}, {c})")
     return x, y
 
 def final_position(moves, x_max = 4, y_max = 4):
@@ -158,10 +154,10 @@ def test_moves(moves):
     session = requests.Session()
     for m in moves:
         if re.search("coords", m):
-            r = session.get(local_address + m)
+            r = session.get(local_address + m, headers=text_headers)
         else:
-            r = session.post(local_address + m)
-    final_x, final_y = find_image_in_grid(find_robot_grid(r))
+            r = session.post(local_address + m, headers=text_headers)
+    final_x, final_y = find_robot_in_grid(find_robot_grid(r))
     if (final_x != expected_x) or (final_y != expected_y):
         print("Final position doesn't match expected position!")
         print(f"moves: {moves}")
@@ -179,6 +175,10 @@ def main():
               matches the saved ones.
     - TEST 2: ask for random requests.
     - TEST 3: tries many moves sequences.
+    - TEST 4: changes the grid size.
+    - TEST 5: renders a grid with a wall as SVG.
+    - TEST 6: renders the grid as PNG and a program as an animated GIF.
+    - TEST 7: answers with an ASCII grid to clients asking for plain text.
 
     Returns:
         (boolean): True if all tests passed, otherwise False.
@@ -363,7 +363,8 @@ def main():
         data={
             "change_max_i": new_max_i,
             "change_max_j": new_max_j
-        }
+        },
+        headers=text_headers
     )
     
     if r_change_grid.status_code != requests.codes.ok:
@@ -392,7 +393,7 @@ def main():
             "change_max_j": new_max_j
         }
     )
-    r = session.get(local_address + 'right')
+    r = session.get(local_address + 'right', headers=text_headers)
     new_grid = find_robot_grid(r)
     if len(new_grid) != 20:
         print(f"Max_i (> 20) should be forced back to 20 and not {len(new_grid)}!")
@@ -412,7 +413,7 @@ def main():
             "change_max_j": new_max_j
         }
     )
-    r = session.get(local_address + 'right')
+    r = session.get(local_address + 'right', headers=text_headers)
     new_grid = find_robot_grid(r)
     if len(new_grid) != 5:
         print(f"Max_i (not a number) should be forced back to 5 and not {len(new_grid)}!")
@@ -427,7 +428,798 @@ def main():
     print("------------------------------------------------------------------")
 
 
-    return flag1 and flag2 and flag3 and flag4
+    print("------------------------------------------------------------------")
+    print("TEST 5 : SVG rendering")
+    print("----------------------")
+    flag5 = True
+    session = requests.Session()
+    session.post(local_address + 'change-max', data={"change_max_i": 3, "change_max_j": 4})
+    r = session.post(
+        local_address + 'user-code',
+        data={"user_code": "wall (0, 1)\ndown\nright"}
+    )
+    if r.status_code != requests.codes.ok:
+        print(f"Bad status code for the program: {r.status_code}")
+        flag5 = False
+
+    r_svg = session.get(local_address + 'render.svg')
+    if r_svg.status_code != requests.codes.ok:
+        print(f"Bad status code: {r_svg.status_code}")
+        flag5 = False
+
+    ct = r_svg.headers["Content-Type"]
+    if ct != "image/svg+xml":
+        print(f"Wrong Content-Type: {ct} (received) != image/svg+xml (expected)")
+        flag5 = False
+
+    expected_title = "<title>RustBot at (1, 1) in a 3x4 grid</title>"
+    if expected_title not in r_svg.text:
+        print(f"Missing {expected_title} in the SVG")
+        flag5 = False
+
+    # Wall in cell (0, 1): 24px of headers, 40px per cell
+    expected_wall = '<rect x="64" y="24" width="40" height="40" fill="#2e3440"/>'
+    if expected_wall not in r_svg.text:
+        print(f"Missing wall {expected_wall} in the SVG")
+        flag5 = False
+
+    # The robot can't go through the wall
+    r = session.post(local_address + 'user-code', data={"user_code": "up"})
+    if r.status_code != requests.codes.unprocessable_entity:
+        print(f"Moving into a wall should fail, not return {r.status_code}")
+        flag5 = False
+
+    if flag5:
+        print("OK!")
+    print("------------------------------------------------------------------")
+
+    print("------------------------------------------------------------------")
+    print("TEST 6 : PNG and GIF rendering")
+    print("------------------------------")
+    flag6 = True
+    session = requests.Session()
+    r_png = session.get(local_address + 'render.png')
+    ct = r_png.headers["Content-Type"]
+    if r_png.status_code != requests.codes.ok or ct != "image/png":
+        print(f"Bad PNG response: {r_png.status_code} {ct}")
+        flag6 = False
+    if not r_png.content.startswith(b"\x89PNG"):
+        print("Not a PNG image")
+        flag6 = False
+
+    r_gif = session.post(
+        local_address + 'user-code?format=gif',
+        data={"user_code": "right\ndown\nleft"}
+    )
+    ct = r_gif.headers["Content-Type"]
+    if r_gif.status_code != requests.codes.ok or ct != "image/gif":
+        print(f"Bad GIF response: {r_gif.status_code} {ct}")
+        flag6 = False
+    # One graphic control extension per frame: starting grid + 3 steps
+    nb_frames = r_gif.content.count(b"\x21\xf9\x04")
+    if not r_gif.content.startswith(b"GIF89a") or nb_frames != 4:
+        print(f"Not an animated GIF with 4 frames ({nb_frames} frames)")
+        flag6 = False
+
+    r_gif = session.post(
+        local_address + 'user-code?format=gif',
+        data={"user_code": "go to (99, 99)"}
+    )
+    if r_gif.status_code != requests.codes.unprocessable_entity:
+        print(f"A failing program should return 422, not {r_gif.status_code}")
+        flag6 = False
+
+    if flag6:
+        print("OK!")
+    print("------------------------------------------------------------------")
+
+    print("------------------------------------------------------------------")
+    print("TEST 7 : content negotiation")
+    print("----------------------------")
+    flag7 = True
+    session = requests.Session()
+    r = session.get(local_address + 'play', headers=text_headers)
+    ct = r.headers["Content-Type"]
+    if ct != "text/plain; charset=utf-8":
+        print(f"Wrong Content-Type: {ct} (received) != text/plain; charset=utf-8 (expected)")
+        flag7 = False
+    if r.text != "R....\n.....\n.....\n.....\n.....\n":
+        print(f"Wrong ASCII grid:\n{r.text}")
+        flag7 = False
+    program = "wall (1, 0)\nrepeat 12\n    item (0, 2)\nend\nitem (0, 3)\npen down\nright"
+    painter = requests.Session()
+    painter.post(local_address + 'user-code', data={"user_code": program})
+    r = painter.get(local_address + 'code', headers=text_headers)
+    if r.text != "*R+1.\n#....\n.....\n.....\n.....\n":
+        print(f"Wrong ASCII grid with items, paint and a wall:\n{r.text}")
+        flag7 = False
+
+    # Browsers accept everything but prefer HTML
+    browser_headers = {"Accept": "text/html,application/xhtml+xml,*/*;q=0.8"}
+    r = session.get(local_address + 'play', headers=browser_headers)
+    ct = r.headers["Content-Type"]
+    if ct != "text/html; charset=utf-8":
+        print(f"Wrong Content-Type: {ct} (received) != text/html; charset=utf-8 (expected)")
+        flag7 = False
+
+    if flag7:
+        print("OK!")
+    print("------------------------------------------------------------------")
+
+    print("------------------------------------------------------------------")
+    print("TEST 8 : HTML fragments")
+    print("-----------------------")
+    flag8 = True
+    session.post(local_address + 'reset')
+    session.post(local_address + 'down')
+    r = session.get(local_address + 'fragment/grid')
+    if r.status_code != 200 or not r.text.startswith('<table id="robot-grid">') or "<html>" in r.text:
+        print(f"Wrong grid fragment:\n{r.text}")
+        flag8 = False
+    r = session.get(local_address + 'fragment/coords')
+    if r.text.strip() != '<p id="coords">Current coordinates: (1, 0)</p>':
+        print(f"Wrong coordinates fragment: {r.text}")
+        flag8 = False
+    r = session.post(local_address + 'user-code?format=fragment', data={"user_code": "jump"})
+    if r.status_code != 422 or not r.text.startswith('<p id="code-error">') or "<html>" in r.text:
+        print(f"Wrong diagnostics fragment: {r.status_code}\n{r.text}")
+        flag8 = False
+    r = session.post(local_address + 'user-code?format=fragment', data={"user_code": "right"})
+    if r.status_code != 200 or r.text.strip() != '<p id="code-error"></p>':
+        print(f"Wrong empty diagnostics fragment: {r.status_code}\n{r.text}")
+        flag8 = False
+
+    if flag8:
+        print("OK!")
+    print("------------------------------------------------------------------")
+
+    print("------------------------------------------------------------------")
+    print("TEST 9 : saved programs")
+    print("-----------------------")
+    flag9 = True
+    session = requests.Session()
+    session.get(local_address + 'code')
+    r = session.post(local_address + 'programs', data={"name": " square ", "user_code": "right\ndown"})
+    if r.status_code != 201 or r.text != "square":
+        print(f"Saving failed: {r.status_code} {r.text}")
+        flag9 = False
+    session.post(local_address + 'programs', data={"name": "line", "user_code": "right"})
+    r = session.get(local_address + 'programs')
+    if r.json() != ["line", "square"]:
+        print(f"Wrong list of programs: {r.text}")
+        flag9 = False
+    r = session.get(local_address + 'programs/square')
+    if r.text != "right\ndown":
+        print(f"Wrong saved program: {r.text}")
+        flag9 = False
+    r = session.post(local_address + 'programs/square/rename', data={"new_name": "line"})
+    if r.status_code != 409:
+        print(f"Renaming to an existing name should fail, not return {r.status_code}")
+        flag9 = False
+    r = session.post(local_address + 'programs/square/rename', data={"new_name": "my square"})
+    if r.status_code != 200 or session.get(local_address + 'programs/my%20square').text != "right\ndown":
+        print(f"Renaming failed: {r.status_code} {r.text}")
+        flag9 = False
+    r = session.delete(local_address + 'programs/line')
+    if r.status_code != 204 or session.get(local_address + 'programs/line').status_code != 404:
+        print(f"Deleting failed: {r.status_code} {r.text}")
+        flag9 = False
+    r = session.get(local_address + 'fragment/programs')
+    if '<option value="my square">' not in r.text or 'value="line"' in r.text:
+        print(f"Wrong programs fragment:\n{r.text}")
+        flag9 = False
+    # Other sessions don't see them
+    if requests.get(local_address + 'programs').json() != []:
+        print("Programs are visible from another session")
+        flag9 = False
+    # The last program sent is shown again in the textarea
+    session.post(local_address + 'user-code', data={"user_code": "down\n<left>"})
+    r = session.get(local_address + 'code')
+    if 'id="code">down\n&#60;left&#62;</textarea>' not in r.text:
+        print("Last program not shown in the textarea")
+        flag9 = False
+
+    if flag9:
+        print("OK!")
+    print("------------------------------------------------------------------")
+
+    print("------------------------------------------------------------------")
+    print("TEST 10 : permalinks")
+    print("--------------------")
+    flag10 = True
+    teacher = requests.Session()
+    teacher.post(local_address + 'change-max', data={"change_max_i": 3, "change_max_j": 4})
+    teacher.post(local_address + 'user-code', data={"user_code": "wall (1, 1)\ngo to (2, 3)"})
+    r = teacher.post(local_address + 'share', data={"user_code": "up\nleft"})
+    link = r.text
+    if r.status_code != 200 or not link.startswith("/s/"):
+        print(f"Bad permalink: {r.status_code} {link}")
+        flag10 = False
+    student = requests.Session()
+    r = student.get(local_address + link[1:])
+    if r.status_code != 200 or not r.url.endswith("/code") or 'id="code">up\nleft</textarea>' not in r.text:
+        print(f"Permalink did not restore the program: {r.status_code} {r.url}")
+        flag10 = False
+    r = student.get(local_address + 'code', headers=text_headers)
+    if r.text.splitlines() != ["....", ".#..", "...R"]:
+        print(f"Permalink did not restore the grid:\n{r.text}")
+        flag10 = False
+    r = student.get(local_address + 's/not-a-link')
+    if r.status_code != 400:
+        print(f"An invalid permalink should return 400, not {r.status_code}")
+        flag10 = False
+
+    if flag10:
+        print("OK!")
+    print("------------------------------------------------------------------")
+
+    print("------------------------------------------------------------------")
+    print("TEST 11 : state export and import")
+    print("---------------------------------")
+    flag11 = True
+    source = requests.Session()
+    source.post(local_address + 'change-max', data={"change_max_i": 4, "change_max_j": 3})
+    source.post(local_address + 'user-code', data={"user_code": "wall (0, 2)\ndown\nright"})
+    r = source.get(local_address + 'state/export')
+    state = r.json()
+    expected = {
+        "version": 1,
+        "position": {"i": 1, "j": 1},
+        "heading": "east",
+        "max-i": 4,
+        "max-j": 3,
+        "walls": [{"i": 0, "j": 2}],
+        "history": [{"i": 0, "j": 0}, {"i": 1, "j": 0}, {"i": 1, "j": 1}],
+        "pen": {"down": False, "color": "black"},
+        "paint": [],
+        "items": [],
+        "inventory": 0,
+        "program": "wall (0, 2)\ndown\nright",
+        "dialect": "rustbot",
+    }
+    if state != expected:
+        print(f"Wrong exported state: {state}")
+        flag11 = False
+    target = requests.Session()
+    r = target.post(local_address + 'state/import', json=state)
+    if r.status_code != 200 or target.get(local_address + 'state/export').json() != expected:
+        print(f"Import failed: {r.status_code} {r.text}")
+        flag11 = False
+    if target.get(local_address + 'code', headers=text_headers).text.splitlines() != ["..#", ".R.", "...", "..."]:
+        print("Imported grid not shown")
+        flag11 = False
+    r = target.post(local_address + 'state/import', json=dict(state, version=99))
+    if r.status_code != 422:
+        print(f"Importing an unknown version should return 422, not {r.status_code}")
+        flag11 = False
+    r = target.post(local_address + 'state/import', json=dict(state, walls=[{"i": 1, "j": 1}]))
+    if r.status_code != 422:
+        print(f"Importing a wall under the robot should return 422, not {r.status_code}")
+        flag11 = False
+    for too_many in [dict(state, inventory=1000), dict(state, items=[{"i": 0, "j": 0, "count": 600}] * 2)]:
+        r = target.post(local_address + 'state/import', json=too_many)
+        if r.status_code != 422:
+            print(f"Importing more than 999 items should return 422, not {r.status_code}")
+            flag11 = False
+    source = requests.Session()
+    for _ in range(3):
+        source.post(local_address + 'user-code', data={"user_code": "repeat 400\n    item (0, 0)\nend"})
+    state = source.get(local_address + 'state/export').json()
+    r = target.post(local_address + 'state/import', json=state)
+    if r.status_code != 200 or target.get(local_address + 'state/export').json() != state:
+        print(f"A state exported after adding many items should import back: {r.status_code} {r.text}")
+        flag11 = False
+
+    if flag11:
+        print("OK!")
+    print("------------------------------------------------------------------")
+
+    print("------------------------------------------------------------------")
+    print("TEST 12 : pen")
+    print("-------------")
+    flag12 = True
+    session = requests.Session()
+    session.post(local_address + 'change-max', data={"change_max_i": 3, "change_max_j": 3})
+    r = session.post(local_address + 'user-code', data={"user_code": "pen color red\npen down\nright\ndown\npen up\ndown"})
+    if r.status_code != 200:
+        print(f"Pen program failed: {r.status_code}")
+        flag12 = False
+    r = session.get(local_address + 'code', headers=text_headers)
+    if r.text.splitlines() != ["**.", ".*.", ".R."]:
+        print(f"Wrong painted grid:\n{r.text}")
+        flag12 = False
+    r = session.get(local_address + 'fragment/grid')
+    if r.text.count('style="background-color: #bf616a"') != 3:
+        print("Painted cells not colored in the grid")
+        flag12 = False
+    # Play mode toggle
+    session.post(local_address + 'pen-color', data={"color": "blue"})
+    session.post(local_address + 'pen')
+    session.post(local_address + 'left')
+    r = session.get(local_address + 'fragment/pen')
+    if "PEN UP" not in r.text or '<option value="blue" selected>' not in r.text:
+        print(f"Wrong pen controls:\n{r.text}")
+        flag12 = False
+    r = session.get(local_address + 'state/export').json()
+    if r["pen"] != {"down": True, "color": "blue"} or {"i": 2, "j": 0, "color": "blue"} not in r["paint"]:
+        print(f"Wrong pen state: {r['pen']} {r['paint']}")
+        flag12 = False
+    r = session.post(local_address + 'user-code', data={"user_code": "pen color pink"})
+    if r.status_code != 422:
+        print(f"Unknown colors should fail, not return {r.status_code}")
+        flag12 = False
+
+    if flag12:
+        print("OK!")
+    print("------------------------------------------------------------------")
+
+    print("------------------------------------------------------------------")
+    print("TEST 13 : canvas export")
+    print("-----------------------")
+    flag13 = True
+    session = requests.Session()
+    session.post(local_address + 'change-max', data={"change_max_i": 2, "change_max_j": 3})
+    session.post(local_address + 'user-code', data={"user_code": "pen color green\npen down\nright\npen up\ndown"})
+    r = session.get(local_address + 'canvas.svg', params={"scale": 5})
+    if (r.headers["Content-Type"] != "image/svg+xml"
+            or 'width="15" height="10" viewBox="0 0 3 2"' not in r.text
+            or r.text.count('fill="#a3be8c"') != 2):
+        print(f"Wrong canvas SVG:\n{r.text}")
+        flag13 = False
+    r = session.get(local_address + 'canvas.png', params={"scale": 4})
+    width, height = int.from_bytes(r.content[16:20], "big"), int.from_bytes(r.content[20:24], "big")
+    if not r.content.startswith(b"\x89PNG") or (width, height) != (12, 8) or b"tRNS" not in r.content:
+        print(f"Wrong canvas PNG: {width}x{height}")
+        flag13 = False
+    r = session.get(local_address + 'canvas.png')
+    if int.from_bytes(r.content[16:20], "big") != 3:
+        print("Default canvas scale should be one pixel per cell")
+        flag13 = False
+    r = session.get(local_address + 'canvas.png', params={"scale": 0})
+    if r.status_code != 400:
+        print(f"An invalid scale should return 400, not {r.status_code}")
+        flag13 = False
+
+    if flag13:
+        print("OK!")
+    print("------------------------------------------------------------------")
+
+    print("------------------------------------------------------------------")
+    print("TEST 14 : items")
+    print("---------------")
+    flag14 = True
+    session = requests.Session()
+    session.post(local_address + 'change-max', data={"change_max_i": 2, "change_max_j": 4})
+    program = "item (0, 2)\nitem (0, 2)\nitem (1, 0)\nwhile not on item\n    right\nend\npick\nif on item\n    pick\nelse\n    wall (1, 1)\nend\ndrop\nright"
+    r = session.post(local_address + 'user-code', data={"user_code": program})
+    if r.status_code != 200:
+        print(f"Items program failed: {r.status_code}")
+        flag14 = False
+    r = session.get(local_address + 'code', headers=text_headers)
+    if r.text.splitlines() != ["..1R", "1..."]:
+        print(f"Wrong grid with items:\n{r.text}")
+        flag14 = False
+    r = session.get(local_address + 'fragment/grid')
+    if (r.text.count('<span class="items"') != 2
+            or '<caption id="inventory">Items carried: 1</caption>' not in r.text):
+        print(f"Items not shown in the grid:\n{r.text}")
+        flag14 = False
+    r = session.get(local_address + 'state/export').json()
+    if r["inventory"] != 1 or r["items"] != [{"i": 0, "j": 2, "count": 1}, {"i": 1, "j": 0, "count": 1}]:
+        print(f"Wrong items state: {r['items']} {r['inventory']}")
+        flag14 = False
+    for code, message in [
+        ("drop\ndrop", "line 2: the robot carries no item to drop"),
+        ("go to (0, 0)\npick", "line 2: there is no item to pick at (0, 0)"),
+        ("while not on item\nend", "line 1: more than 1000 steps executed"),
+        ("if on item\n    right", "line 1: `if` without `end`"),
+        ("end", "line 1: `end` without a block"),
+    ]:
+        r = session.post(local_address + 'user-code', data={"user_code": code}, params={"format": "fragment"})
+        if r.status_code != 422 or message not in r.text:
+            print(f"Wrong error for {code!r}: {r.status_code} {r.text}")
+            flag14 = False
+    session = requests.Session()
+    for run in range(3):
+        r = session.post(local_address + 'user-code', data={"user_code": "repeat 400\n    item (0, 0)\nend"}, params={"format": "fragment"})
+        if (r.status_code, run) not in [(200, 0), (200, 1), (422, 2)]:
+            print(f"Wrong status for the run {run + 1} of 400 items: {r.status_code}")
+            flag14 = False
+    if "line 2: more than 999 items on a cell or carried by the robot" not in r.text:
+        print(f"Wrong error for too many items: {r.text}")
+        flag14 = False
+    r = requests.get(local_address + 'state/export', cookies={"items": "0.1.5000", "inventory": "5000"}).json()
+    if r["items"] != [{"i": 0, "j": 1, "count": 999}] or r["inventory"] != 999:
+        print(f"Items of cookies should be cut to 999: {r['items']} {r['inventory']}")
+        flag14 = False
+
+    if flag14:
+        print("OK!")
+    print("------------------------------------------------------------------")
+
+    print("------------------------------------------------------------------")
+    print("TEST 15 : heading, procedures and Karel")
+    print("---------------------------------------")
+    flag15 = True
+    session = requests.Session()
+    world = "Dimension: (4, 2)\nKarel: (1, 1) east\nBeeper: (3, 1) 2\nWall: (2, 1) east\nSpeed: 1\n"
+    r = session.post(local_address + 'karel/world', data=world)
+    if r.status_code != 200 or r.text != "line 4: walls between cells are not supported, ignored":
+        print(f"Karel world import failed: {r.status_code} {r.text}")
+        flag15 = False
+    program = "function main() {\n  while (frontIsClear()) {\n    if (beepersPresent()) { pickBeeper(); }\n    move();\n  }\n  turnLeft();\n}"
+    r = session.post(local_address + 'user-code', data={"user_code": program, "dialect": "karel"})
+    if r.status_code != 200:
+        print(f"Karel program failed: {r.status_code}")
+        flag15 = False
+    r = session.get(local_address + 'code', headers=text_headers)
+    if r.text.splitlines() != ["....", "..1R"]:
+        print(f"Wrong grid after the Karel program:\n{r.text}")
+        flag15 = False
+    r = session.get(local_address + 'code')
+    if '<option value="karel" selected>Karel</option>' not in r.text or 'title="facing north"' not in r.text:
+        print("Karel dialect or heading not shown in code mode")
+        flag15 = False
+    state = session.get(local_address + 'state/export').json()
+    if state["heading"] != "north" or state["dialect"] != "karel" or state["inventory"] != 1:
+        print(f"Wrong state after the Karel program: {state}")
+        flag15 = False
+    r = session.post(local_address + 'share', data={"user_code": "move();", "dialect": "karel"})
+    student = requests.Session()
+    student.get(local_address + r.text[1:])
+    state = student.get(local_address + 'state/export').json()
+    if state["heading"] != "north" or state["dialect"] != "karel" or state["program"] != "move();":
+        print(f"Permalink did not restore the heading and dialect: {state}")
+        flag15 = False
+    program = "turn left\ndef turn_around\n    turn left\n    turn left\nend\nrepeat 2\n    while front is clear and not on item\n        move\n    end\n    turn_around\nend"
+    r = student.post(local_address + 'user-code', data={"user_code": program, "dialect": "rustbot"})
+    if r.status_code != 200 or student.get(local_address + 'code', headers=text_headers).text.splitlines() != ["....", "..R."]:
+        print(f"Native program with procedures failed: {r.status_code}")
+        flag15 = False
+    for code, dialect, status, message in [
+        ("move()", "karel", 422, "expected `;`, found the end of the program"),
+        ("jump();", "karel", 422, "unknown function `jump()`"),
+        ("jump", "rustbot", 422, "unknown command: `jump`"),
+        ("move", "cobol", 400, "unknown dialect `cobol`"),
+        ("if " + "not " * 100 + "on item\nend", "rustbot", 422, "condition nested too deeply"),
+        ("function main() { if (" + "!" * 100 + "beepersPresent()) { move(); } }", "karel", 422, "condition nested too deeply"),
+        ("repeat 1\n" * 40 + "right\n" + "end\n" * 40, "rustbot", 422, "line 33: blocks nested too deeply (max 32)"),
+        ("function main() {" + "while(frontIsClear()){" * 430 + "}" * 431, "karel", 422, "blocks nested too deeply (max 32)"),
+        ("def p\n" + "    repeat 1\n" * 31 + "p\n" + "end\n" * 32 + "p", "rustbot", 422, "more than 400 nested blocks running"),
+    ]:
+        r = student.post(local_address + 'user-code', data={"user_code": code, "dialect": dialect}, params={"format": "fragment"})
+        if r.status_code != status or message not in r.text:
+            print(f"Wrong error for {code!r} in {dialect}: {r.status_code} {r.text}")
+            flag15 = False
+    r = student.post(local_address + 'karel/world', data="Karel: (1, 1) east\n")
+    if r.status_code != 422:
+        print(f"A Karel world without dimension should return 422, not {r.status_code}")
+        flag15 = False
+    r = student.post(local_address + 'karel/world', data="Dimension: (2, 2)\nBeeper: (1, 1) 1000\n")
+    if r.status_code != 422:
+        print(f"A Karel world with 1000 beepers on a corner should return 422, not {r.status_code}")
+        flag15 = False
+    edge = requests.Session()
+    edge.post(local_address + 'karel/world', data="Dimension: (2, 1)\nKarel: (2, 1) east\n")
+    for code, dialect, message in [
+        ("move();", "karel", "line 1: the robot faces the east edge of the grid"),
+        ("turn left\nturn left\nwall (0, 0)\nstep", "rustbot", "line 4: there is a wall at (0, 0)"),
+    ]:
+        r = edge.post(local_address + 'user-code', data={"user_code": code, "dialect": dialect}, params={"format": "fragment"})
+        if r.status_code != 422 or message not in r.text:
+            print(f"Karel move() should fail at edges and walls, for {code!r}: {r.status_code} {r.text}")
+            flag15 = False
+
+    if flag15:
+        print("OK!")
+    print("------------------------------------------------------------------")
+
+    print("------------------------------------------------------------------")
+    print("TEST 16 : Logo")
+    print("--------------")
+    flag16 = True
+    session = requests.Session()
+    session.post(local_address + 'change-max', data={"change_max_i": 4, "change_max_j": 5})
+    program = "; a square\nTO SQUARE\n  REPEAT 4 [FD 2 RT 90]\nEND\nPD SQUARE PU\nBK 1 lt 90 fd 1"
+    r = session.post(local_address + 'user-code', data={"user_code": program, "dialect": "logo"})
+    if r.status_code != 200:
+        print(f"Logo program failed: {r.status_code}")
+        flag16 = False
+    r = session.get(local_address + 'code', headers=text_headers)
+    if r.text.splitlines() != ["***..", "*.*..", "***..", "....R"]:
+        print(f"Wrong grid after the Logo program:\n{r.text}")
+        flag16 = False
+    if '<option value="logo" selected>Logo</option>' not in session.get(local_address + 'code').text:
+        print("Logo dialect not selected in code mode")
+        flag16 = False
+    for code, message in [
+        ("RT 45", "line 1: angle 45 is not a multiple of 90 degrees"),
+        ("REPEAT 2 [FD 1", "line 1: `[` without `]`"),
+        ("TO SQUARE :SIZE\nFD :SIZE\nEND", "line 1: procedures with inputs are not supported"),
+        ("FD 1\nSPIRAL", "line 2: unknown procedure `spiral`"),
+        ("REPEAT 1 [" * 900 + "FD 1" + "]" * 900, "line 1: blocks nested too deeply (max 32)"),
+        ("TO P\n" + "REPEAT 1 [\n" * 32 + "FD 1" + "]" * 32 + "\nEND", "line 33: blocks nested too deeply (max 32)"),
+    ]:
+        r = session.post(local_address + 'user-code', data={"user_code": code, "dialect": "logo"}, params={"format": "fragment"})
+        if r.status_code != 422 or message not in r.text:
+            print(f"Wrong error for {code!r}: {r.status_code} {r.text}")
+            flag16 = False
+
+    if flag16:
+        print("OK!")
+    print("------------------------------------------------------------------")
+
+    print("------------------------------------------------------------------")
+    print("TEST 17 : programs as JSON trees")
+    print("--------------------------------")
+    flag17 = True
+    session = requests.Session()
+    session.post(local_address + 'change-max', data={"change_max_i": 3, "change_max_j": 3})
+    tree = {
+        "version": 1,
+        "procedures": [{"name": "zig", "body": [{"type": "move", "direction": "right"}, {"type": "move", "direction": "down"}]}],
+        "statements": [
+            {"type": "item", "i": 2, "j": 2},
+            {"type": "repeat", "count": 2, "body": [
+                {"type": "if", "condition": {"type": "not", "condition": {"type": "on_item"}},
+                 "then": [{"type": "call", "name": "zig"}], "else": [{"type": "pick"}]},
+            ]},
+        ],
+    }
+    text = "def zig\n    right\n    down\nend\n\nitem (2, 2)\nrepeat 2\n    if not on item\n        zig\n    else\n        pick\n    end\nend\n"
+    r = session.post(local_address + 'ast/to-text', json=tree)
+    if r.status_code != 200 or r.text != text:
+        print(f"Wrong text for the tree: {r.status_code} {r.text!r}")
+        flag17 = False
+    r = session.post(local_address + 'ast/from-text', data={"user_code": text})
+    if r.status_code != 200 or r.json() != tree:
+        print(f"Wrong tree for the text: {r.status_code} {r.text}")
+        flag17 = False
+    r = session.post(local_address + 'user-code/ast', json=tree)
+    if r.status_code != 200 or session.get(local_address + 'code', headers=text_headers).text.splitlines() != ["...", "...", "..R"]:
+        print(f"Tree not run: {r.status_code}")
+        flag17 = False
+    r = session.post(local_address + 'user-code/ast', json=tree)
+    if r.status_code != 200 or session.get(local_address + 'state/export').json()["inventory"] != 2:
+        print(f"Tree not run a second time: {r.status_code}")
+        flag17 = False
+    r = session.get(local_address + 'ast/schema')
+    if r.status_code != 200 or r.json()["$defs"]["statement"]["oneOf"][0]["properties"]["type"] != {"const": "move"}:
+        print(f"Schema not served: {r.status_code}")
+        flag17 = False
+    r = session.post(local_address + 'ast/from-text', data={"user_code": "REPEAT 2 [FD 1]", "dialect": "logo"})
+    if r.json()["statements"] != [{"type": "repeat", "count": 2, "body": [{"type": "move", "direction": "forward"}]}]:
+        print(f"Wrong tree for a Logo program: {r.text}")
+        flag17 = False
+    for body, message in [
+        ({"version": 1, "statements": [{"type": "jump"}]}, "unknown variant `jump`"),
+        ({"version": 1, "statements": [{"type": "call", "name": "zag"}]}, "unknown procedure `zag`"),
+        ({"version": 2, "statements": []}, "unsupported AST version 2"),
+        ({"version": 1, "statements": [{"type": "pen_color", "color": "pink"}]}, "unknown color `pink`"),
+        ({"version": 1, "statements": [{"type": "go_to", "i": 5, "j": 0}]}, "line 1: "),
+    ]:
+        r = session.post(local_address + 'user-code/ast', json=body, params={"format": "fragment"})
+        if r.status_code != 422 or message not in r.text:
+            print(f"Wrong error for {body}: {r.status_code} {r.text}")
+            flag17 = False
+
+    if flag17:
+        print("OK!")
+    print("------------------------------------------------------------------")
+
+    print("------------------------------------------------------------------")
+    print("TEST 18 : formatter")
+    print("-------------------")
+    flag18 = True
+    source = "\n\nGo To(1 ,2)\n\n\nWHILE  not(On Item)\n  RIGHT\n        End\ndef Zig\nPen Color RED\n END\n   Zig\n\n"
+    formatted = "go to (1, 2)\n\nwhile not on item\n    right\nend\ndef Zig\n    pen color red\nend\nZig\n"
+    r = requests.post(local_address + 'format', data={"user_code": source})
+    if r.status_code != 200 or r.text != formatted:
+        print(f"Wrong formatted program: {r.status_code} {r.text!r}")
+        flag18 = False
+    r = requests.post(local_address + 'format', data={"user_code": formatted})
+    if r.text != formatted:
+        print(f"Formatting is not stable: {r.text!r}")
+        flag18 = False
+    for code, dialect, status, message in [
+        ("right\nend", "rustbot", 422, "line 2: `end` without a block"),
+        ("move();", "karel", 422, "Only RustBot programs can be formatted, not Karel"),
+        ("right", "cobol", 400, "unknown dialect `cobol`"),
+    ]:
+        r = requests.post(local_address + 'format', data={"user_code": code, "dialect": dialect})
+        if r.status_code != status or message not in r.text:
+            print(f"Wrong error for {code!r}: {r.status_code} {r.text}")
+            flag18 = False
+
+    if flag18:
+        print("OK!")
+    print("------------------------------------------------------------------")
+
+    print("TEST 19 : warnings")
+    print("------------------")
+    flag19 = True
+    session = requests.Session()
+    session.post(local_address + 'change-max', data={"change_max_i": 3, "change_max_j": 3})
+    code = "def unused\n    down\nend\nright\nleft\nif on item\n    go to (5, 0)\nend\nright\n"
+    r = session.post(local_address + 'user-code', data={"user_code": code}, params={"format": "fragment"})
+    for warning in [
+        "line 1: procedure `unused` is never called",
+        "line 5: `right` then `left` cancel out",
+        "line 7: `go to (5, 0)` is outside of the 3x3 grid",
+    ]:
+        if r.status_code != 200 or warning not in r.text:
+            print(f"Missing warning {warning!r}: {r.status_code} {r.text}")
+            flag19 = False
+    if session.get(local_address + 'code', headers=text_headers).text.splitlines() != [".R.", "...", "..."]:
+        print("Warnings should not prevent the program from running")
+        flag19 = False
+    code = "nb lines = 0\nnb lines = 3\nwhile front is clear or not front is clear\n    right\nend\nright\n"
+    r = session.post(local_address + 'user-code', data={"user_code": code})
+    for warning in [
+        "line 1: `nb lines = 0` makes an empty grid",
+        "line 3: this loop never ends, its condition always holds",
+        "line 6: unreachable code after a loop that never ends",
+    ]:
+        if r.status_code != 422 or warning not in r.text:
+            print(f"Missing warning {warning!r} on the page: {r.status_code}")
+            flag19 = False
+    r = session.post(local_address + 'user-code', data={"user_code": "right\ndown"}, params={"format": "fragment"})
+    if r.text.strip() != '<p id="code-error"></p>':
+        print(f"No warning expected: {r.text}")
+        flag19 = False
+
+    if flag19:
+        print("OK!")
+    print("------------------------------------------------------------------")
+
+    print("TEST 20 : shortest programs")
+    print("---------------------------")
+    flag20 = True
+    session = requests.Session()
+    session.post(local_address + 'change-max', data={"change_max_i": 5, "change_max_j": 5})
+    r = session.post(local_address + 'optimize', data={"user_code": "repeat 7\n    right\nend\nturn left\nturn left\nturn left"})
+    expected = {"steps": 10, "i": 0, "j": 2, "heading": "south", "program": "right\nright\nturn right\n", "optimal_steps": 3, "equivalent": True}
+    if r.status_code != 200 or r.json() != expected:
+        print(f"Wrong optimum: {r.status_code} {r.text}")
+        flag20 = False
+    if session.get(local_address + 'code', headers=text_headers).text.splitlines()[0] != "R....":
+        print("Optimizing should not move the robot")
+        flag20 = False
+    session.post(local_address + 'user-code', data={"user_code": "wall (0, 1)"})
+    r = session.post(local_address + 'optimize', data={"user_code": "down\nright\nright\nup"})
+    if r.json()["program"] != "left\nleft\nleft\n":
+        print(f"Walls should be walked around the other way: {r.text}")
+        flag20 = False
+    r = session.post(local_address + 'optimize', data={"user_code": "pen down\nup\ndown"})
+    if r.json()["program"] != "" or r.json()["equivalent"]:
+        print(f"Painting programs are not equivalent to moves: {r.text}")
+        flag20 = False
+    r = session.post(local_address + 'optimize', data={"user_code": "RT 90 REPEAT 3 [FD 1] RT 90", "dialect": "logo"})
+    if r.json()["program"] != "up\nup\nturn right\nturn right\n":
+        print(f"Wrong optimum for a Logo program: {r.text}")
+        flag20 = False
+    for code, dialect, status, message in [
+        ("go to (9, 9)", "rustbot", 422, "line 1: (9, 9) is outside the 5x5 grid"),
+        ("right", "cobol", 400, "unknown dialect `cobol`"),
+    ]:
+        r = session.post(local_address + 'optimize', data={"user_code": code, "dialect": dialect})
+        if r.status_code != status or message not in r.text:
+            print(f"Wrong error for {code!r}: {r.status_code} {r.text}")
+            flag20 = False
+
+    if flag20:
+        print("OK!")
+    print("------------------------------------------------------------------")
+
+    print("TEST 21 : highlighting and completion")
+    print("-------------------------------------")
+    flag21 = True
+    source = "def zig\n    right\nend\nif not on item and fr\n    zig\nend\npen color red\n#"
+    r = requests.post(local_address + 'lang/tokens', data={"user_code": source})
+    kinds = [token["kind"] for token in r.json()] if r.status_code == 200 else []
+    expected = ["keyword", "procedure", "command", "keyword", "keyword", "keyword", "condition", "condition",
+                "keyword", "unknown", "procedure", "keyword", "command", "command", "value", "error"]
+    if kinds != expected:
+        print(f"Wrong kinds of tokens: {r.status_code} {kinds}")
+        flag21 = False
+    if r.status_code == 200 and r.json()[1] != {"line": 1, "start": 4, "end": 7, "kind": "procedure"}:
+        print(f"Wrong span: {r.json()[1]}")
+        flag21 = False
+    for offset, completions in [
+        (source.index(" fr") + 3, [{"text": "front is clear", "start": source.index(" fr") + 1, "kind": "condition"}]),
+        (source.index("on item") + 9, [{"text": "and", "start": source.index(" and") + 1, "kind": "keyword"}]),
+        (source.index("right") + 1, [{"text": "right", "start": source.index("right"), "kind": "command"},
+                                     {"text": "repeat", "start": source.index("right"), "kind": "keyword"}]),
+        (source.index("    zig") + 5, [{"text": "zig", "start": source.index("    zig") + 4, "kind": "procedure"}]),
+    ]:
+        r = requests.post(local_address + 'lang/complete', data={"user_code": source, "offset": offset})
+        if r.status_code != 200 or r.json() != completions:
+            print(f"Wrong completions at {offset}: {r.status_code} {r.text}")
+            flag21 = False
+    r = requests.post(local_address + 'lang/complete', data={"user_code": "pen c", "offset": 5})
+    if [completion["text"] for completion in r.json()][:2] != ["pen color black", "pen color white"]:
+        print(f"Wrong completions of colors: {r.text}")
+        flag21 = False
+    r = requests.post(local_address + 'lang/complete', data={"user_code": "right"})
+    if r.status_code != 422:
+        print(f"A completion without offset should return 422, not {r.status_code}")
+        flag21 = False
+    for url, data in [("lang/tokens", {"user_code": "move();", "dialect": "karel"}),
+                      ("lang/complete", {"user_code": "FD", "offset": 2, "dialect": "logo"}),
+                      ("lang/tokens", {"user_code": "right", "dialect": "cobol"})]:
+        r = requests.post(local_address + url, data=data)
+        if r.status_code != 400:
+            print(f"{url} with the {data['dialect']} dialect should return 400, not {r.status_code}")
+            flag21 = False
+    r = requests.post(local_address + 'lang/tokens', data={"user_code": "right", "dialect": "rustbot"})
+    if r.status_code != 200:
+        print(f"lang/tokens with the rustbot dialect failed: {r.status_code}")
+        flag21 = False
+    r = requests.get(local_address + 'code')
+    if 'id="code-highlight"' not in r.text or 'id="completions"' not in r.text:
+        print("The code page should have a highlight layer and a list of completions")
+        flag21 = False
+
+    if flag21:
+        print("OK!")
+    print("------------------------------------------------------------------")
+
+    print("------------------------------------------------------------------")
+    print("TEST 22 : readiness while shutting down")
+    print("---------------------------------------")
+    flag22 = True
+    draining = subprocess.Popen(
+        ["cargo", "run", "-q", "--", "--port", "3001", "--drain-delay", "2"],
+        stdout=subprocess.DEVNULL,
+        stderr=subprocess.DEVNULL,
+    )
+    draining_address = local_address.replace("3000", "3001")
+    for _ in range(60):
+        try:
+            r = requests.get(draining_address + 'readyz')
+            break
+        except requests.exceptions.ConnectionError:
+            time.sleep(0.5)
+    if r.status_code != 200:
+        print(f"/readyz should answer 200 before shutting down, not {r.status_code}")
+        flag22 = False
+    draining.terminate()
+    time.sleep(0.5)
+    try:
+        r = requests.get(draining_address + 'readyz')
+        if r.status_code != 503:
+            print(f"/readyz should answer 503 while draining, not {r.status_code}")
+            flag22 = False
+    except requests.exceptions.ConnectionError:
+        print("The server should keep answering during the drain delay")
+        flag22 = False
+    if draining.wait(timeout=10) != 0:
+        print("The server should exit after the drain delay")
+        flag22 = False
+
+    if flag22:
+        print("OK!")
+    print("------------------------------------------------------------------")
+
+    print("------------------------------------------------------------------")
+    print("TEST 23 : invalid configuration")
+    print("-------------------------------")
+    flag23 = True
+    for flag, message in [
+        ("--max-steps", "invalid configuration: max_steps must be at least 1"),
+        ("--max-program-len", "invalid configuration: max_program_len must be at least 1"),
+    ]:
+        result = subprocess.run(["cargo", "run", "-q", "--", "--port", "3002", flag, "0"],
+                                capture_output=True, text=True, timeout=60)
+        if result.returncode == 0 or message not in result.stderr:
+            print(f"The server should refuse {flag} 0: {result.returncode} {result.stderr}")
+            flag23 = False
+
+    if flag23:
+        print("OK!")
+    print("------------------------------------------------------------------")
+
+    return flag1 and flag2 and flag3 and flag4 and flag5 and flag6 and flag7 and flag8 and flag9 and flag10 and flag11 and flag12 and flag13 and flag14 and flag15 and flag16 and flag17 and flag18 and flag19 and flag20 and flag21 and flag22 and flag23
 
 def wait_for_server(timeout=30):
     start = time.time()
@@ -0,0 +1,78 @@
+//! Languages code mode programs can be written in.
+
+use std::fmt;
+use std::str::FromStr;
+
+use crate::interpreter::ProgramError;
+use crate::parser::Program;
+use crate::{karel, logo, parser};
+
+/// A language of code mode programs. Every dialect is parsed into the same
+/// `Program`, run by the same interpreter.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Dialect {
+    /// The code mode language (see `parser`)
+    #[default]
+    RustBot,
+    /// Karel the Robot (see `karel`)
+    Karel,
+    /// Turtle commands of Logo (see `logo`)
+    Logo,
+}
+
+impl Dialect {
+    /// Every dialect, the default one first.
+    pub const ALL: [Dialect; 3] = [Dialect::RustBot, Dialect::Karel, Dialect::Logo];
+
+    /// Name of the dialect, as used in forms and on the command line.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Dialect::RustBot => "rustbot",
+            Dialect::Karel => "karel",
+            Dialect::Logo => "logo",
+        }
+    }
+
+    /// Name of the dialect for people.
+    pub fn label(&self) -> &'static str {
+        match self {
+            Dialect::RustBot => "RustBot",
+            Dialect::Karel => "Karel",
+            Dialect::Logo => "Logo",
+        }
+    }
+
+    /// Parses the source code of a program written in this dialect.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `ProgramError` of kind `Syntax` if the program is not valid.
+    pub fn parse(&self, source: &str) -> Result<Program, ProgramError> {
+        match self {
+            Dialect::RustBot => parser::parse(source),
+            Dialect::Karel => karel::parse(source),
+            Dialect::Logo => logo::parse(source),
+        }
+    }
+}
+
+impl fmt::Display for Dialect {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// Reads a dialect from its name.
+impl FromStr for Dialect {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        Dialect::ALL
+            .into_iter()
+            .find(|dialect| dialect.name() == name)
+            .ok_or_else(|| {
+                let names: Vec<_> = Dialect::ALL.iter().map(Dialect::name).collect();
+                format!("unknown dialect `{name}` (available: {})", names.join(", "))
+            })
+    }
+}
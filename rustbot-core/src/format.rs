@@ -0,0 +1,155 @@
+//! Formatter of the code mode language.
+//!
+//! The parser tolerates spaces anywhere, so students write the same program
+//! in many ways. `format` writes it the one way the rest of RustBot does:
+//! ```text
+//! Go To(1 ,2)          go to (1, 2)
+//! WHILE  not On Item   while not on item
+//!   RIGHT                  right
+//!      End             end
+//! ```
+//! Every line is written like `Command`'s and `Condition`'s `Display`, words
+//! of the language in lowercase (names of procedures are kept as they are),
+//! blocks indented by four spaces and closed by an `end` at the level of their
+//! first line. Blank lines are kept, but never more than one in a row, nor at
+//! the start or end of the program.
+
+use std::collections::BTreeSet;
+
+use crate::interpreter::{ErrorKind, ProgramError};
+use crate::parser::{self, Line, Token};
+use crate::world::{Color, Heading};
+
+/// Words of the language, written in lowercase (with the names of colors
+/// and headings).
+pub(crate) const WORDS: [&str; 33] = [
+    "if", "else", "while", "repeat", "def", "end", "not", "and", "or", "facing", "on", "item",
+    "carrying", "front", "left", "right", "is", "clear", "up", "down", "go", "to", "wall", "nb",
+    "lines", "columns", "pen", "color", "pick", "drop", "move", "step", "turn",
+];
+
+/// Whether `word` is a word of the language, in any case.
+fn is_language_word(word: &str) -> bool {
+    let word = word.to_lowercase();
+    WORDS.contains(&word.as_str())
+        || Color::ALL.iter().any(|color| color.name() == word)
+        || Heading::ALL.iter().any(|heading| heading.name() == word)
+}
+
+/// Name defined by a `def` line (`def` in any case).
+fn defined_name<'a>(tokens: &[Token<'a>]) -> Option<&'a str> {
+    match tokens {
+        [Token::Word(def), Token::Word(name)] if def.eq_ignore_ascii_case("def") => Some(name),
+        _ => None,
+    }
+}
+
+/// Names of the procedures defined in a program, as written in their `def`
+/// line.
+fn procedures(source: &str) -> BTreeSet<&str> {
+    source
+        .lines()
+        .filter_map(|line| defined_name(&parser::tokenize(line).ok()?))
+        .collect()
+}
+
+/// Rewrites a line with the words of the language in lowercase, keeping the
+/// names of procedures (after `def`, and wherever they are called).
+fn lowercase(line: &str, procedures: &BTreeSet<&str>) -> Result<String, String> {
+    let tokens = parser::tokenize(line)?;
+    let words: Vec<String> = tokens
+        .iter()
+        .map(|token| match token {
+            Token::Word(word) if procedures.contains(word) || !is_language_word(word) => {
+                word.to_string()
+            }
+            Token::Word(word) => word.to_lowercase(),
+            Token::Number(number) => number.to_string(),
+            Token::Symbol(symbol) => symbol.to_string(),
+        })
+        .collect();
+    Ok(words.join(" "))
+}
+
+/// Builds a syntax error.
+fn syntax(line: usize, message: String) -> ProgramError {
+    ProgramError {
+        line,
+        kind: ErrorKind::Syntax(message),
+    }
+}
+
+/// Writes a line the canonical way, without indentation.
+fn canonical(line: &Line) -> String {
+    match line {
+        Line::Blank => String::new(),
+        Line::Command(command) => command.to_string(),
+        Line::If(condition) => format!("if {condition}"),
+        Line::Else => "else".to_string(),
+        Line::While(condition) => format!("while {condition}"),
+        Line::Repeat(count) => format!("repeat {count}"),
+        Line::Def(name) => format!("def {name}"),
+        Line::End => "end".to_string(),
+        Line::Call(name) => name.clone(),
+    }
+}
+
+/// Formats the source code of a program.
+///
+/// # Errors
+///
+/// Returns the `ProgramError` of `parse` if the program is not valid (once its
+/// words are in lowercase): only valid programs can be formatted.
+///
+/// # Example
+/// ```
+/// use rustbot_core::format;
+///
+/// let source = "Go To(1 ,2)\n\n\nWHILE  not(On Item)\n  RIGHT\n      End\n";
+/// let formatted = format(source).unwrap();
+/// assert_eq!(formatted, "go to (1, 2)\n\nwhile not on item\n    right\nend\n");
+/// assert_eq!(format(&formatted).unwrap(), formatted);
+///
+/// // Procedures may be named like commands, in another case
+/// let source = "DEF Right\n    Left\nEND\nRight\nRIGHT";
+/// assert_eq!(format(source).unwrap(), "def Right\n    left\nend\nRight\nright\n");
+/// ```
+pub fn format(source: &str) -> Result<String, ProgramError> {
+    // Same lines, in lowercase, to check the program with the parser's errors
+    let procedures = procedures(source);
+    let mut lowercased = Vec::new();
+    for (index, line) in source.lines().enumerate() {
+        let line = lowercase(line, &procedures).map_err(|message| syntax(index + 1, message))?;
+        lowercased.push(line);
+    }
+    parser::parse(&lowercased.join("\n"))?;
+
+    let mut formatted = String::new();
+    let mut depth = 0;
+    let mut blank = false;
+    for (index, line) in lowercased.iter().enumerate() {
+        let line = parser::parse_line(line).map_err(|message| syntax(index + 1, message))?;
+        if let Line::Blank = line {
+            blank = !formatted.is_empty();
+            continue;
+        }
+        if blank {
+            formatted.push('\n');
+            blank = false;
+        }
+        // `else` and `end` are at the level of the first line of their block
+        if matches!(line, Line::Else | Line::End) {
+            depth -= 1;
+        }
+        formatted.push_str(&"    ".repeat(depth));
+        formatted.push_str(&canonical(&line));
+        formatted.push('\n');
+        if matches!(
+            line,
+            Line::If(_) | Line::Else | Line::While(_) | Line::Repeat(_) | Line::Def(_)
+        ) {
+            depth += 1;
+        }
+    }
+    Ok(formatted)
+}
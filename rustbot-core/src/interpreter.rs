@@ -0,0 +1,356 @@
+//! Execution of code mode programs on a `World`.
+
+use std::fmt;
+
+use crate::parser::{Condition, Program, Statement, StatementKind, parse};
+use crate::world::{Command, Robot, World, WorldError};
+
+/// Maximum number of procedure calls in progress at the same time, so that
+/// endless recursion stops before exhausting the stack.
+pub const MAX_CALL_DEPTH: usize = 100;
+
+/// Maximum number of blocks running at the same time, procedure bodies
+/// included: parsers bound the nesting of each procedure, but recursive calls
+/// stack them.
+pub const MAX_RUNNING_BLOCKS: usize = 400;
+
+/// Limits protecting the interpreter from runaway programs.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Limits {
+    /// Maximum authorized number of lines
+    pub max_lines: u32,
+    /// Maximum authorized number of columns
+    pub max_columns: u32,
+    /// Maximum number of items on a cell, or carried by the robot
+    pub max_items: u32,
+    /// Maximum number of steps of a single program: commands executed,
+    /// conditions checked, `repeat` turns and procedure calls (so that loops
+    /// without any command stop too)
+    pub max_steps: usize,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Limits {
+            max_lines: 20,
+            max_columns: 20,
+            max_items: 999,
+            max_steps: 1_000,
+        }
+    }
+}
+
+/// One executed command and where the robot was after it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Step {
+    /// Line of the command in the source, starting at 1
+    pub line: usize,
+    pub command: Command,
+    /// Position of the robot after the command
+    pub robot: Robot,
+    /// Grid size (`lines`, `columns`) after the command
+    pub grid: (u32, u32),
+}
+
+/// Result of a successful run.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Run {
+    /// Every executed command, in order
+    pub trace: Vec<Step>,
+}
+
+impl Run {
+    /// Number of commands executed.
+    pub fn steps(&self) -> usize {
+        self.trace.len()
+    }
+}
+
+/// What went wrong in a program.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ErrorKind {
+    /// The line is not a valid command
+    Syntax(String),
+    /// The command cannot be applied (see `WorldError`)
+    World(WorldError),
+    /// The program takes more than `Limits::max_steps` steps
+    TooManySteps(usize),
+    /// More than `MAX_CALL_DEPTH` procedure calls are in progress
+    TooDeep(usize),
+    /// More than `MAX_RUNNING_BLOCKS` blocks are running
+    TooNested(usize),
+    /// The grid would be bigger than `Limits::max_lines` x `Limits::max_columns`
+    GridTooBig { max_lines: u32, max_columns: u32 },
+    /// A cell would hold, or the robot carry, more than `Limits::max_items` items
+    TooManyItems(u32),
+}
+
+/// Error in a program, with the line it comes from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProgramError {
+    /// Line number in the source, starting at 1
+    pub line: usize,
+    pub kind: ErrorKind,
+}
+
+impl ProgramError {
+    /// Short name of the error, usable as a metric label or a log field.
+    pub fn name(&self) -> &'static str {
+        match &self.kind {
+            ErrorKind::Syntax(_) => "syntax_error",
+            ErrorKind::World(WorldError::OutOfGrid { .. }) => "out_of_grid",
+            ErrorKind::World(WorldError::EmptyGrid) => "empty_grid",
+            ErrorKind::World(WorldError::Wall { .. }) => "wall",
+            ErrorKind::World(WorldError::NoItem { .. }) => "no_item",
+            ErrorKind::World(WorldError::EmptyInventory) => "empty_inventory",
+            ErrorKind::World(WorldError::Edge(_)) => "edge",
+            ErrorKind::TooManySteps(_) => "too_many_steps",
+            ErrorKind::TooDeep(_) => "too_deep",
+            ErrorKind::TooNested(_) => "too_nested",
+            ErrorKind::GridTooBig { .. } => "grid_too_big",
+            ErrorKind::TooManyItems(_) => "too_many_items",
+        }
+    }
+}
+
+impl fmt::Display for ProgramError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: ", self.line)?;
+        match &self.kind {
+            ErrorKind::Syntax(message) => write!(f, "{message}"),
+            ErrorKind::World(error) => write!(f, "{error}"),
+            ErrorKind::TooManySteps(max) => write!(f, "more than {max} steps executed"),
+            ErrorKind::TooDeep(max) => write!(f, "more than {max} nested procedure calls"),
+            ErrorKind::TooNested(max) => write!(f, "more than {max} nested blocks running"),
+            ErrorKind::GridTooBig {
+                max_lines,
+                max_columns,
+            } => write!(f, "the grid can't be bigger than {max_lines}x{max_columns}"),
+            ErrorKind::TooManyItems(max) => {
+                write!(f, "more than {max} items on a cell or carried by the robot")
+            }
+        }
+    }
+}
+
+impl std::error::Error for ProgramError {}
+
+/// Runs a parsed program on a world.
+///
+/// # Errors
+///
+/// Stops at the first failing command and returns a `ProgramError`. The world
+/// then keeps the changes made by the previous commands: run on a clone to get
+/// all-or-nothing behaviour.
+pub fn run(program: &Program, world: &mut World, limits: &Limits) -> Result<Run, ProgramError> {
+    run_observed(program, world, limits, |_, _| {})
+}
+
+/// Runs a parsed program on a world like `run`, calling `observe` after each
+/// executed command with the new step and the world as it is then (to draw
+/// one frame per step, for example).
+///
+/// # Errors
+///
+/// Same as `run`.
+pub fn run_observed(
+    program: &Program,
+    world: &mut World,
+    limits: &Limits,
+    mut observe: impl FnMut(&Step, &World),
+) -> Result<Run, ProgramError> {
+    let mut execution = Execution {
+        program,
+        world,
+        limits,
+        observe: &mut observe,
+        run: Run::default(),
+        steps: 0,
+        depth: 0,
+        blocks: 0,
+    };
+    execution.block(0, &program.statements)?;
+    Ok(execution.run)
+}
+
+/// State of a running program.
+struct Execution<'a> {
+    program: &'a Program,
+    world: &'a mut World,
+    limits: &'a Limits,
+    observe: &'a mut dyn FnMut(&Step, &World),
+    run: Run,
+    /// Steps taken so far
+    steps: usize,
+    /// Procedure calls in progress
+    depth: usize,
+    /// Blocks running, procedure bodies included
+    blocks: usize,
+}
+
+impl Execution<'_> {
+    /// Counts one step, failing once the program takes too many.
+    fn step(&mut self, line: usize) -> Result<(), ProgramError> {
+        if self.steps >= self.limits.max_steps {
+            return Err(ProgramError {
+                line,
+                kind: ErrorKind::TooManySteps(self.limits.max_steps),
+            });
+        }
+        self.steps += 1;
+        Ok(())
+    }
+
+    /// Runs the statements of a block, one level deeper.
+    ///
+    /// # Arguments
+    ///
+    /// * `line` - Line of the statement (or call) running the block.
+    /// * `statements` - The statements of the block.
+    fn block(&mut self, line: usize, statements: &[Statement]) -> Result<(), ProgramError> {
+        if self.blocks >= MAX_RUNNING_BLOCKS {
+            return Err(ProgramError {
+                line,
+                kind: ErrorKind::TooNested(MAX_RUNNING_BLOCKS),
+            });
+        }
+        self.blocks += 1;
+        let result = self.statements(statements);
+        self.blocks -= 1;
+        result
+    }
+
+    /// Runs a list of statements, in order.
+    fn statements(&mut self, statements: &[Statement]) -> Result<(), ProgramError> {
+        for statement in statements {
+            match &statement.kind {
+                StatementKind::Command(command) => self.command(statement.line, *command)?,
+                StatementKind::If {
+                    condition,
+                    then,
+                    otherwise,
+                } => {
+                    if self.check(statement.line, condition)? {
+                        self.block(statement.line, then)?;
+                    } else {
+                        self.block(statement.line, otherwise)?;
+                    }
+                }
+                StatementKind::While { condition, body } => {
+                    while self.check(statement.line, condition)? {
+                        self.block(statement.line, body)?;
+                    }
+                }
+                StatementKind::Repeat { count, body } => {
+                    for _ in 0..*count {
+                        self.step(statement.line)?;
+                        self.block(statement.line, body)?;
+                    }
+                }
+                StatementKind::Call(name) => self.call(statement.line, name)?,
+            }
+        }
+        Ok(())
+    }
+
+    /// Runs a procedure.
+    fn call(&mut self, line: usize, name: &str) -> Result<(), ProgramError> {
+        self.step(line)?;
+        if self.depth >= MAX_CALL_DEPTH {
+            return Err(ProgramError {
+                line,
+                kind: ErrorKind::TooDeep(MAX_CALL_DEPTH),
+            });
+        }
+        let program = self.program;
+        let Some(procedure) = program.procedures.get(name) else {
+            return Err(ProgramError {
+                line,
+                kind: ErrorKind::Syntax(format!("unknown command: `{name}`")),
+            });
+        };
+        self.depth += 1;
+        let result = self.block(line, &procedure.body);
+        self.depth -= 1;
+        result
+    }
+
+    /// Checks a condition on the world.
+    fn check(&mut self, line: usize, condition: &Condition) -> Result<bool, ProgramError> {
+        self.step(line)?;
+        Ok(holds(condition, self.world))
+    }
+
+    /// Applies a command to the world and records it.
+    fn command(&mut self, line: usize, command: Command) -> Result<(), ProgramError> {
+        let error = |kind| ProgramError { line, kind };
+        self.step(line)?;
+        let limits = self.limits;
+        let too_big = match command {
+            Command::SetLines(lines) => lines > limits.max_lines,
+            Command::SetColumns(columns) => columns > limits.max_columns,
+            _ => false,
+        };
+        if too_big {
+            return Err(error(ErrorKind::GridTooBig {
+                max_lines: limits.max_lines,
+                max_columns: limits.max_columns,
+            }));
+        }
+        let world = &*self.world;
+        let robot = world.robot();
+        let too_many_items = match command {
+            Command::Item(i, j) => world.items_at(i, j) >= limits.max_items,
+            Command::Pick => {
+                world.items_at(robot.i, robot.j) > 0 && world.inventory() >= limits.max_items
+            }
+            Command::Drop => {
+                world.inventory() > 0 && world.items_at(robot.i, robot.j) >= limits.max_items
+            }
+            _ => false,
+        };
+        if too_many_items {
+            return Err(error(ErrorKind::TooManyItems(limits.max_items)));
+        }
+        self.world
+            .apply(command)
+            .map_err(|e| error(ErrorKind::World(e)))?;
+        let step = Step {
+            line,
+            command,
+            robot: self.world.robot(),
+            grid: (self.world.lines(), self.world.columns()),
+        };
+        (self.observe)(&step, self.world);
+        self.run.trace.push(step);
+        Ok(())
+    }
+}
+
+/// Whether a condition holds on the world.
+fn holds(condition: &Condition, world: &World) -> bool {
+    match condition {
+        Condition::OnItem => {
+            let robot = world.robot();
+            world.items_at(robot.i, robot.j) > 0
+        }
+        Condition::CarryingItem => world.inventory() > 0,
+        Condition::FrontIsClear => world.is_clear(world.heading()),
+        Condition::LeftIsClear => world.is_clear(world.heading().left()),
+        Condition::RightIsClear => world.is_clear(world.heading().right()),
+        Condition::Facing(heading) => world.heading() == *heading,
+        Condition::Not(condition) => !holds(condition, world),
+        Condition::And(left, right) => holds(left, world) && holds(right, world),
+        Condition::Or(left, right) => holds(left, world) || holds(right, world),
+    }
+}
+
+/// Parses then runs the source code of a program.
+///
+/// # Errors
+///
+/// Returns the syntax error of `parse`, or the execution error of `run`.
+pub fn run_source(source: &str, world: &mut World, limits: &Limits) -> Result<Run, ProgramError> {
+    let program = parse(source)?;
+    run(&program, world, limits)
+}
@@ -0,0 +1,754 @@
+//! Karel front-end: programs and world files written for Karel the Robot.
+//!
+//! Programs use the syntax of Karel exercise banks (Stanford Karel in Java,
+//! CodeHS in JavaScript) and are compiled into the same `Program` as the code
+//! mode language, so they run on the same interpreter:
+//! ```text
+//! function start() {
+//!     while (frontIsClear()) {
+//!         if (beepersPresent()) {
+//!             pickBeeper();
+//!         }
+//!         move();
+//!     }
+//!     turnAround();
+//! }
+//!
+//! function turnAround() {
+//!     turnLeft();
+//!     turnLeft();
+//! }
+//! ```
+//! Functions are declared with `function name()` or `void name()` (with any
+//! `public`/`private` modifier, optionally inside a `class` whose `import`s
+//! are ignored). If there are no statements outside of functions, the program
+//! starts with `start()`, `run()` or `main()`. Loops are `while (condition)`,
+//! `repeat (n)` and `for (var i = 0; i < n; i++)`, conditions can be combined
+//! with `!`, `&&` and `||`, and comments are `// ...` or `/* ... */`.
+//!
+//! Built-in functions are `move()`, `turnLeft()`, `turnRight()`,
+//! `turnAround()`, `putBeeper()` and `pickBeeper()`, and conditions are
+//! `frontIsClear()`, `leftIsClear()`, `rightIsClear()` (and their
+//! `...IsBlocked()` opposites), `beepersPresent()`, `noBeepersPresent()`,
+//! `beepersInBag()`, `noBeepersInBag()`, `facingNorth()` (and the other
+//! directions) and `notFacingNorth()`. CodeHS names (`putBall()`,
+//! `takeBall()`, `ballsPresent()`, `noBallsPresent()`) work too.
+//!
+//! Beepers are items of the grid. As in Karel, and unlike the `move` of code
+//! mode, `move()` doesn't wrap around the edges of the grid: moving into an
+//! edge or a wall is an error.
+
+use std::fmt;
+
+use crate::interpreter::{ErrorKind, Limits, ProgramError};
+use crate::parser::{
+    Condition, MAX_BLOCK_DEPTH, MAX_CONDITION_DEPTH, Procedure, Program, Statement, StatementKind,
+    unknown_call,
+};
+use crate::world::{Color, Command, Heading, Robot, World};
+
+/// Functions run when a program has no statement outside of functions.
+const ENTRY_POINTS: [&str; 3] = ["start", "run", "main"];
+
+/// A token of a Karel program.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Token<'a> {
+    Word(&'a str),
+    Number(u32),
+    /// Punctuation and operators: `(`, `{`, `;`, `&&`, `++`...
+    Symbol(&'a str),
+}
+
+/// Splits a program into tokens, each with its line number, skipping spaces
+/// and comments.
+///
+/// # Errors
+///
+/// Returns a `ProgramError` for a number that doesn't fit in a `u32` or an
+/// unclosed comment.
+fn tokenize(source: &str) -> Result<Vec<(usize, Token<'_>)>, ProgramError> {
+    let mut tokens = Vec::new();
+    let mut line = 1;
+    let mut rest = source;
+    while let Some(c) = rest.chars().next() {
+        let (token, length) = if c == '\n' {
+            line += 1;
+            (None, 1)
+        } else if c.is_whitespace() {
+            (None, c.len_utf8())
+        } else if rest.starts_with("//") {
+            (None, rest.find('\n').unwrap_or(rest.len()))
+        } else if rest.starts_with("/*") {
+            let end = rest
+                .find("*/")
+                .ok_or_else(|| syntax(line, "unclosed comment"))?;
+            line += rest[..end].matches('\n').count();
+            (None, end + 2)
+        } else if c.is_alphanumeric() || c == '_' {
+            let length = rest
+                .find(|c: char| !(c.is_alphanumeric() || c == '_'))
+                .unwrap_or(rest.len());
+            let text = &rest[..length];
+            let token = if text.chars().all(|c| c.is_ascii_digit()) {
+                let number = text
+                    .parse()
+                    .map_err(|_| syntax(line, &format!("number {text} is too big")))?;
+                Token::Number(number)
+            } else {
+                Token::Word(text)
+            };
+            (Some(token), length)
+        } else {
+            let length = match &rest[..rest.len().min(2)] {
+                "&&" | "||" | "++" | "+=" | "<=" => 2,
+                _ => c.len_utf8(),
+            };
+            (Some(Token::Symbol(&rest[..length])), length)
+        };
+        if let Some(token) = token {
+            tokens.push((line, token));
+        }
+        rest = &rest[length..];
+    }
+    Ok(tokens)
+}
+
+/// Builds a syntax error.
+fn syntax(line: usize, message: &str) -> ProgramError {
+    ProgramError {
+        line,
+        kind: ErrorKind::Syntax(message.to_string()),
+    }
+}
+
+/// Statement of a built-in function called on `line`, if `name` is one.
+fn builtin(name: &str, line: usize) -> Option<StatementKind> {
+    let command = match name {
+        "move" => Command::Step,
+        "turnLeft" => Command::TurnLeft,
+        "turnRight" => Command::TurnRight,
+        "turnAround" => {
+            let turn = Statement {
+                line,
+                kind: StatementKind::Command(Command::TurnLeft),
+            };
+            return Some(StatementKind::Repeat {
+                count: 2,
+                body: vec![turn],
+            });
+        }
+        "putBeeper" | "putBall" => Command::Drop,
+        "pickBeeper" | "takeBall" => Command::Pick,
+        _ => return None,
+    };
+    Some(StatementKind::Command(command))
+}
+
+/// Condition of a built-in predicate, if `name` is one.
+fn predicate(name: &str) -> Option<Condition> {
+    let not = |condition| Condition::Not(Box::new(condition));
+    let facing = |name: &str| {
+        let heading = name.to_lowercase().parse::<Heading>().ok()?;
+        Some(Condition::Facing(heading))
+    };
+    let condition = match name {
+        "frontIsClear" => Condition::FrontIsClear,
+        "frontIsBlocked" => not(Condition::FrontIsClear),
+        "leftIsClear" => Condition::LeftIsClear,
+        "leftIsBlocked" => not(Condition::LeftIsClear),
+        "rightIsClear" => Condition::RightIsClear,
+        "rightIsBlocked" => not(Condition::RightIsClear),
+        "beepersPresent" | "ballsPresent" => Condition::OnItem,
+        "noBeepersPresent" | "noBallsPresent" => not(Condition::OnItem),
+        "beepersInBag" => Condition::CarryingItem,
+        "noBeepersInBag" => not(Condition::CarryingItem),
+        _ => {
+            if let Some(heading) = name.strip_prefix("notFacing") {
+                return facing(heading).map(not);
+            }
+            return facing(name.strip_prefix("facing")?);
+        }
+    };
+    Some(condition)
+}
+
+/// Recursive descent parser over the tokens of a Karel program.
+struct Parser<'a> {
+    tokens: Vec<(usize, Token<'a>)>,
+    position: usize,
+    /// Line of the last token, for errors at the end of the program
+    last_line: usize,
+    /// Number of `!` and `(` around the current token of a condition
+    depth: usize,
+    /// Number of blocks (bodies of functions and statements) around the
+    /// current token
+    blocks: usize,
+}
+
+impl<'a> Parser<'a> {
+    /// Next token, without consuming it.
+    fn peek(&self) -> Option<Token<'a>> {
+        self.tokens.get(self.position).map(|&(_, token)| token)
+    }
+
+    /// Line of the next token (or of the last one at the end).
+    fn line(&self) -> usize {
+        self.tokens
+            .get(self.position)
+            .map_or(self.last_line, |&(line, _)| line)
+    }
+
+    /// Consumes the next token.
+    fn next(&mut self) -> Option<Token<'a>> {
+        let token = self.peek();
+        self.position += 1;
+        token
+    }
+
+    /// Consumes the next token if it is `token`.
+    fn eat(&mut self, token: Token<'_>) -> bool {
+        if self.peek() == Some(token) {
+            self.position += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Consumes the next token, which must be `token`.
+    fn expect(&mut self, token: Token<'_>) -> Result<(), ProgramError> {
+        if self.eat(token) {
+            return Ok(());
+        }
+        let expected = match token {
+            Token::Word(text) | Token::Symbol(text) => text.to_string(),
+            Token::Number(number) => number.to_string(),
+        };
+        Err(self.unexpected(&format!("`{expected}`")))
+    }
+
+    /// Error for the next token, which is not what was `expected`.
+    fn unexpected(&self, expected: &str) -> ProgramError {
+        let found = match self.peek() {
+            Some(Token::Word(text) | Token::Symbol(text)) => format!("`{text}`"),
+            Some(Token::Number(number)) => format!("`{number}`"),
+            None => "the end of the program".to_string(),
+        };
+        syntax(self.line(), &format!("expected {expected}, found {found}"))
+    }
+
+    /// Consumes a name.
+    fn name(&mut self) -> Result<&'a str, ProgramError> {
+        match self.peek() {
+            Some(Token::Word(name)) => {
+                self.position += 1;
+                Ok(name)
+            }
+            _ => Err(self.unexpected("a name")),
+        }
+    }
+
+    /// Consumes a number.
+    fn number(&mut self) -> Result<u32, ProgramError> {
+        match self.peek() {
+            Some(Token::Number(number)) => {
+                self.position += 1;
+                Ok(number)
+            }
+            _ => Err(self.unexpected("a number")),
+        }
+    }
+
+    /// Parses the whole program: imports, classes, functions and statements.
+    fn program(&mut self) -> Result<Program, ProgramError> {
+        let mut program = Program::default();
+        let mut depth = 0;
+        while let Some(token) = self.peek() {
+            match token {
+                Token::Word("import") => {
+                    while !matches!(self.next(), Some(Token::Symbol(";")) | None) {}
+                }
+                Token::Word("public" | "private" | "protected" | "static" | "final") => {
+                    self.position += 1;
+                }
+                Token::Word("class") => {
+                    self.position += 1;
+                    self.name()?;
+                    if self.eat(Token::Word("extends")) {
+                        self.name()?;
+                    }
+                    self.expect(Token::Symbol("{"))?;
+                    depth += 1;
+                }
+                Token::Symbol("}") if depth > 0 => {
+                    self.position += 1;
+                    depth -= 1;
+                }
+                Token::Word("function" | "void") => {
+                    let line = self.line();
+                    self.position += 1;
+                    let name = self.name()?;
+                    if builtin(name, line).is_some() || predicate(name).is_some() {
+                        return Err(syntax(line, &format!("`{name}` is a built-in function")));
+                    }
+                    if program.procedures.contains_key(name) {
+                        return Err(syntax(
+                            line,
+                            &format!("function `{name}` is already defined"),
+                        ));
+                    }
+                    self.expect(Token::Symbol("("))?;
+                    self.expect(Token::Symbol(")"))?;
+                    let body = self.block()?;
+                    program
+                        .procedures
+                        .insert(name.to_string(), Procedure { line, body });
+                }
+                _ => {
+                    let statement = self.statement()?;
+                    program.statements.extend(statement);
+                }
+            }
+        }
+        if depth > 0 {
+            return Err(syntax(
+                self.last_line,
+                "missing `}` at the end of the class",
+            ));
+        }
+
+        if program.statements.is_empty()
+            && let Some((name, procedure)) = ENTRY_POINTS
+                .iter()
+                .find_map(|&name| Some((name, program.procedures.get(name)?)))
+        {
+            program.statements.push(Statement {
+                line: procedure.line,
+                kind: StatementKind::Call(name.to_string()),
+            });
+        }
+        if let Some((line, name)) = unknown_call(&program) {
+            return Err(syntax(line, &format!("unknown function `{name}()`")));
+        }
+        Ok(program)
+    }
+
+    /// Parses a block: statements between braces, or a single statement.
+    fn block(&mut self) -> Result<Vec<Statement>, ProgramError> {
+        if self.blocks == MAX_BLOCK_DEPTH {
+            return Err(syntax(
+                self.line(),
+                &format!("blocks nested too deeply (max {MAX_BLOCK_DEPTH})"),
+            ));
+        }
+        self.blocks += 1;
+        let statements = self.block_statements();
+        self.blocks -= 1;
+        statements
+    }
+
+    /// Statements of a block, one level deeper.
+    fn block_statements(&mut self) -> Result<Vec<Statement>, ProgramError> {
+        if !self.eat(Token::Symbol("{")) {
+            return Ok(self.statement()?.into_iter().collect());
+        }
+        let mut statements = Vec::new();
+        while !self.eat(Token::Symbol("}")) {
+            if self.peek().is_none() {
+                return Err(self.unexpected("`}`"));
+            }
+            statements.extend(self.statement()?);
+        }
+        Ok(statements)
+    }
+
+    /// Parses a statement (`None` for an empty one, a lone `;`).
+    fn statement(&mut self) -> Result<Option<Statement>, ProgramError> {
+        let line = self.line();
+        let kind = match self.next() {
+            Some(Token::Symbol(";")) => return Ok(None),
+            Some(Token::Word("while")) => {
+                let condition = self.parenthesized_condition()?;
+                let body = self.block()?;
+                StatementKind::While { condition, body }
+            }
+            Some(Token::Word("if")) => {
+                let condition = self.parenthesized_condition()?;
+                let then = self.block()?;
+                let otherwise = if self.eat(Token::Word("else")) {
+                    self.block()?
+                } else {
+                    Vec::new()
+                };
+                StatementKind::If {
+                    condition,
+                    then,
+                    otherwise,
+                }
+            }
+            Some(Token::Word("repeat")) => {
+                self.expect(Token::Symbol("("))?;
+                let count = self.number()?;
+                self.expect(Token::Symbol(")"))?;
+                let body = self.block()?;
+                StatementKind::Repeat { count, body }
+            }
+            Some(Token::Word("for")) => {
+                let count = self.for_header()?;
+                let body = self.block()?;
+                StatementKind::Repeat { count, body }
+            }
+            Some(Token::Word(name)) => {
+                self.expect(Token::Symbol("("))?;
+                self.expect(Token::Symbol(")"))?;
+                self.expect(Token::Symbol(";"))?;
+                match builtin(name, line) {
+                    Some(kind) => kind,
+                    None if predicate(name).is_some() => {
+                        return Err(syntax(
+                            line,
+                            &format!("`{name}()` is a condition, use it in `if` or `while`"),
+                        ));
+                    }
+                    None => StatementKind::Call(name.to_string()),
+                }
+            }
+            _ => {
+                self.position -= 1;
+                return Err(self.unexpected("a statement"));
+            }
+        };
+        Ok(Some(Statement { line, kind }))
+    }
+
+    /// Parses `(var i = 0; i < n; i++)`, the header of a counting loop.
+    ///
+    /// # Returns
+    ///
+    /// The number of turns, `n`.
+    fn for_header(&mut self) -> Result<u32, ProgramError> {
+        self.expect(Token::Symbol("("))?;
+        if matches!(self.peek(), Some(Token::Word("var" | "let" | "int"))) {
+            self.position += 1;
+        }
+        let variable = self.name()?;
+        self.expect(Token::Symbol("="))?;
+        self.expect(Token::Number(0))?;
+        self.expect(Token::Symbol(";"))?;
+        self.expect(Token::Word(variable))?;
+        self.expect(Token::Symbol("<"))?;
+        let count = self.number()?;
+        self.expect(Token::Symbol(";"))?;
+        if self.eat(Token::Symbol("++")) {
+            self.expect(Token::Word(variable))?;
+        } else {
+            self.expect(Token::Word(variable))?;
+            if !self.eat(Token::Symbol("++")) {
+                self.expect(Token::Symbol("+="))?;
+                self.expect(Token::Number(1))?;
+            }
+        }
+        self.expect(Token::Symbol(")"))?;
+        Ok(count)
+    }
+
+    /// Parses a condition between parentheses, after `if` or `while`.
+    fn parenthesized_condition(&mut self) -> Result<Condition, ProgramError> {
+        self.expect(Token::Symbol("("))?;
+        let condition = self.or()?;
+        self.expect(Token::Symbol(")"))?;
+        Ok(condition)
+    }
+
+    fn or(&mut self) -> Result<Condition, ProgramError> {
+        let mut condition = self.and()?;
+        while self.eat(Token::Symbol("||")) {
+            condition = Condition::Or(Box::new(condition), Box::new(self.and()?));
+        }
+        Ok(condition)
+    }
+
+    fn and(&mut self) -> Result<Condition, ProgramError> {
+        let mut condition = self.unary()?;
+        while self.eat(Token::Symbol("&&")) {
+            condition = Condition::And(Box::new(condition), Box::new(self.unary()?));
+        }
+        Ok(condition)
+    }
+
+    /// Runs `parse` one level deeper in a condition.
+    fn nested(
+        &mut self,
+        parse: impl FnOnce(&mut Self) -> Result<Condition, ProgramError>,
+    ) -> Result<Condition, ProgramError> {
+        if self.depth == MAX_CONDITION_DEPTH {
+            return Err(syntax(self.line(), "condition nested too deeply"));
+        }
+        self.depth += 1;
+        let condition = parse(self);
+        self.depth -= 1;
+        condition
+    }
+
+    fn unary(&mut self) -> Result<Condition, ProgramError> {
+        if self.eat(Token::Symbol("!")) {
+            return self.nested(|parser| Ok(Condition::Not(Box::new(parser.unary()?))));
+        }
+        if self.eat(Token::Symbol("(")) {
+            return self.nested(|parser| {
+                let condition = parser.or()?;
+                parser.expect(Token::Symbol(")"))?;
+                Ok(condition)
+            });
+        }
+        let line = self.line();
+        let name = self.name()?;
+        let condition = predicate(name)
+            .ok_or_else(|| syntax(line, &format!("unknown condition `{name}()`")))?;
+        self.expect(Token::Symbol("("))?;
+        self.expect(Token::Symbol(")"))?;
+        Ok(condition)
+    }
+}
+
+/// Parses a Karel program into a `Program` of the code mode language.
+///
+/// # Errors
+///
+/// Returns a `ProgramError` of kind `Syntax` for the first token that doesn't
+/// fit the grammar, or for a call to a function that is not defined.
+///
+/// # Example
+/// ```
+/// use rustbot_core::{Limits, World, karel, run};
+///
+/// let program = karel::parse("function start() {\n  move();\n  turnLeft();\n}").unwrap();
+/// let mut world = World::new(3, 3).unwrap();
+/// run(&program, &mut world, &Limits::default()).unwrap();
+/// assert_eq!((world.robot().j, world.heading().name()), (1, "north"));
+/// ```
+pub fn parse(source: &str) -> Result<Program, ProgramError> {
+    let tokens = tokenize(source)?;
+    let last_line = tokens.last().map_or(1, |&(line, _)| line);
+    Parser {
+        tokens,
+        position: 0,
+        last_line,
+        depth: 0,
+        blocks: 0,
+    }
+    .program()
+}
+
+/// Number of items carried by the robot for an `infinite` beeper bag.
+pub const INFINITE_BAG: u32 = 999;
+
+/// A world read from a Karel world file, with what could not be imported.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KarelWorld {
+    pub world: World,
+    /// Lines of the file that were ignored, with the reason
+    pub warnings: Vec<String>,
+}
+
+/// Error returned when a Karel world file cannot be read.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WorldFileError {
+    /// Line number in the file, starting at 1
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for WorldFileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for WorldFileError {}
+
+/// Reads `(x, y)` followed by optional words.
+fn coordinates(value: &str) -> Option<((u32, u32), Vec<&str>)> {
+    let (point, rest) = value.trim().strip_prefix('(')?.split_once(')')?;
+    let (x, y) = point.split_once(',')?;
+    let point = (x.trim().parse().ok()?, y.trim().parse().ok()?);
+    Some((point, rest.split_whitespace().collect()))
+}
+
+/// Reads a Karel world file (the `.w` format of Stanford Karel):
+/// ```text
+/// Dimension: (5, 3)
+/// Karel: (1, 1) east
+/// Beeper: (3, 1) 2
+/// BeeperBag: infinite
+/// Color: (2, 2) red
+/// ```
+/// Karel counts avenues (`x`, columns) and streets (`y`, lines) from 1, with
+/// street 1 at the bottom: `(1, 1)` is the bottom left cell of the grid.
+/// Beepers become items, the beeper bag the inventory of the robot (an
+/// `infinite` bag holds `INFINITE_BAG` items, or `max_items` if fewer) and
+/// colored corners painted
+/// cells. Walls of Karel worlds stand between cells, which RustBot grids can't
+/// hold: they are ignored, with a warning, like unknown lines.
+///
+/// # Arguments
+///
+/// * `text` - Content of the file.
+/// * `limits` - Maximum grid size and numbers of items.
+///
+/// # Errors
+///
+/// Returns a `WorldFileError` if the file has no `Dimension` first, a grid or
+/// numbers of beepers bigger than the limits, or a malformed or out of grid
+/// position.
+///
+/// # Example
+/// ```
+/// use rustbot_core::{Limits, karel, render};
+///
+/// let text = "Dimension: (3, 2)\nKarel: (1, 1) north\nBeeper: (3, 2) 2\nWall: (1, 1) east";
+/// let imported = karel::parse_world(text, &Limits::default()).unwrap();
+/// assert_eq!(render::ascii(&imported.world), "..2\nR..\n");
+/// assert_eq!(imported.world.heading().name(), "north");
+/// assert_eq!(imported.warnings.len(), 1);
+/// ```
+pub fn parse_world(text: &str, limits: &Limits) -> Result<KarelWorld, WorldFileError> {
+    let mut world: Option<World> = None;
+    let mut robot = None;
+    let mut warnings = Vec::new();
+    let mut last_line = 0;
+    for (index, line) in text.lines().enumerate() {
+        let line_number = index + 1;
+        last_line = line_number;
+        let error = |message: String| WorldFileError {
+            line: line_number,
+            message,
+        };
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        let Some((key, value)) = line.split_once(':') else {
+            warnings.push(format!("line {line_number}: ignored `{line}`"));
+            continue;
+        };
+        let key = key.trim().to_lowercase();
+        match key.as_str() {
+            "dimension" => {
+                let ((columns, lines), _) = coordinates(value)
+                    .ok_or_else(|| error("expected `Dimension: (avenues, streets)`".into()))?;
+                if lines > limits.max_lines || columns > limits.max_columns {
+                    return Err(error(format!(
+                        "the grid can't be bigger than {}x{}",
+                        limits.max_lines, limits.max_columns
+                    )));
+                }
+                world = Some(World::new(lines, columns).map_err(|e| error(e.to_string()))?);
+                continue;
+            }
+            "speed" => continue,
+            _ => {}
+        }
+        let Some(world) = world.as_mut() else {
+            return Err(error(
+                "the file must start with `Dimension: (avenues, streets)`".into(),
+            ));
+        };
+        // Converts Karel coordinates into a cell of the grid
+        let cell = |(x, y): (u32, u32)| {
+            if x == 0 || y == 0 || x > world.columns() || y > world.lines() {
+                return Err(error(format!(
+                    "({x}, {y}) is outside of the {}x{} world",
+                    world.columns(),
+                    world.lines()
+                )));
+            }
+            Ok((world.lines() - y, x - 1))
+        };
+        let malformed = || error(format!("expected `{}: (x, y)`", key));
+        match key.as_str() {
+            "karel" => {
+                let (point, words) = coordinates(value).ok_or_else(malformed)?;
+                let (i, j) = cell(point)?;
+                let heading = match words.first() {
+                    Some(word) => word.to_lowercase().parse().map_err(error)?,
+                    None => Heading::East,
+                };
+                robot = Some((Robot { i, j }, heading));
+            }
+            "beeper" => {
+                let (point, words) = coordinates(value).ok_or_else(malformed)?;
+                let (i, j) = cell(point)?;
+                let count = match words.first() {
+                    Some(word) => word
+                        .parse()
+                        .map_err(|_| error(format!("`{word}` is not a number of beepers")))?,
+                    None => 1,
+                };
+                world
+                    .put_items(i, j, count)
+                    .map_err(|e| error(e.to_string()))?;
+                // Counts of a corner listed more than once add up
+                if world.items_at(i, j) > limits.max_items {
+                    return Err(error(format!(
+                        "a corner can't hold more than {} beepers",
+                        limits.max_items
+                    )));
+                }
+            }
+            "beeperbag" => {
+                let value = value.trim();
+                let count = if value.eq_ignore_ascii_case("infinite") {
+                    INFINITE_BAG.min(limits.max_items)
+                } else {
+                    value
+                        .parse()
+                        .map_err(|_| error(format!("`{value}` is not a number of beepers")))?
+                };
+                if count > limits.max_items {
+                    return Err(error(format!(
+                        "the beeper bag can't hold more than {} beepers",
+                        limits.max_items
+                    )));
+                }
+                world.set_inventory(count);
+            }
+            "color" => {
+                let (point, words) = coordinates(value).ok_or_else(malformed)?;
+                let (i, j) = cell(point)?;
+                let name = words.first().map(|word| word.to_lowercase());
+                match name.as_deref().map(str::parse::<Color>) {
+                    Some(Ok(color)) => {
+                        world.paint(i, j, color).map_err(|e| error(e.to_string()))?
+                    }
+                    _ => warnings.push(format!("line {line_number}: unknown color, ignored")),
+                }
+            }
+            "wall" => warnings.push(format!(
+                "line {line_number}: walls between cells are not supported, ignored"
+            )),
+            _ => warnings.push(format!("line {line_number}: ignored `{line}`")),
+        }
+    }
+
+    let mut world = world.ok_or_else(|| WorldFileError {
+        line: last_line.max(1),
+        message: "missing `Dimension: (avenues, streets)`".to_string(),
+    })?;
+    if let Some((position, heading)) = robot {
+        // No wall was imported, so the robot can go anywhere in the grid
+        world
+            .apply(Command::GoTo(position.i, position.j))
+            .expect("the robot position was checked");
+        world.set_trail([position]);
+        world.set_heading(heading);
+    } else {
+        // Karel starts at (1, 1), facing east
+        let bottom = world.lines() - 1;
+        world
+            .apply(Command::GoTo(bottom, 0))
+            .expect("the grid is not empty");
+        world.set_trail([Robot { i: bottom, j: 0 }]);
+    }
+    Ok(KarelWorld { world, warnings })
+}
@@ -0,0 +1,332 @@
+//! Editor support for the code mode language: kinds of tokens, to highlight
+//! programs, and completions at the cursor.
+//!
+//! Both read programs with the lexer of the parser, so that editors split
+//! lines exactly like `parse` does. Offsets count characters (not bytes) from
+//! the start of the source, like the cursor of a text field. The language has
+//! no variables: completions are keywords, commands, conditions and the
+//! procedures defined in the program.
+
+use std::collections::BTreeSet;
+use std::fmt;
+
+use crate::format::WORDS;
+use crate::parser::{self, KEYWORDS, Line, Token};
+use crate::world::{Color, Heading};
+
+/// What a token is, for highlighting.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokenKind {
+    /// Word starting or closing a block, or combining conditions (`if`,
+    /// `end`, `not`…)
+    Keyword,
+    /// Word of a command (`go`, `to`, `pen`…)
+    Command,
+    /// Word of a condition (`front`, `is`, `clear`…)
+    Condition,
+    /// Name of a color or a heading
+    Value,
+    Number,
+    /// `(`, `)`, `,`, `;` or `=`
+    Symbol,
+    /// Name of a procedure defined in the program
+    Procedure,
+    /// Word that is neither a word of the language nor a defined procedure
+    Unknown,
+    /// Character that is not part of the language, or a number too big
+    Error,
+}
+
+impl TokenKind {
+    /// Name of the kind, as sent to editors.
+    pub fn name(&self) -> &'static str {
+        match self {
+            TokenKind::Keyword => "keyword",
+            TokenKind::Command => "command",
+            TokenKind::Condition => "condition",
+            TokenKind::Value => "value",
+            TokenKind::Number => "number",
+            TokenKind::Symbol => "symbol",
+            TokenKind::Procedure => "procedure",
+            TokenKind::Unknown => "unknown",
+            TokenKind::Error => "error",
+        }
+    }
+}
+
+impl fmt::Display for TokenKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// A token of a program, and where it is.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TokenSpan {
+    /// Line number in the source, starting at 1
+    pub line: usize,
+    /// Offset of the first character of the token
+    pub start: usize,
+    /// Offset after the last character of the token
+    pub end: usize,
+    pub kind: TokenKind,
+}
+
+/// Text to insert at the cursor.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Completion {
+    /// Text replacing the source from `start` to the cursor
+    pub text: String,
+    /// Offset of the start of the words being completed (the cursor if none)
+    pub start: usize,
+    /// `Keyword`, `Command`, `Condition` or `Procedure`
+    pub kind: TokenKind,
+}
+
+/// Words and symbols combining conditions, after which a new condition starts.
+const CONNECTORS: [&str; 6] = ["if", "while", "not", "and", "or", "("];
+
+/// Names of the procedures defined in a program (by its valid `def` lines).
+fn procedures(source: &str) -> BTreeSet<String> {
+    source
+        .lines()
+        .filter_map(|line| match parser::parse_line(line) {
+            Ok(Line::Def(name)) => Some(name),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Kind of a token, in a condition (`if` and `while` lines) or not.
+fn kind(
+    token: Result<Token<'_>, String>,
+    in_condition: bool,
+    procedures: &BTreeSet<String>,
+) -> TokenKind {
+    let word = match token {
+        Err(_) => return TokenKind::Error,
+        Ok(Token::Number(_)) => return TokenKind::Number,
+        Ok(Token::Symbol('(' | ')' | ',' | ';' | '=')) => return TokenKind::Symbol,
+        Ok(Token::Symbol(_)) => return TokenKind::Error,
+        Ok(Token::Word(word)) => word,
+    };
+    if KEYWORDS.contains(&word) {
+        TokenKind::Keyword
+    } else if Color::ALL.iter().any(|color| color.name() == word)
+        || Heading::ALL.iter().any(|heading| heading.name() == word)
+    {
+        TokenKind::Value
+    } else if WORDS.contains(&word) && in_condition {
+        TokenKind::Condition
+    } else if WORDS.contains(&word) {
+        TokenKind::Command
+    } else if procedures.contains(word) {
+        TokenKind::Procedure
+    } else {
+        TokenKind::Unknown
+    }
+}
+
+/// Splits a program into tokens, with their kind.
+///
+/// Lines are split like `parse` does, even invalid ones, so that programs
+/// being written can be highlighted.
+///
+/// # Example
+/// ```
+/// use rustbot_core::lang::{self, TokenKind};
+///
+/// let tokens = lang::tokens("def zig\n    right\nend\nif not on item\n    zig\nend");
+/// let kinds: Vec<TokenKind> = tokens.iter().map(|token| token.kind).collect();
+/// assert_eq!(kinds[..4], [TokenKind::Keyword, TokenKind::Procedure, TokenKind::Command, TokenKind::Keyword]);
+/// assert_eq!(kinds[6..8], [TokenKind::Condition, TokenKind::Condition]);
+/// assert_eq!((tokens[1].start, tokens[1].end), (4, 7));
+/// ```
+pub fn tokens(source: &str) -> Vec<TokenSpan> {
+    let procedures = procedures(source);
+    let mut tokens = Vec::new();
+    // Offset of the first character of the line
+    let mut offset = 0;
+    for (index, text) in source.split('\n').enumerate() {
+        let spans = parser::token_spans(text);
+        let in_condition = spans
+            .first()
+            .is_some_and(|span| matches!(&text[span.clone()], "if" | "while"));
+        let is_def = spans
+            .first()
+            .is_some_and(|span| &text[span.clone()] == "def");
+        for (position, span) in spans.into_iter().enumerate() {
+            let token = parser::token(&text[span.clone()]);
+            let kind = match token {
+                Ok(Token::Word(_)) if is_def && position == 1 => TokenKind::Procedure,
+                token => kind(token, in_condition, &procedures),
+            };
+            let start = offset + text[..span.start].chars().count();
+            tokens.push(TokenSpan {
+                line: index + 1,
+                start,
+                end: start + text[span].chars().count(),
+                kind,
+            });
+        }
+        offset += text.chars().count() + 1;
+    }
+    tokens
+}
+
+/// Lines (or their start) proposed at the start of a line, with their kind.
+fn statements(procedures: &BTreeSet<String>) -> Vec<(String, TokenKind)> {
+    let mut statements: Vec<(String, TokenKind)> = [
+        "right",
+        "left",
+        "up",
+        "down",
+        "go to (",
+        "wall (",
+        "item (",
+        "nb lines =",
+        "nb columns =",
+        "pen down",
+        "pen up",
+    ]
+    .into_iter()
+    .map(|command| (command.to_string(), TokenKind::Command))
+    .collect();
+    statements.extend(
+        Color::ALL
+            .iter()
+            .map(|color| (format!("pen color {color}"), TokenKind::Command)),
+    );
+    statements.extend(
+        ["pick", "drop", "move", "step", "turn left", "turn right"]
+            .into_iter()
+            .map(|command| (command.to_string(), TokenKind::Command)),
+    );
+    statements.extend(
+        ["if", "else", "while", "repeat", "def", "end"]
+            .into_iter()
+            .map(|keyword| (keyword.to_string(), TokenKind::Keyword)),
+    );
+    statements.extend(
+        procedures
+            .iter()
+            .map(|name| (name.clone(), TokenKind::Procedure)),
+    );
+    statements
+}
+
+/// Conditions proposed after `if`, `while`, `not`, `and`, `or` and `(`.
+fn conditions() -> Vec<(String, TokenKind)> {
+    let mut conditions: Vec<(String, TokenKind)> = [
+        "on item",
+        "carrying item",
+        "front is clear",
+        "left is clear",
+        "right is clear",
+    ]
+    .into_iter()
+    .map(|condition| (condition.to_string(), TokenKind::Condition))
+    .collect();
+    conditions.extend(
+        Heading::ALL
+            .iter()
+            .map(|heading| (format!("facing {heading}"), TokenKind::Condition)),
+    );
+    conditions.push(("not".to_string(), TokenKind::Keyword));
+    conditions
+}
+
+/// Completions at the cursor: the keywords, commands, conditions and
+/// procedures starting with the words typed since the start of the line (or
+/// of the condition), and `and`/`or` after a whole condition.
+///
+/// # Arguments
+///
+/// * `source` - The program being written.
+/// * `offset` - The cursor, in characters from the start of `source` (the
+///   end of the source if past it).
+///
+/// # Example
+/// ```
+/// use rustbot_core::lang;
+///
+/// let source = "def zig\nend\nz\nif fr";
+/// let texts = |offset| -> Vec<String> {
+///     lang::complete(source, offset).into_iter().map(|completion| completion.text).collect()
+/// };
+/// assert_eq!(texts(13), ["zig"]);
+/// assert_eq!(texts(19), ["front is clear"]);
+/// assert_eq!(lang::complete(source, 19)[0].start, 17);
+/// ```
+pub fn complete(source: &str, offset: usize) -> Vec<Completion> {
+    // Line of the cursor, up to the cursor
+    let end = source
+        .char_indices()
+        .nth(offset)
+        .map_or(source.len(), |(index, _)| index);
+    let line_start = source[..end].rfind('\n').map_or(0, |index| index + 1);
+    let line = &source[line_start..end];
+    let spans = parser::token_spans(line);
+    let words: Vec<&str> = spans.iter().map(|span| &line[span.clone()]).collect();
+    let char_offset = |byte: usize| source[..line_start + byte].chars().count();
+    let cursor = char_offset(line.len());
+
+    // Word under the cursor, which is not finished
+    let typing = match (spans.last(), parser::token(words.last().unwrap_or(&""))) {
+        (Some(span), Ok(Token::Word(_))) => span.end == line.len(),
+        _ => false,
+    };
+    let done = &words[..words.len() - usize::from(typing)];
+    let in_condition = matches!(done.first(), Some(&("if" | "while")));
+    // The phrase being typed starts after the last connector of a condition
+    let first = if in_condition {
+        done.iter()
+            .rposition(|word| CONNECTORS.contains(word))
+            .map_or(0, |position| position + 1)
+    } else {
+        0
+    };
+
+    let mut phrase = words[first..].join(" ");
+    if !typing && !phrase.is_empty() {
+        phrase.push(' ');
+    }
+    let start = spans
+        .get(first)
+        .map_or(cursor, |span| char_offset(span.start));
+    let candidates = if in_condition {
+        conditions()
+    } else {
+        statements(&procedures(source))
+    };
+    let mut completions: Vec<Completion> = candidates
+        .into_iter()
+        .filter(|(text, _)| text.starts_with(&phrase) && *text != phrase)
+        .map(|(text, kind)| Completion { text, start, kind })
+        .collect();
+
+    // After a whole condition: `and` or `or`
+    let (head, last) = if typing {
+        (&words[first..words.len() - 1], words[words.len() - 1])
+    } else {
+        (&words[first..], "")
+    };
+    let head = head.join(" ");
+    if in_condition && conditions().iter().any(|(condition, _)| *condition == head) {
+        let start = match spans.last() {
+            Some(span) if typing => char_offset(span.start),
+            _ => cursor,
+        };
+        completions.extend(
+            ["and", "or"]
+                .into_iter()
+                .filter(|connector| connector.starts_with(last) && *connector != last)
+                .map(|connector| Completion {
+                    text: connector.to_string(),
+                    start,
+                    kind: TokenKind::Keyword,
+                }),
+        );
+    }
+    completions
+}
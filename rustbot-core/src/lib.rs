@@ -0,0 +1,37 @@
+//! Core of the RustBot project: a robot moving on a grid, and the small language
+//! used in code mode to drive it.
+//!
+//! This crate has no web dependency, so it can be embedded in other tools.
+//!
+//! # Example
+//! ```
+//! use rustbot_core::{Command, Limits, World, run_source};
+//!
+//! let mut world = World::new(5, 5).unwrap();
+//! world.apply(Command::Left).unwrap();
+//! assert_eq!((world.robot().i, world.robot().j), (0, 4));
+//!
+//! let run = run_source("down\ngo to (2, 3)", &mut world, &Limits::default()).unwrap();
+//! assert_eq!(run.trace.len(), 2);
+//! assert_eq!((world.robot().i, world.robot().j), (2, 3));
+//! ```
+
+pub mod dialect;
+pub mod format;
+pub mod interpreter;
+pub mod karel;
+pub mod lang;
+pub mod lint;
+pub mod logo;
+pub mod optimize;
+pub mod parser;
+pub mod render;
+pub mod world;
+
+pub use dialect::Dialect;
+pub use format::format;
+pub use interpreter::{ErrorKind, Limits, ProgramError, Run, Step, run, run_observed, run_source};
+pub use lint::{Warning, WarningKind, lint};
+pub use optimize::{Optimization, optimize};
+pub use parser::{Condition, Procedure, Program, Statement, StatementKind, parse};
+pub use world::{Color, Command, Heading, Robot, World, WorldError};
@@ -0,0 +1,372 @@
+//! Static analysis of programs: mistakes found without running them.
+//!
+//! Warnings don't stop a program from running: they point at code that is
+//! most likely not what the student meant, even when the run goes well (a
+//! branch that is never taken can still hold a `go to` outside of the grid).
+//! The language has no variables, so there are no unused ones to report.
+
+use std::collections::BTreeSet;
+use std::fmt;
+
+use crate::parser::{Condition, Program, Statement, StatementKind};
+use crate::world::{Command, Heading, World};
+
+/// A warning about a program.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Warning {
+    /// Line number in the source, starting at 1
+    pub line: usize,
+    pub kind: WarningKind,
+}
+
+/// What looks wrong.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WarningKind {
+    /// The condition of a `while` always holds
+    NeverEnds,
+    /// Statements after a loop that never ends
+    Unreachable,
+    /// The condition of an `if` or a `while` never holds
+    NeverHolds,
+    /// Nothing in the body of a `while` can change its condition
+    Stuck,
+    /// A command targets a cell outside of the grid (with its size then)
+    OutOfGrid {
+        command: Command,
+        lines: u32,
+        columns: u32,
+    },
+    /// `nb lines = 0` or `nb columns = 0`
+    EmptyGrid(Command),
+    /// Two commands in a row undoing each other
+    CancelOut(Command, Command),
+    /// A procedure that is never called
+    UnusedProcedure(String),
+}
+
+impl Warning {
+    /// Short name of the warning, usable as a metric label or a log field.
+    pub fn name(&self) -> &'static str {
+        match &self.kind {
+            WarningKind::NeverEnds => "never_ends",
+            WarningKind::Unreachable => "unreachable",
+            WarningKind::NeverHolds => "never_holds",
+            WarningKind::Stuck => "stuck_loop",
+            WarningKind::OutOfGrid { .. } => "out_of_grid",
+            WarningKind::EmptyGrid(_) => "empty_grid",
+            WarningKind::CancelOut(..) => "cancel_out",
+            WarningKind::UnusedProcedure(_) => "unused_procedure",
+        }
+    }
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: ", self.line)?;
+        match &self.kind {
+            WarningKind::NeverEnds => write!(f, "this loop never ends, its condition always holds"),
+            WarningKind::Unreachable => write!(f, "unreachable code after a loop that never ends"),
+            WarningKind::NeverHolds => {
+                write!(f, "this condition never holds, the block never runs")
+            }
+            WarningKind::Stuck => write!(
+                f,
+                "nothing in this loop changes its condition: once entered, it never ends"
+            ),
+            WarningKind::OutOfGrid {
+                command,
+                lines,
+                columns,
+            } => write!(f, "`{command}` is outside of the {lines}x{columns} grid"),
+            WarningKind::EmptyGrid(command) => write!(f, "`{command}` makes an empty grid"),
+            WarningKind::CancelOut(first, second) => {
+                write!(f, "`{first}` then `{second}` cancel out")
+            }
+            WarningKind::UnusedProcedure(name) => write!(f, "procedure `{name}` is never called"),
+        }
+    }
+}
+
+/// What a condition checks, or what a command can change.
+mod facts {
+    pub const ON_ITEM: u8 = 1;
+    pub const CARRYING_ITEM: u8 = 2;
+    /// Whether the cells around the robot are clear
+    pub const CLEAR: u8 = 4;
+    pub const FACING: u8 = 8;
+}
+
+/// Facts a condition depends on.
+fn checked_facts(condition: &Condition) -> u8 {
+    match condition {
+        Condition::OnItem => facts::ON_ITEM,
+        Condition::CarryingItem => facts::CARRYING_ITEM,
+        Condition::FrontIsClear | Condition::LeftIsClear | Condition::RightIsClear => facts::CLEAR,
+        Condition::Facing(_) => facts::FACING,
+        Condition::Not(inner) => checked_facts(inner),
+        Condition::And(left, right) | Condition::Or(left, right) => {
+            checked_facts(left) | checked_facts(right)
+        }
+    }
+}
+
+/// Facts a command can change.
+fn changed_facts(command: &Command) -> u8 {
+    match command {
+        Command::Up
+        | Command::Down
+        | Command::Left
+        | Command::Right
+        | Command::GoTo(..)
+        | Command::Reset
+        | Command::Move
+        | Command::Step
+        | Command::SetLines(_)
+        | Command::SetColumns(_)
+        | Command::Wall(..) => facts::ON_ITEM | facts::CLEAR,
+        Command::TurnLeft | Command::TurnRight => facts::CLEAR | facts::FACING,
+        Command::Item(..) => facts::ON_ITEM,
+        Command::Pick | Command::Drop => facts::ON_ITEM | facts::CARRYING_ITEM,
+        Command::PenDown | Command::PenUp | Command::PenColor(_) => 0,
+    }
+}
+
+/// Value of a condition for one combination of the facts: the bits of
+/// `flags` for on item, carrying item, front, left and right clear.
+fn holds(condition: &Condition, flags: u8, heading: Heading) -> bool {
+    match condition {
+        Condition::OnItem => flags & 1 != 0,
+        Condition::CarryingItem => flags & 2 != 0,
+        Condition::FrontIsClear => flags & 4 != 0,
+        Condition::LeftIsClear => flags & 8 != 0,
+        Condition::RightIsClear => flags & 16 != 0,
+        Condition::Facing(facing) => *facing == heading,
+        Condition::Not(inner) => !holds(inner, flags, heading),
+        Condition::And(left, right) => holds(left, flags, heading) && holds(right, flags, heading),
+        Condition::Or(left, right) => holds(left, flags, heading) || holds(right, flags, heading),
+    }
+}
+
+/// Whether a condition holds in every situation, in none, or depends on it.
+fn constant(condition: &Condition) -> Option<bool> {
+    let mut values =
+        (0..32u8).flat_map(|flags| Heading::ALL.map(|heading| holds(condition, flags, heading)));
+    let first = values.next()?;
+    values.all(|value| value == first).then_some(first)
+}
+
+/// Walks a program, collecting warnings.
+struct Linter<'p> {
+    program: &'p Program,
+    /// Size of the grid: the starting one, then as changed by `nb lines` and
+    /// `nb columns` in the order of the source
+    lines: u32,
+    columns: u32,
+    /// Largest size the grid has had so far
+    max_lines: u32,
+    max_columns: u32,
+    warnings: Vec<Warning>,
+}
+
+impl Linter<'_> {
+    /// Facts that running `statements` can change, following calls.
+    fn changes(&self, statements: &[Statement], called: &mut BTreeSet<String>) -> u8 {
+        statements.iter().fold(0, |changed, statement| {
+            changed
+                | match &statement.kind {
+                    StatementKind::Command(command) => changed_facts(command),
+                    StatementKind::If {
+                        then, otherwise, ..
+                    } => self.changes(then, called) | self.changes(otherwise, called),
+                    StatementKind::While { body, .. } | StatementKind::Repeat { body, .. } => {
+                        self.changes(body, called)
+                    }
+                    StatementKind::Call(name) => match self.program.procedures.get(name) {
+                        Some(procedure) if called.insert(name.clone()) => {
+                            self.changes(&procedure.body, called)
+                        }
+                        _ => 0,
+                    },
+                }
+        })
+    }
+
+    /// Checks a list of statements run one after the other.
+    fn statements(&mut self, statements: &[Statement]) {
+        let mut previous: Option<&Command> = None;
+        let mut never_ends = false;
+        for statement in statements {
+            let warn = |kind| Warning {
+                line: statement.line,
+                kind,
+            };
+            if never_ends {
+                self.warnings.push(warn(WarningKind::Unreachable));
+                // One warning is enough: the rest is just as unreachable
+                return;
+            }
+            match &statement.kind {
+                StatementKind::Command(command) => {
+                    self.command(statement.line, command);
+                    let cancel = matches!(
+                        (previous, command),
+                        (Some(Command::Right), Command::Left)
+                            | (Some(Command::Left), Command::Right)
+                            | (Some(Command::Up), Command::Down)
+                            | (Some(Command::Down), Command::Up)
+                            | (Some(Command::TurnLeft), Command::TurnRight)
+                            | (Some(Command::TurnRight), Command::TurnLeft)
+                    );
+                    if let (true, Some(first)) = (cancel, previous) {
+                        self.warnings
+                            .push(warn(WarningKind::CancelOut(*first, *command)));
+                        // `right, left, right`: only the first pair is reported
+                        previous = None;
+                    } else {
+                        previous = Some(command);
+                    }
+                    continue;
+                }
+                StatementKind::If {
+                    condition,
+                    then,
+                    otherwise,
+                } => {
+                    if constant(condition) == Some(false) {
+                        self.warnings.push(warn(WarningKind::NeverHolds));
+                    }
+                    self.statements(then);
+                    self.statements(otherwise);
+                }
+                StatementKind::While { condition, body } => {
+                    match constant(condition) {
+                        Some(true) => {
+                            self.warnings.push(warn(WarningKind::NeverEnds));
+                            never_ends = true;
+                        }
+                        Some(false) => self.warnings.push(warn(WarningKind::NeverHolds)),
+                        None => {
+                            let changed = self.changes(body, &mut BTreeSet::new());
+                            if changed & checked_facts(condition) == 0 {
+                                self.warnings.push(warn(WarningKind::Stuck));
+                            }
+                        }
+                    }
+                    self.statements(body);
+                }
+                StatementKind::Repeat { body, .. } => self.statements(body),
+                StatementKind::Call(_) => {}
+            }
+            previous = None;
+        }
+    }
+
+    /// Checks a command against the size of the grid.
+    fn command(&mut self, line: usize, command: &Command) {
+        let kind = match *command {
+            Command::SetLines(0) | Command::SetColumns(0) => WarningKind::EmptyGrid(*command),
+            Command::SetLines(lines) => {
+                self.lines = lines;
+                self.max_lines = self.max_lines.max(lines);
+                return;
+            }
+            Command::SetColumns(columns) => {
+                self.columns = columns;
+                self.max_columns = self.max_columns.max(columns);
+                return;
+            }
+            Command::GoTo(i, j) | Command::Wall(i, j) | Command::Item(i, j)
+                if i >= self.lines || j >= self.columns =>
+            {
+                WarningKind::OutOfGrid {
+                    command: *command,
+                    lines: self.lines,
+                    columns: self.columns,
+                }
+            }
+            _ => return,
+        };
+        self.warnings.push(Warning { line, kind });
+    }
+
+    /// Names of the procedures called from `statements`, directly or not.
+    fn called(&self, statements: &[Statement], called: &mut BTreeSet<String>) {
+        for statement in statements {
+            match &statement.kind {
+                StatementKind::Command(_) => {}
+                StatementKind::If {
+                    then, otherwise, ..
+                } => {
+                    self.called(then, called);
+                    self.called(otherwise, called);
+                }
+                StatementKind::While { body, .. } | StatementKind::Repeat { body, .. } => {
+                    self.called(body, called);
+                }
+                StatementKind::Call(name) => {
+                    if let Some(procedure) = self.program.procedures.get(name)
+                        && called.insert(name.clone())
+                    {
+                        self.called(&procedure.body, called);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Analyzes a program without running it.
+///
+/// # Arguments
+///
+/// * `program` - The parsed program.
+/// * `world` - The world the program will run on, for the size of the grid.
+///
+/// # Returns
+///
+/// The warnings, sorted by line: loops that never end and the code after
+/// them, conditions that never hold, loops whose body can't change their
+/// condition, cells outside of the grid (as resized by the program so far),
+/// empty grids, moves and turns undone by the next command, and procedures
+/// that are never called.
+///
+/// # Example
+/// ```
+/// use rustbot_core::{World, lint, parse};
+///
+/// let program = parse("right\nleft\nwhile not on item\n    pen down\nend").unwrap();
+/// let warnings = lint(&program, &World::new(3, 3).unwrap());
+/// let names: Vec<_> = warnings.iter().map(|warning| warning.name()).collect();
+/// assert_eq!(names, ["cancel_out", "stuck_loop"]);
+/// assert_eq!(warnings[0].to_string(), "line 2: `right` then `left` cancel out");
+/// ```
+pub fn lint(program: &Program, world: &World) -> Vec<Warning> {
+    let mut linter = Linter {
+        program,
+        lines: world.lines(),
+        columns: world.columns(),
+        max_lines: world.lines(),
+        max_columns: world.columns(),
+        warnings: Vec::new(),
+    };
+    linter.statements(&program.statements);
+
+    let mut called = BTreeSet::new();
+    linter.called(&program.statements, &mut called);
+    for (name, procedure) in &program.procedures {
+        if !called.contains(name) {
+            linter.warnings.push(Warning {
+                line: procedure.line,
+                kind: WarningKind::UnusedProcedure(name.clone()),
+            });
+        }
+        // Procedures can be called anywhere: check them against the largest grid
+        linter.lines = linter.max_lines;
+        linter.columns = linter.max_columns;
+        linter.statements(&procedure.body);
+    }
+
+    // Stable sort: warnings of a line stay in the order they were found
+    linter.warnings.sort_by_key(|warning| warning.line);
+    linter.warnings
+}
@@ -0,0 +1,368 @@
+//! Logo front-end: the turtle commands of classic Logo examples.
+//!
+//! Programs are compiled into the same `Program` as the code mode language,
+//! so they run on the same interpreter, the turtle being the robot:
+//! ```text
+//! ; a square, then the same one with the pen up
+//! TO SQUARE
+//!   REPEAT 4 [FD 3 RT 90]
+//! END
+//! PD SQUARE
+//! PU SQUARE
+//! ```
+//! Commands are `FD n` (`FORWARD`), `BK n` (`BACK`), `RT angle` (`RIGHT`),
+//! `LT angle` (`LEFT`), `PU` (`PENUP`), `PD` (`PENDOWN`), `SETPC color`
+//! (`SETPENCOLOR`) and `REPEAT n [ ... ]`. Procedures are defined with
+//! `TO name` … `END`, without inputs, and called by their name. Names are
+//! case insensitive, several commands can share a line and comments start
+//! with `;`.
+//!
+//! Distances are counted in cells, and moves wrap around the edges of the
+//! grid like the other moves. The grid has no diagonals: angles must be
+//! multiples of 90 degrees.
+
+use crate::interpreter::{ErrorKind, ProgramError};
+use crate::parser::{MAX_BLOCK_DEPTH, Procedure, Program, Statement, StatementKind, unknown_call};
+use crate::world::{Color, Command};
+
+/// A token of a Logo program.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Token<'a> {
+    Word(&'a str),
+    Number(u32),
+    /// `[`, `]`, `:` or `"`
+    Symbol(char),
+}
+
+/// Words with a meaning of their own, which can't name procedures.
+const KEYWORDS: [&str; 21] = [
+    "fd",
+    "forward",
+    "bk",
+    "back",
+    "rt",
+    "right",
+    "lt",
+    "left",
+    "pu",
+    "penup",
+    "pd",
+    "pendown",
+    "setpc",
+    "setpencolor",
+    "repeat",
+    "to",
+    "end",
+    "home",
+    "cs",
+    "clearscreen",
+    "st",
+];
+
+/// Splits a program into tokens, each with its line number, skipping spaces
+/// and comments.
+///
+/// # Errors
+///
+/// Returns a `ProgramError` for a number that doesn't fit in a `u32` or a
+/// character that is not part of the language.
+fn tokenize(source: &str) -> Result<Vec<(usize, Token<'_>)>, ProgramError> {
+    let mut tokens = Vec::new();
+    for (index, text) in source.lines().enumerate() {
+        let line = index + 1;
+        let text = text.split(';').next().unwrap_or_default();
+        let mut rest = text;
+        while let Some(c) = rest.chars().next() {
+            let length = if c.is_whitespace() {
+                c.len_utf8()
+            } else if c.is_alphanumeric() || c == '_' || c == '.' {
+                let length = rest
+                    .find(|c: char| !(c.is_alphanumeric() || c == '_' || c == '.'))
+                    .unwrap_or(rest.len());
+                let word = &rest[..length];
+                let token = if word.chars().all(|c| c.is_ascii_digit()) {
+                    let number = word
+                        .parse()
+                        .map_err(|_| syntax(line, &format!("number {word} is too big")))?;
+                    Token::Number(number)
+                } else {
+                    Token::Word(word)
+                };
+                tokens.push((line, token));
+                length
+            } else if matches!(c, '[' | ']' | ':' | '"') {
+                tokens.push((line, Token::Symbol(c)));
+                1
+            } else {
+                return Err(syntax(line, &format!("unexpected character `{c}`")));
+            };
+            rest = &rest[length..];
+        }
+    }
+    Ok(tokens)
+}
+
+/// Builds a syntax error.
+fn syntax(line: usize, message: &str) -> ProgramError {
+    ProgramError {
+        line,
+        kind: ErrorKind::Syntax(message.to_string()),
+    }
+}
+
+/// Statement running `command` on `line`.
+fn command(line: usize, command: Command) -> Statement {
+    Statement {
+        line,
+        kind: StatementKind::Command(command),
+    }
+}
+
+/// Statements running `body` `count` times: nothing for `0`, the body itself
+/// for `1` and a `repeat` block otherwise.
+fn repeat(line: usize, count: u32, body: Vec<Statement>) -> Vec<Statement> {
+    match count {
+        0 => Vec::new(),
+        1 => body,
+        count => vec![Statement {
+            line,
+            kind: StatementKind::Repeat { count, body },
+        }],
+    }
+}
+
+/// Parser over the tokens of a Logo program.
+struct Parser<'a> {
+    tokens: Vec<(usize, Token<'a>)>,
+    position: usize,
+    /// Line of the last token, for errors at the end of the program
+    last_line: usize,
+    /// Number of blocks (`REPEAT`s and the procedure being defined) around
+    /// the current token
+    blocks: usize,
+}
+
+impl<'a> Parser<'a> {
+    /// Next token, without consuming it.
+    fn peek(&self) -> Option<Token<'a>> {
+        self.tokens.get(self.position).map(|&(_, token)| token)
+    }
+
+    /// Line of the next token (or of the last one at the end).
+    fn line(&self) -> usize {
+        self.tokens
+            .get(self.position)
+            .map_or(self.last_line, |&(line, _)| line)
+    }
+
+    /// Consumes the next token if it is `symbol`.
+    fn eat(&mut self, symbol: char) -> bool {
+        if self.peek() == Some(Token::Symbol(symbol)) {
+            self.position += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Error for the next token, which is not what was `expected`.
+    fn unexpected(&self, expected: &str) -> ProgramError {
+        let found = match self.peek() {
+            Some(Token::Word(word)) => format!("`{word}`"),
+            Some(Token::Number(number)) => format!("`{number}`"),
+            Some(Token::Symbol(symbol)) => format!("`{symbol}`"),
+            None => "the end of the program".to_string(),
+        };
+        syntax(self.line(), &format!("expected {expected}, found {found}"))
+    }
+
+    /// Consumes a number.
+    fn number(&mut self) -> Result<u32, ProgramError> {
+        match self.peek() {
+            Some(Token::Number(number)) => {
+                self.position += 1;
+                Ok(number)
+            }
+            _ => Err(self.unexpected("a number")),
+        }
+    }
+
+    /// Consumes an angle, as a number of quarter turns.
+    fn quarter_turns(&mut self) -> Result<u32, ProgramError> {
+        let line = self.line();
+        let angle = self.number()?;
+        if angle % 90 != 0 {
+            return Err(syntax(
+                line,
+                &format!("angle {angle} is not a multiple of 90 degrees"),
+            ));
+        }
+        Ok(angle / 90 % 4)
+    }
+
+    /// Consumes a word, lowercased.
+    fn word(&mut self, expected: &str) -> Result<String, ProgramError> {
+        match self.peek() {
+            Some(Token::Word(word)) => {
+                self.position += 1;
+                Ok(word.to_lowercase())
+            }
+            _ => Err(self.unexpected(expected)),
+        }
+    }
+
+    /// Parses the whole program: procedures and statements.
+    fn program(&mut self) -> Result<Program, ProgramError> {
+        let mut program = Program::default();
+        while let Some(token) = self.peek() {
+            if !matches!(token, Token::Word(word) if word.eq_ignore_ascii_case("to")) {
+                let statements = self.statement()?;
+                program.statements.extend(statements);
+                continue;
+            }
+            let line = self.line();
+            self.position += 1;
+            let name = self.word("the name of the procedure")?;
+            if KEYWORDS.contains(&name.as_str()) {
+                return Err(syntax(
+                    line,
+                    &format!("`{name}` can't be the name of a procedure"),
+                ));
+            }
+            if program.procedures.contains_key(&name) {
+                return Err(syntax(
+                    line,
+                    &format!("procedure `{name}` is already defined"),
+                ));
+            }
+            if self.peek() == Some(Token::Symbol(':')) {
+                return Err(syntax(line, "procedures with inputs are not supported"));
+            }
+            let mut body = Vec::new();
+            self.blocks = 1;
+            loop {
+                match self.peek() {
+                    Some(Token::Word(word)) if word.eq_ignore_ascii_case("end") => break,
+                    Some(Token::Word(word)) if word.eq_ignore_ascii_case("to") => {
+                        return Err(syntax(
+                            self.line(),
+                            "procedures must be defined outside of other procedures",
+                        ));
+                    }
+                    Some(_) => body.extend(self.statement()?),
+                    None => return Err(syntax(line, "`TO` without `END`")),
+                }
+            }
+            self.position += 1;
+            self.blocks = 0;
+            program.procedures.insert(name, Procedure { line, body });
+        }
+        if let Some((line, name)) = unknown_call(&program) {
+            return Err(syntax(line, &format!("unknown procedure `{name}`")));
+        }
+        Ok(program)
+    }
+
+    /// Parses a command, which can compile into several statements (or none,
+    /// for `FD 0`).
+    fn statement(&mut self) -> Result<Vec<Statement>, ProgramError> {
+        let line = self.line();
+        let name = self.word("a command")?;
+        let statements = match name.as_str() {
+            "fd" | "forward" => {
+                let distance = self.number()?;
+                repeat(line, distance, vec![command(line, Command::Move)])
+            }
+            "bk" | "back" => {
+                let distance = self.number()?;
+                let turn_around = repeat(line, 2, vec![command(line, Command::TurnLeft)]);
+                let mut statements = turn_around.clone();
+                statements.extend(repeat(line, distance, vec![command(line, Command::Move)]));
+                statements.extend(turn_around);
+                statements
+            }
+            "rt" | "right" => {
+                let turns = self.quarter_turns()?;
+                repeat(line, turns, vec![command(line, Command::TurnRight)])
+            }
+            "lt" | "left" => {
+                let turns = self.quarter_turns()?;
+                repeat(line, turns, vec![command(line, Command::TurnLeft)])
+            }
+            "pu" | "penup" => vec![command(line, Command::PenUp)],
+            "pd" | "pendown" => vec![command(line, Command::PenDown)],
+            "setpc" | "setpencolor" => {
+                self.eat('"');
+                let color = self
+                    .word("a color")?
+                    .parse::<Color>()
+                    .map_err(|e| syntax(line, &e))?;
+                vec![command(line, Command::PenColor(color))]
+            }
+            "repeat" => {
+                let count = self.number()?;
+                if !self.eat('[') {
+                    return Err(self.unexpected("`[`"));
+                }
+                if self.blocks == MAX_BLOCK_DEPTH {
+                    return Err(syntax(
+                        line,
+                        &format!("blocks nested too deeply (max {MAX_BLOCK_DEPTH})"),
+                    ));
+                }
+                self.blocks += 1;
+                let mut body = Vec::new();
+                while !self.eat(']') {
+                    if self.peek().is_none() {
+                        return Err(syntax(line, "`[` without `]`"));
+                    }
+                    body.extend(self.statement()?);
+                }
+                self.blocks -= 1;
+                vec![Statement {
+                    line,
+                    kind: StatementKind::Repeat { count, body },
+                }]
+            }
+            "end" => return Err(syntax(line, "`END` without `TO`")),
+            "home" | "cs" | "clearscreen" | "st" => {
+                return Err(syntax(line, &format!("`{name}` is not supported")));
+            }
+            _ => vec![Statement {
+                line,
+                kind: StatementKind::Call(name),
+            }],
+        };
+        Ok(statements)
+    }
+}
+
+/// Parses a Logo program into a `Program` of the code mode language.
+///
+/// # Errors
+///
+/// Returns a `ProgramError` of kind `Syntax` for the first token that doesn't
+/// fit the grammar, an angle that is not a multiple of 90 degrees, blocks
+/// nested too deeply, or a call to a procedure that is not defined.
+///
+/// # Example
+/// ```
+/// use rustbot_core::{Limits, World, logo, run};
+///
+/// let program = logo::parse("REPEAT 2 [FD 2 RT 90]").unwrap();
+/// let mut world = World::new(5, 5).unwrap();
+/// run(&program, &mut world, &Limits::default()).unwrap();
+/// assert_eq!((world.robot().i, world.robot().j), (2, 2));
+/// assert_eq!(world.heading().name(), "west");
+/// ```
+pub fn parse(source: &str) -> Result<Program, ProgramError> {
+    let tokens = tokenize(source)?;
+    let last_line = tokens.last().map_or(1, |&(line, _)| line);
+    Parser {
+        tokens,
+        position: 0,
+        last_line,
+        blocks: 0,
+    }
+    .program()
+}
@@ -0,0 +1,218 @@
+//! Optimizer of programs: the shortest program moving the robot the same way.
+//!
+//! The program is run on a copy of the world, then its net effect on the robot
+//! (the cell where it ends and the heading it faces) is reached with as few
+//! commands as possible. Moves wrap around the edges of the grid, so going the
+//! other way round can be shorter: on a grid of five columns, seven `right`s
+//! end where two do.
+//! ```text
+//! repeat 7                     right
+//!     right                    right
+//! end                     →    turn right
+//! turn left
+//! turn left
+//! turn left
+//! ```
+//! Moves go along one direction at a time (all the `right`s, then all the
+//! `down`s), around walls if needed. `go to` is only used when the cell can't
+//! be reached by moves, since it would otherwise always be the shortest.
+
+use std::collections::VecDeque;
+
+use crate::interpreter::{Limits, ProgramError, run};
+use crate::parser::Program;
+use crate::world::{Command, Heading, Robot, World};
+
+/// Net effect of a program on the robot, and the shortest way to get it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Optimization {
+    /// Number of commands executed by the program
+    pub steps: usize,
+    /// Position of the robot after the program
+    pub robot: Robot,
+    /// Heading of the robot after the program
+    pub heading: Heading,
+    /// Shortest sequence of commands leaving the robot at the same position,
+    /// with the same heading
+    pub commands: Vec<Command>,
+    /// Whether `commands` leave the whole world as the program does, and not
+    /// only the robot (the program may also paint, put walls or items, or
+    /// resize the grid)
+    pub equivalent: bool,
+}
+
+impl Optimization {
+    /// The shortest program, as code mode source (one command per line).
+    pub fn source(&self) -> String {
+        self.commands
+            .iter()
+            .map(|command| format!("{command}\n"))
+            .collect()
+    }
+}
+
+/// Shortest way from `from` to `to` on an axis of `size` cells wrapping
+/// around: the number of moves, and whether they go towards higher indices.
+fn offset(from: u32, to: u32, size: u32) -> (u32, bool) {
+    let forward = (to + size - from) % size;
+    let backward = (size - forward) % size;
+    if forward <= backward {
+        (forward, true)
+    } else {
+        (backward, false)
+    }
+}
+
+/// Cell reached from `robot` by `command` (one of the four moves), wrapping
+/// around the edges of the grid.
+fn neighbour(world: &World, robot: Robot, command: Command) -> Robot {
+    let (lines, columns) = (world.lines(), world.columns());
+    let Robot { i, j } = robot;
+    match command {
+        Command::Up => Robot {
+            i: (i + lines - 1) % lines,
+            j,
+        },
+        Command::Down => Robot {
+            i: (i + 1) % lines,
+            j,
+        },
+        Command::Left => Robot {
+            i,
+            j: (j + columns - 1) % columns,
+        },
+        _ => Robot {
+            i,
+            j: (j + 1) % columns,
+        },
+    }
+}
+
+/// Moves from `from` to `to` along one direction, then the other (columns
+/// first or lines first), if no wall is in the way.
+fn direct_path(world: &World, from: Robot, to: Robot, columns_first: bool) -> Option<Vec<Command>> {
+    let (lines, down) = offset(from.i, to.i, world.lines());
+    let (columns, right) = offset(from.j, to.j, world.columns());
+    let vertical = vec![if down { Command::Down } else { Command::Up }; lines as usize];
+    let horizontal = vec![if right { Command::Right } else { Command::Left }; columns as usize];
+    let moves = if columns_first {
+        [horizontal, vertical].concat()
+    } else {
+        [vertical, horizontal].concat()
+    };
+
+    let mut robot = from;
+    for &command in &moves {
+        robot = neighbour(world, robot, command);
+        if world.is_wall(robot.i, robot.j) {
+            return None;
+        }
+    }
+    Some(moves)
+}
+
+/// Shortest moves from `from` to `to` around the walls (breadth-first
+/// search), or `None` if walls close the way.
+fn shortest_path(world: &World, from: Robot, to: Robot) -> Option<Vec<Command>> {
+    let columns = world.columns() as usize;
+    let index = |robot: Robot| robot.i as usize * columns + robot.j as usize;
+    // Move reaching each visited cell, and the cell it was made from
+    let mut previous: Vec<Option<(Command, Robot)>> = vec![None; world.lines() as usize * columns];
+
+    let mut queue = VecDeque::from([from]);
+    while let Some(robot) = queue.pop_front() {
+        if robot == to {
+            let mut moves = Vec::new();
+            let mut cell = to;
+            while cell != from {
+                let (command, before) = previous[index(cell)]?;
+                moves.push(command);
+                cell = before;
+            }
+            moves.reverse();
+            return Some(moves);
+        }
+        for command in [Command::Right, Command::Left, Command::Down, Command::Up] {
+            let next = neighbour(world, robot, command);
+            if next != from && previous[index(next)].is_none() && !world.is_wall(next.i, next.j) {
+                previous[index(next)] = Some((command, robot));
+                queue.push_back(next);
+            }
+        }
+    }
+    None
+}
+
+/// Fewest quarter turns from `from` to `to`.
+fn turns(from: Heading, to: Heading) -> Vec<Command> {
+    match (to as usize + 4 - from as usize) % 4 {
+        0 => Vec::new(),
+        1 => vec![Command::TurnRight],
+        2 => vec![Command::TurnRight; 2],
+        _ => vec![Command::TurnLeft],
+    }
+}
+
+/// Computes the net effect of a program on the robot, and the shortest
+/// sequence of commands with the same effect.
+///
+/// # Arguments
+///
+/// * `program` - The program to optimize.
+/// * `world` - The world the program starts from (left unchanged).
+/// * `limits` - Limits of the run of the program.
+///
+/// # Errors
+///
+/// Returns the `ProgramError` of the run if the program fails.
+///
+/// # Example
+/// ```
+/// use rustbot_core::{Limits, World, optimize, parse};
+///
+/// let program = parse("repeat 7\n    right\nend\nturn left\nturn left\nturn left").unwrap();
+/// let world = World::new(5, 5).unwrap();
+/// let optimization = optimize(&program, &world, &Limits::default()).unwrap();
+/// assert_eq!(optimization.steps, 10);
+/// assert_eq!(optimization.source(), "right\nright\nturn right\n");
+/// assert!(optimization.equivalent);
+/// ```
+pub fn optimize(
+    program: &Program,
+    world: &World,
+    limits: &Limits,
+) -> Result<Optimization, ProgramError> {
+    let mut after = world.clone();
+    let steps = run(program, &mut after, limits)?.steps();
+    let (from, to) = (world.robot(), after.robot());
+
+    // The program may have resized the grid: its last cell is then reached by `go to`
+    let reachable = to.i < world.lines() && to.j < world.columns() && !world.is_wall(to.i, to.j);
+    let mut commands = if reachable {
+        direct_path(world, from, to, true)
+            .or_else(|| direct_path(world, from, to, false))
+            .or_else(|| shortest_path(world, from, to))
+    } else {
+        None
+    }
+    .unwrap_or_else(|| vec![Command::GoTo(to.i, to.j)]);
+    commands.extend(turns(world.heading(), after.heading()));
+
+    // Same world as after the program, the trail of the robot apart
+    let mut optimized = world.clone();
+    let equivalent = commands
+        .iter()
+        .all(|&command| optimized.apply(command).is_ok())
+        && {
+            optimized.set_trail(after.trail().iter().copied());
+            optimized == after
+        };
+
+    Ok(Optimization {
+        steps,
+        robot: to,
+        heading: after.heading(),
+        commands,
+        equivalent,
+    })
+}
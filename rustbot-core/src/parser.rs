@@ -0,0 +1,780 @@
+//! Parser of the code mode language.
+//!
+//! A program is a list of commands, one per line (blank lines are ignored):
+//! ```text
+//! right
+//! left
+//! up
+//! down
+//! go to (i, j)
+//! nb lines = max_i
+//! nb columns = max_j
+//! wall (i, j)
+//! pen down
+//! pen up
+//! pen color name
+//! item (i, j)
+//! pick
+//! drop
+//! move
+//! turn left
+//! turn right
+//! ```
+//! Spaces are free around numbers and symbols, and `go to`, `wall` and `item`
+//! also accept `;` between coordinates. Colors are `black`, `white`, `red`,
+//! `orange`, `yellow`, `green`, `blue` and `purple`. `move` goes one cell
+//! forward in the direction the robot faces (east at first), which the turns
+//! change.
+//!
+//! Commands can be run conditionally or repeated, in blocks closed by `end`:
+//! ```text
+//! if condition
+//!     ...
+//! else
+//!     ...
+//! end
+//! while condition
+//!     ...
+//! end
+//! repeat 4
+//!     ...
+//! end
+//! ```
+//! The `else` part is optional. Conditions are checked on the cell of the
+//! robot and its neighbours:
+//! ```text
+//! on item
+//! carrying item
+//! front is clear
+//! left is clear
+//! right is clear
+//! facing north
+//! ```
+//! They can be combined with `not`, `and`, `or` and parentheses. A cell is
+//! clear if it is in the grid (edges don't wrap around here) and not a wall.
+//!
+//! Procedures are defined at the top level with `def name` ... `end`, and
+//! called with their name alone on a line, before or after their definition.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::ops::Range;
+
+use crate::interpreter::{ErrorKind, ProgramError};
+use crate::world::{Color, Command, Heading};
+
+/// A statement of a program, with the line it comes from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Statement {
+    /// Line number in the source, starting at 1 (the first line for a block)
+    pub line: usize,
+    pub kind: StatementKind,
+}
+
+/// What a statement does.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StatementKind {
+    /// Apply a command to the world
+    Command(Command),
+    /// Run `then` if the condition holds, `otherwise` if not
+    If {
+        condition: Condition,
+        then: Vec<Statement>,
+        otherwise: Vec<Statement>,
+    },
+    /// Run `body` as long as the condition holds
+    While {
+        condition: Condition,
+        body: Vec<Statement>,
+    },
+    /// Run `body` `count` times
+    Repeat { count: u32, body: Vec<Statement> },
+    /// Run the procedure with this name (see `Program::procedures`)
+    Call(String),
+}
+
+/// A named list of statements, run by `StatementKind::Call`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Procedure {
+    /// Line of the definition in the source, starting at 1
+    pub line: usize,
+    pub body: Vec<Statement>,
+}
+
+/// A condition of `if` and `while`, checked on the world.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Condition {
+    /// There is at least one item on the cell of the robot
+    OnItem,
+    /// The robot carries at least one item
+    CarryingItem,
+    /// The cell in front of the robot is clear (see `World::is_clear`)
+    FrontIsClear,
+    /// The cell on the left of the robot is clear
+    LeftIsClear,
+    /// The cell on the right of the robot is clear
+    RightIsClear,
+    /// The robot faces this direction
+    Facing(Heading),
+    /// The inner condition doesn't hold
+    Not(Box<Condition>),
+    /// Both conditions hold
+    And(Box<Condition>, Box<Condition>),
+    /// At least one of the conditions holds
+    Or(Box<Condition>, Box<Condition>),
+}
+
+impl Condition {
+    /// Binding strength of the condition when written: `or` binds less than
+    /// `and`, which binds less than `not` and single conditions.
+    fn precedence(&self) -> u8 {
+        match self {
+            Condition::Or(..) => 1,
+            Condition::And(..) => 2,
+            _ => 3,
+        }
+    }
+}
+
+/// Writes the condition the way it is written in code mode, with the
+/// parentheses needed to read it back the same.
+impl fmt::Display for Condition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Writes an operand, in parentheses if it binds less than `min`
+        let operand = |f: &mut fmt::Formatter<'_>, condition: &Condition, min: u8| {
+            if condition.precedence() < min {
+                write!(f, "({condition})")
+            } else {
+                write!(f, "{condition}")
+            }
+        };
+        match self {
+            Condition::OnItem => write!(f, "on item"),
+            Condition::CarryingItem => write!(f, "carrying item"),
+            Condition::FrontIsClear => write!(f, "front is clear"),
+            Condition::LeftIsClear => write!(f, "left is clear"),
+            Condition::RightIsClear => write!(f, "right is clear"),
+            Condition::Facing(heading) => write!(f, "facing {heading}"),
+            Condition::Not(condition) => {
+                write!(f, "not ")?;
+                operand(f, condition, 3)
+            }
+            Condition::And(left, right) => {
+                operand(f, left, 2)?;
+                write!(f, " and ")?;
+                operand(f, right, 3)
+            }
+            Condition::Or(left, right) => {
+                operand(f, left, 1)?;
+                write!(f, " or ")?;
+                operand(f, right, 2)
+            }
+        }
+    }
+}
+
+/// A parsed program, ready to be run by `interpreter::run`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Program {
+    pub statements: Vec<Statement>,
+    /// Procedures by name
+    pub procedures: BTreeMap<String, Procedure>,
+}
+
+/// Writes statements one per line, blocks indented by four spaces per level.
+fn write_statements(
+    f: &mut fmt::Formatter<'_>,
+    statements: &[Statement],
+    depth: usize,
+) -> fmt::Result {
+    let indent = "    ".repeat(depth);
+    for statement in statements {
+        match &statement.kind {
+            StatementKind::Command(command) => writeln!(f, "{indent}{command}")?,
+            StatementKind::If {
+                condition,
+                then,
+                otherwise,
+            } => {
+                writeln!(f, "{indent}if {condition}")?;
+                write_statements(f, then, depth + 1)?;
+                if !otherwise.is_empty() {
+                    writeln!(f, "{indent}else")?;
+                    write_statements(f, otherwise, depth + 1)?;
+                }
+                writeln!(f, "{indent}end")?;
+            }
+            StatementKind::While { condition, body } => {
+                writeln!(f, "{indent}while {condition}")?;
+                write_statements(f, body, depth + 1)?;
+                writeln!(f, "{indent}end")?;
+            }
+            StatementKind::Repeat { count, body } => {
+                writeln!(f, "{indent}repeat {count}")?;
+                write_statements(f, body, depth + 1)?;
+                writeln!(f, "{indent}end")?;
+            }
+            StatementKind::Call(name) => writeln!(f, "{indent}{name}")?,
+        }
+    }
+    Ok(())
+}
+
+/// Writes the program in the code mode language: procedures first, each
+/// followed by a blank line, then the statements. Whatever dialect the
+/// program was written in, `parse` reads the text back into the same program
+/// (line numbers aside), as long as its procedure names are allowed in the
+/// code mode language.
+///
+/// # Example
+/// ```
+/// use rustbot_core::{karel, parse};
+///
+/// let program = karel::parse("function main() {\n  while (frontIsClear()) { move(); }\n}").unwrap();
+/// let text = program.to_string();
+/// assert_eq!(text, "def main\n    while front is clear\n        step\n    end\nend\n\nmain\n");
+/// assert_eq!(parse(&text).unwrap().to_string(), text);
+/// ```
+impl fmt::Display for Program {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (name, procedure) in &self.procedures {
+            writeln!(f, "def {name}")?;
+            write_statements(f, &procedure.body, 1)?;
+            writeln!(f, "end")?;
+            writeln!(f)?;
+        }
+        write_statements(f, &self.statements, 0)
+    }
+}
+
+/// A token of a line of code.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum Token<'a> {
+    Word(&'a str),
+    Number(u32),
+    Symbol(char),
+}
+
+/// Splits a line into the byte ranges of its words and numbers (letters,
+/// digits and `_`) and symbols (any other character), skipping spaces.
+pub(crate) fn token_spans(line: &str) -> Vec<Range<usize>> {
+    let mut spans = Vec::new();
+    let mut chars = line.char_indices().peekable();
+    while let Some(&(start, c)) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+        } else if c.is_ascii_digit() || c.is_alphabetic() || c == '_' {
+            let mut end = start;
+            while let Some(&(index, c)) = chars.peek() {
+                if !(c.is_alphanumeric() || c == '_') {
+                    break;
+                }
+                end = index + c.len_utf8();
+                chars.next();
+            }
+            spans.push(start..end);
+        } else {
+            spans.push(start..start + c.len_utf8());
+            chars.next();
+        }
+    }
+    spans
+}
+
+/// Reads the token of a span found by `token_spans`.
+///
+/// # Errors
+///
+/// Returns an error message if a number doesn't fit in a `u32`.
+pub(crate) fn token(text: &str) -> Result<Token<'_>, String> {
+    let first = text.chars().next().unwrap_or_default();
+    if text.chars().all(|c| c.is_ascii_digit()) {
+        text.parse()
+            .map(Token::Number)
+            .map_err(|_| format!("number {text} is too big"))
+    } else if first.is_ascii_digit() || first.is_alphabetic() || first == '_' {
+        Ok(Token::Word(text))
+    } else {
+        Ok(Token::Symbol(first))
+    }
+}
+
+/// Splits a line into words, numbers and symbols, skipping spaces.
+///
+/// # Errors
+///
+/// Returns an error message if a number doesn't fit in a `u32`.
+pub(crate) fn tokenize(line: &str) -> Result<Vec<Token<'_>>, String> {
+    token_spans(line)
+        .into_iter()
+        .map(|span| token(&line[span]))
+        .collect()
+}
+
+/// One line of code.
+pub(crate) enum Line {
+    Blank,
+    Command(Command),
+    If(Condition),
+    Else,
+    While(Condition),
+    Repeat(u32),
+    Def(String),
+    End,
+    /// A single word that is not a command: maybe a procedure
+    Call(String),
+}
+
+/// Words starting a block or used in conditions, which can't name procedures.
+pub(crate) const KEYWORDS: [&str; 10] = [
+    "if", "else", "while", "repeat", "def", "end", "not", "and", "or", "facing",
+];
+
+/// Maximum nesting of `not` and parentheses in a condition, so that
+/// conditions like `not not not …` can't overflow the stack of the parser.
+pub(crate) const MAX_CONDITION_DEPTH: usize = 64;
+
+/// Maximum nesting of blocks (a `def` counts as one), so that running,
+/// checking or writing a program, which recurse once per block, can't overflow
+/// the stack.
+pub(crate) const MAX_BLOCK_DEPTH: usize = 32;
+
+/// Reads a condition from tokens, advancing them.
+///
+/// `or` binds less than `and`, which binds less than `not`:
+/// `not a and b or c` is `((not a) and b) or c`.
+struct ConditionParser<'t, 'a> {
+    tokens: &'t [Token<'a>],
+    /// Number of `not` and `(` around the current token
+    depth: usize,
+}
+
+impl ConditionParser<'_, '_> {
+    /// Consumes the next token if it is the word `word`.
+    fn eat(&mut self, word: &str) -> bool {
+        match self.tokens.split_first() {
+            Some((Token::Word(w), rest)) if *w == word => {
+                self.tokens = rest;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn or(&mut self) -> Result<Condition, String> {
+        let mut condition = self.and()?;
+        while self.eat("or") {
+            condition = Condition::Or(Box::new(condition), Box::new(self.and()?));
+        }
+        Ok(condition)
+    }
+
+    fn and(&mut self) -> Result<Condition, String> {
+        let mut condition = self.unary()?;
+        while self.eat("and") {
+            condition = Condition::And(Box::new(condition), Box::new(self.unary()?));
+        }
+        Ok(condition)
+    }
+
+    /// Runs `parse` one level deeper.
+    fn nested(
+        &mut self,
+        parse: impl FnOnce(&mut Self) -> Result<Condition, String>,
+    ) -> Result<Condition, String> {
+        if self.depth == MAX_CONDITION_DEPTH {
+            return Err("condition nested too deeply".to_string());
+        }
+        self.depth += 1;
+        let condition = parse(self);
+        self.depth -= 1;
+        condition
+    }
+
+    fn unary(&mut self) -> Result<Condition, String> {
+        if self.eat("not") {
+            return self.nested(|parser| Ok(Condition::Not(Box::new(parser.unary()?))));
+        }
+        if let Some((Token::Symbol('('), rest)) = self.tokens.split_first() {
+            self.tokens = rest;
+            return self.nested(|parser| {
+                let condition = parser.or()?;
+                match parser.tokens.split_first() {
+                    Some((Token::Symbol(')'), rest)) => parser.tokens = rest,
+                    _ => return Err("missing `)` in condition".to_string()),
+                }
+                Ok(condition)
+            });
+        }
+        let (condition, length) = match self.tokens {
+            [Token::Word("on"), Token::Word("item"), ..] => (Condition::OnItem, 2),
+            [Token::Word("carrying"), Token::Word("item"), ..] => (Condition::CarryingItem, 2),
+            [
+                Token::Word("front"),
+                Token::Word("is"),
+                Token::Word("clear"),
+                ..,
+            ] => (Condition::FrontIsClear, 3),
+            [
+                Token::Word("left"),
+                Token::Word("is"),
+                Token::Word("clear"),
+                ..,
+            ] => (Condition::LeftIsClear, 3),
+            [
+                Token::Word("right"),
+                Token::Word("is"),
+                Token::Word("clear"),
+                ..,
+            ] => (Condition::RightIsClear, 3),
+            [Token::Word("facing"), Token::Word(name), ..] => {
+                (Condition::Facing(name.parse::<Heading>()?), 2)
+            }
+            [] => return Err("missing condition (try `on item`)".to_string()),
+            _ => {
+                return Err(
+                    "unknown condition (try `on item`, `front is clear` or `facing north`)"
+                        .to_string(),
+                );
+            }
+        };
+        self.tokens = &self.tokens[length..];
+        Ok(condition)
+    }
+}
+
+/// Parses the tokens of a condition, after `if` or `while`.
+fn parse_condition(tokens: &[Token<'_>]) -> Result<Condition, String> {
+    let mut parser = ConditionParser { tokens, depth: 0 };
+    let condition = parser.or()?;
+    if !parser.tokens.is_empty() {
+        return Err("unexpected words after the condition".to_string());
+    }
+    Ok(condition)
+}
+
+/// Parses one line of code.
+pub(crate) fn parse_line(line: &str) -> Result<Line, String> {
+    let tokens = tokenize(line)?;
+    let command = match tokens.as_slice() {
+        [] => return Ok(Line::Blank),
+        [Token::Word("if"), condition @ ..] => return Ok(Line::If(parse_condition(condition)?)),
+        [Token::Word("else")] => return Ok(Line::Else),
+        [Token::Word("while"), condition @ ..] => {
+            return Ok(Line::While(parse_condition(condition)?));
+        }
+        [Token::Word("repeat"), Token::Number(count)] => return Ok(Line::Repeat(*count)),
+        [Token::Word("def"), Token::Word(name)] => {
+            if KEYWORDS.contains(name) || matches!(parse_line(name)?, Line::Command(_)) {
+                return Err(format!("`{name}` can't be the name of a procedure"));
+            }
+            return Ok(Line::Def(name.to_string()));
+        }
+        [Token::Word("end")] => return Ok(Line::End),
+        [Token::Word("right")] => Command::Right,
+        [Token::Word("left")] => Command::Left,
+        [Token::Word("up")] => Command::Up,
+        [Token::Word("down")] => Command::Down,
+        [
+            Token::Word("go"),
+            Token::Word("to"),
+            Token::Symbol('('),
+            Token::Number(i),
+            Token::Symbol(',' | ';'),
+            Token::Number(j),
+            Token::Symbol(')'),
+        ] => Command::GoTo(*i, *j),
+        [
+            Token::Word("wall"),
+            Token::Symbol('('),
+            Token::Number(i),
+            Token::Symbol(',' | ';'),
+            Token::Number(j),
+            Token::Symbol(')'),
+        ] => Command::Wall(*i, *j),
+        [
+            Token::Word("nb"),
+            Token::Word("lines"),
+            Token::Symbol('='),
+            Token::Number(lines),
+        ] => Command::SetLines(*lines),
+        [
+            Token::Word("nb"),
+            Token::Word("columns"),
+            Token::Symbol('='),
+            Token::Number(columns),
+        ] => Command::SetColumns(*columns),
+        [Token::Word("pen"), Token::Word("down")] => Command::PenDown,
+        [Token::Word("pen"), Token::Word("up")] => Command::PenUp,
+        [Token::Word("pen"), Token::Word("color"), Token::Word(name)] => {
+            Command::PenColor(name.parse::<Color>()?)
+        }
+        [
+            Token::Word("item"),
+            Token::Symbol('('),
+            Token::Number(i),
+            Token::Symbol(',' | ';'),
+            Token::Number(j),
+            Token::Symbol(')'),
+        ] => Command::Item(*i, *j),
+        [Token::Word("pick")] => Command::Pick,
+        [Token::Word("drop")] => Command::Drop,
+        [Token::Word("move")] => Command::Move,
+        [Token::Word("step")] => Command::Step,
+        [Token::Word("turn"), Token::Word("left")] => Command::TurnLeft,
+        [Token::Word("turn"), Token::Word("right")] => Command::TurnRight,
+        [Token::Word(name)] if !KEYWORDS.contains(name) => return Ok(Line::Call(name.to_string())),
+        _ => return Err(format!("unknown command: `{}`", line.trim())),
+    };
+    Ok(Line::Command(command))
+}
+
+/// What a block being parsed is.
+enum BlockKind {
+    If(Condition),
+    While(Condition),
+    Repeat(u32),
+    Def(String),
+}
+
+/// A block being parsed: its `end` is not reached yet.
+struct OpenBlock {
+    /// First line of the block
+    line: usize,
+    kind: BlockKind,
+    /// Statements of the block (before `else` for an `if`)
+    then: Vec<Statement>,
+    /// Statements after `else`, once it is reached
+    otherwise: Option<Vec<Statement>>,
+}
+
+impl OpenBlock {
+    /// Opens a block at its first line.
+    fn new(line: usize, kind: BlockKind) -> Self {
+        OpenBlock {
+            line,
+            kind,
+            then: Vec::new(),
+            otherwise: None,
+        }
+    }
+
+    /// Statements the next line belongs to.
+    fn body(&mut self) -> &mut Vec<Statement> {
+        self.otherwise.as_mut().unwrap_or(&mut self.then)
+    }
+}
+
+/// Finds the first `StatementKind::Call` that names no procedure of the program.
+///
+/// # Returns
+///
+/// The line and the name of the call with the smallest line number, if any.
+pub(crate) fn unknown_call(program: &Program) -> Option<(usize, &str)> {
+    fn first_unknown<'s>(
+        statements: &'s [Statement],
+        program: &Program,
+        found: &mut Option<(usize, &'s str)>,
+    ) {
+        for statement in statements {
+            match &statement.kind {
+                StatementKind::Call(name)
+                    if !program.procedures.contains_key(name)
+                        && found.is_none_or(|(line, _)| statement.line < line) =>
+                {
+                    *found = Some((statement.line, name));
+                }
+                StatementKind::If {
+                    then, otherwise, ..
+                } => {
+                    first_unknown(then, program, found);
+                    first_unknown(otherwise, program, found);
+                }
+                StatementKind::While { body, .. } | StatementKind::Repeat { body, .. } => {
+                    first_unknown(body, program, found);
+                }
+                _ => {}
+            }
+        }
+    }
+
+    let mut found = None;
+    first_unknown(&program.statements, program, &mut found);
+    for procedure in program.procedures.values() {
+        first_unknown(&procedure.body, program, &mut found);
+    }
+    found
+}
+
+/// Parses the source code of a program.
+///
+/// # Errors
+///
+/// Returns a `ProgramError` of kind `Syntax` for the first line that is not a
+/// valid command, for a block that is not closed properly or nested too
+/// deeply, or for a call to a procedure that is not defined.
+///
+/// # Example
+/// ```
+/// use rustbot_core::{Command, Condition, StatementKind, parse};
+///
+/// let program = parse("right\n\nwhile on item\n  pick\nend").unwrap();
+/// assert_eq!(program.statements[1].line, 3);
+/// assert_eq!(
+///     program.statements[1].kind,
+///     StatementKind::While {
+///         condition: Condition::OnItem,
+///         body: vec![rustbot_core::Statement {
+///             line: 4,
+///             kind: StatementKind::Command(Command::Pick),
+///         }],
+///     }
+/// );
+/// ```
+pub fn parse(source: &str) -> Result<Program, ProgramError> {
+    parse_with_procedures(source, &BTreeMap::new())
+}
+
+/// Parses the source code of a program that can also call procedures defined
+/// beforehand (by the previous lines of a REPL, for example).
+///
+/// # Arguments
+///
+/// * `source` - The source code.
+/// * `known` - Procedures defined beforehand, by name.
+///
+/// # Returns
+///
+/// The program, with the known procedures too. Procedures defined in `source`
+/// replace the known ones with the same name.
+///
+/// # Errors
+///
+/// Same as `parse`.
+///
+/// # Example
+/// ```
+/// use rustbot_core::parser::parse_with_procedures;
+///
+/// let known = rustbot_core::parse("def zig\n    right\nend").unwrap().procedures;
+/// let program = parse_with_procedures("zig\ndef zag\n    zig\nend", &known).unwrap();
+/// assert_eq!(program.statements.len(), 1);
+/// assert!(program.procedures.contains_key("zig") && program.procedures.contains_key("zag"));
+/// assert!(parse_with_procedures("zag", &known).is_err());
+/// ```
+pub fn parse_with_procedures(
+    source: &str,
+    known: &BTreeMap<String, Procedure>,
+) -> Result<Program, ProgramError> {
+    let mut program = Program::default();
+    let mut blocks: Vec<OpenBlock> = Vec::new();
+    for (index, line) in source.lines().enumerate() {
+        let line_number = index + 1;
+        let error = |message: &str| ProgramError {
+            line: line_number,
+            kind: ErrorKind::Syntax(message.to_string()),
+        };
+        let line = parse_line(line).map_err(|message| error(&message))?;
+        let opens_block = matches!(
+            line,
+            Line::If(_) | Line::While(_) | Line::Repeat(_) | Line::Def(_)
+        );
+        if opens_block && blocks.len() == MAX_BLOCK_DEPTH {
+            return Err(error(&format!(
+                "blocks nested too deeply (max {MAX_BLOCK_DEPTH})"
+            )));
+        }
+        let (line, kind) = match line {
+            Line::Blank => continue,
+            Line::If(condition) => {
+                blocks.push(OpenBlock::new(line_number, BlockKind::If(condition)));
+                continue;
+            }
+            Line::While(condition) => {
+                blocks.push(OpenBlock::new(line_number, BlockKind::While(condition)));
+                continue;
+            }
+            Line::Repeat(count) => {
+                blocks.push(OpenBlock::new(line_number, BlockKind::Repeat(count)));
+                continue;
+            }
+            Line::Def(name) => {
+                if !blocks.is_empty() {
+                    return Err(error("procedures must be defined outside of blocks"));
+                }
+                if program.procedures.contains_key(&name) {
+                    return Err(error(&format!("procedure `{name}` is already defined")));
+                }
+                blocks.push(OpenBlock::new(line_number, BlockKind::Def(name)));
+                continue;
+            }
+            Line::Else => {
+                match blocks.last_mut() {
+                    Some(block)
+                        if matches!(block.kind, BlockKind::If(_)) && block.otherwise.is_none() =>
+                    {
+                        block.otherwise = Some(Vec::new());
+                    }
+                    _ => return Err(error("`else` without `if`")),
+                }
+                continue;
+            }
+            Line::End => {
+                let block = blocks.pop().ok_or_else(|| error("`end` without a block"))?;
+                let kind = match block.kind {
+                    BlockKind::If(condition) => StatementKind::If {
+                        condition,
+                        then: block.then,
+                        otherwise: block.otherwise.unwrap_or_default(),
+                    },
+                    BlockKind::While(condition) => StatementKind::While {
+                        condition,
+                        body: block.then,
+                    },
+                    BlockKind::Repeat(count) => StatementKind::Repeat {
+                        count,
+                        body: block.then,
+                    },
+                    BlockKind::Def(name) => {
+                        let procedure = Procedure {
+                            line: block.line,
+                            body: block.then,
+                        };
+                        program.procedures.insert(name, procedure);
+                        continue;
+                    }
+                };
+                (block.line, kind)
+            }
+            Line::Command(command) => (line_number, StatementKind::Command(command)),
+            Line::Call(name) => (line_number, StatementKind::Call(name)),
+        };
+        let statement = Statement { line, kind };
+        match blocks.last_mut() {
+            Some(block) => block.body().push(statement),
+            None => program.statements.push(statement),
+        }
+    }
+    if let Some(block) = blocks.pop() {
+        let keyword = match block.kind {
+            BlockKind::If(_) => "if",
+            BlockKind::While(_) => "while",
+            BlockKind::Repeat(_) => "repeat",
+            BlockKind::Def(_) => "def",
+        };
+        return Err(ProgramError {
+            line: block.line,
+            kind: ErrorKind::Syntax(format!("`{keyword}` without `end`")),
+        });
+    }
+    let mut procedures = known.clone();
+    procedures.append(&mut program.procedures);
+    program.procedures = procedures;
+    if let Some((line, name)) = unknown_call(&program) {
+        return Err(ProgramError {
+            line,
+            kind: ErrorKind::Syntax(format!("unknown command: `{name}`")),
+        });
+    }
+    Ok(program)
+}
@@ -0,0 +1,229 @@
+//! Text and image renderings of a world.
+
+use std::collections::BTreeSet;
+use std::fmt::Write;
+
+use crate::world::{Robot, World};
+
+/// Side of a cell in SVG renderings, in pixels.
+pub const SVG_CELL: u32 = 40;
+
+/// Width of the line and column numbers in SVG renderings, in pixels.
+pub const SVG_HEADER: u32 = 24;
+
+/// Renders the grid as ASCII art, one line of text per line of the grid:
+/// `R` for the robot, `#` for walls, the number of items (`+` for 10 or more),
+/// `*` for painted cells and `.` for empty cells.
+///
+/// # Example
+/// ```
+/// use rustbot_core::{Command, World, render};
+///
+/// let mut world = World::new(2, 3).unwrap();
+/// world.apply(Command::Right).unwrap();
+/// world.apply(Command::Wall(1, 0)).unwrap();
+/// world.apply(Command::Item(0, 2)).unwrap();
+/// world.apply(Command::PenDown).unwrap();
+/// world.apply(Command::Down).unwrap();
+/// assert_eq!(render::ascii(&world), ".*1\n#R.\n");
+/// ```
+pub fn ascii(world: &World) -> String {
+    let robot = world.robot();
+    let mut text = String::new();
+    for i in 0..world.lines() {
+        for j in 0..world.columns() {
+            if i == robot.i && j == robot.j {
+                text.push('R');
+            } else if world.is_wall(i, j) {
+                text.push('#');
+            } else if let count @ 1.. = world.items_at(i, j) {
+                text.push(char::from_digit(count, 10).unwrap_or('+'));
+            } else if world.paint_at(i, j).is_some() {
+                text.push('*');
+            } else {
+                text.push('.');
+            }
+        }
+        text.push('\n');
+    }
+    text
+}
+
+/// Pixel coordinates of the center of a cell in SVG renderings.
+fn center(cell: Robot) -> (u32, u32) {
+    (
+        SVG_HEADER + cell.j * SVG_CELL + SVG_CELL / 2,
+        SVG_HEADER + cell.i * SVG_CELL + SVG_CELL / 2,
+    )
+}
+
+/// Renders the grid as a standalone SVG image, with the colors of the web pages:
+/// line and column numbers, cells (with their paint), walls, the trail of the
+/// robot, the robot and the number of items in the top left corner of cells.
+///
+/// The trail is drawn as a line between consecutive positions that are
+/// neighbours in the grid (wrapping around an edge or teleporting breaks it),
+/// with a dot on every visited cell.
+///
+/// # Example
+/// ```
+/// use rustbot_core::{World, render};
+///
+/// let svg = render::svg(&World::new(2, 3).unwrap());
+/// assert!(svg.starts_with("<svg"));
+/// assert!(svg.contains(r#"width="144" height="104""#));
+/// ```
+pub fn svg(world: &World) -> String {
+    let width = SVG_HEADER + world.columns() * SVG_CELL;
+    let height = SVG_HEADER + world.lines() * SVG_CELL;
+    let robot = world.robot();
+    let mut svg = String::new();
+
+    // `write!` into a `String` can't fail
+    let _ = writeln!(
+        svg,
+        r##"<svg xmlns="http://www.w3.org/2000/svg" width="{width}" height="{height}" viewBox="0 0 {width} {height}" font-family="Noto Sans Mono, monospace">"##
+    );
+    let _ = writeln!(
+        svg,
+        "<title>RustBot at ({}, {}) in a {}x{} grid</title>",
+        robot.i,
+        robot.j,
+        world.lines(),
+        world.columns()
+    );
+    let _ = writeln!(
+        svg,
+        r##"<rect width="{width}" height="{height}" fill="#3b4252"/>"##
+    );
+
+    // Line and column numbers
+    let _ = writeln!(
+        svg,
+        r##"<g fill="#eceff4" font-size="12" font-weight="bold" text-anchor="middle" dominant-baseline="central">"##
+    );
+    for j in 0..world.columns() {
+        let (x, _) = center(Robot { i: 0, j });
+        let _ = writeln!(svg, r#"<text x="{x}" y="{}">{j}</text>"#, SVG_HEADER / 2);
+    }
+    for i in 0..world.lines() {
+        let (_, y) = center(Robot { i, j: 0 });
+        let _ = writeln!(svg, r#"<text x="{}" y="{y}">{i}</text>"#, SVG_HEADER / 2);
+    }
+    svg.push_str("</g>\n");
+
+    // Cells, paint and walls
+    let _ = writeln!(svg, r##"<g stroke="#254e65">"##);
+    for i in 0..world.lines() {
+        for j in 0..world.columns() {
+            let fill = match world.paint_at(i, j) {
+                _ if world.is_wall(i, j) => "#2e3440".to_string(),
+                Some(color) => color.hex(),
+                None => "#81a1c1".to_string(),
+            };
+            let _ = writeln!(
+                svg,
+                r#"<rect x="{}" y="{}" width="{SVG_CELL}" height="{SVG_CELL}" fill="{fill}"/>"#,
+                SVG_HEADER + j * SVG_CELL,
+                SVG_HEADER + i * SVG_CELL,
+            );
+        }
+    }
+    svg.push_str("</g>\n");
+
+    // Trail
+    let trail = world.trail();
+    let mut path = String::new();
+    for pair in trail.windows(2) {
+        let (from, to) = (pair[0], pair[1]);
+        if from.i.abs_diff(to.i) + from.j.abs_diff(to.j) == 1 {
+            let ((x1, y1), (x2, y2)) = (center(from), center(to));
+            let _ = write!(path, "M{x1} {y1}L{x2} {y2}");
+        }
+    }
+    if !path.is_empty() {
+        let _ = writeln!(
+            svg,
+            r##"<path d="{path}" fill="none" stroke="#ebcb8b" stroke-width="4" stroke-linecap="round"/>"##
+        );
+    }
+    let _ = writeln!(svg, r##"<g fill="#ebcb8b">"##);
+    let visited: BTreeSet<(u32, u32)> = trail.iter().map(|cell| (cell.i, cell.j)).collect();
+    for (i, j) in visited {
+        let (x, y) = center(Robot { i, j });
+        let _ = writeln!(svg, r#"<circle cx="{x}" cy="{y}" r="4"/>"#);
+    }
+    svg.push_str("</g>\n");
+
+    // Robot: antenna, head with eyes and mouth, body
+    let _ = writeln!(
+        svg,
+        r##"<g transform="translate({} {})" stroke="#2e3440" stroke-width="1.5">
+<line x1="20" y1="4" x2="20" y2="10"/>
+<circle cx="20" cy="4" r="2.5" fill="#bf616a"/>
+<rect x="8" y="10" width="24" height="16" rx="4" fill="#d8dee9"/>
+<circle cx="15" cy="17" r="2.5" fill="#2e3440"/>
+<circle cx="25" cy="17" r="2.5" fill="#2e3440"/>
+<line x1="15" y1="22" x2="25" y2="22"/>
+<rect x="11" y="26" width="18" height="10" rx="2" fill="#d08770"/>
+</g>"##,
+        SVG_HEADER + robot.j * SVG_CELL,
+        SVG_HEADER + robot.i * SVG_CELL,
+    );
+
+    // Items, drawn last so the robot doesn't hide them
+    let _ = writeln!(
+        svg,
+        r##"<g font-size="10" font-weight="bold" text-anchor="middle" dominant-baseline="central">"##
+    );
+    for ((i, j), count) in world.items() {
+        let (x, y) = (SVG_HEADER + j * SVG_CELL + 9, SVG_HEADER + i * SVG_CELL + 9);
+        let _ = writeln!(
+            svg,
+            r##"<circle cx="{x}" cy="{y}" r="8" fill="#a3be8c" stroke="#2e3440"/><text x="{x}" y="{y}" fill="#2e3440">{count}</text>"##
+        );
+    }
+    svg.push_str("</g>\n");
+
+    svg.push_str("</svg>\n");
+    svg
+}
+
+/// Renders only the painted cells (see `Command::PenDown`) as a standalone SVG
+/// image: one `scale` x `scale` pixels square per cell, the other cells being
+/// transparent. This is the pixel art drawn by the robot, without the grid.
+///
+/// # Arguments
+///
+/// * `world` - The world whose paint to draw.
+/// * `scale` - Side of a cell, in pixels.
+///
+/// # Example
+/// ```
+/// use rustbot_core::{Command, World, render};
+///
+/// let mut world = World::new(2, 3).unwrap();
+/// world.apply(Command::PenDown).unwrap();
+/// let svg = render::canvas_svg(&world, 10);
+/// assert!(svg.contains(r#"width="30" height="20" viewBox="0 0 3 2""#));
+/// assert!(svg.contains(r##"<rect x="0" y="0" width="1" height="1" fill="#2e3440"/>"##));
+/// ```
+pub fn canvas_svg(world: &World, scale: u32) -> String {
+    let (columns, lines) = (world.columns(), world.lines());
+    let mut svg = String::new();
+    let _ = writeln!(
+        svg,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{}" height="{}" viewBox="0 0 {columns} {lines}" shape-rendering="crispEdges">"#,
+        columns * scale,
+        lines * scale,
+    );
+    for ((i, j), color) in world.painted() {
+        let _ = writeln!(
+            svg,
+            r#"<rect x="{j}" y="{i}" width="1" height="1" fill="{}"/>"#,
+            color.hex()
+        );
+    }
+    svg.push_str("</svg>\n");
+    svg
+}
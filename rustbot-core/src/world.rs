@@ -0,0 +1,741 @@
+//! The grid and the robot moving on it.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::str::FromStr;
+
+/// Number of positions kept in the trail of the robot.
+pub const TRAIL_LEN: usize = 100;
+
+/// Position of the robot in the grid.
+///
+/// `i` is the line (0 at the top) and `j` the column (0 on the left).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Robot {
+    pub i: u32,
+    pub j: u32,
+}
+
+/// Direction the robot faces, for `Command::Move`, `Command::Step` and the turns.
+///
+/// North is up (towards line 0) and west is left (towards column 0).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Heading {
+    North,
+    #[default]
+    East,
+    South,
+    West,
+}
+
+impl Heading {
+    /// Every heading, turning clockwise from north.
+    pub const ALL: [Heading; 4] = [Heading::North, Heading::East, Heading::South, Heading::West];
+
+    /// Name of the heading, as written in code mode.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Heading::North => "north",
+            Heading::East => "east",
+            Heading::South => "south",
+            Heading::West => "west",
+        }
+    }
+
+    /// Arrow pointing in the direction of the heading.
+    pub fn arrow(&self) -> char {
+        match self {
+            Heading::North => '↑',
+            Heading::East => '→',
+            Heading::South => '↓',
+            Heading::West => '←',
+        }
+    }
+
+    /// Heading after a quarter turn to the left.
+    pub fn left(&self) -> Heading {
+        Heading::ALL[(*self as usize + 3) % 4]
+    }
+
+    /// Heading after a quarter turn to the right.
+    pub fn right(&self) -> Heading {
+        Heading::ALL[(*self as usize + 1) % 4]
+    }
+}
+
+impl fmt::Display for Heading {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// Reads a heading from its name.
+impl FromStr for Heading {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        Heading::ALL
+            .into_iter()
+            .find(|heading| heading.name() == name)
+            .ok_or_else(|| {
+                format!("unknown heading `{name}` (available: north, east, south, west)")
+            })
+    }
+}
+
+/// A color of the pen, to paint the cells visited by the robot.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Color {
+    #[default]
+    Black,
+    White,
+    Red,
+    Orange,
+    Yellow,
+    Green,
+    Blue,
+    Purple,
+}
+
+impl Color {
+    /// Every color, in the order of their index.
+    pub const ALL: [Color; 8] = [
+        Color::Black,
+        Color::White,
+        Color::Red,
+        Color::Orange,
+        Color::Yellow,
+        Color::Green,
+        Color::Blue,
+        Color::Purple,
+    ];
+
+    /// Name of the color, as written in code mode.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Color::Black => "black",
+            Color::White => "white",
+            Color::Red => "red",
+            Color::Orange => "orange",
+            Color::Yellow => "yellow",
+            Color::Green => "green",
+            Color::Blue => "blue",
+            Color::Purple => "purple",
+        }
+    }
+
+    /// Position of the color in `Color::ALL`, to store it compactly.
+    pub fn index(&self) -> usize {
+        *self as usize
+    }
+
+    /// Value of the color as `[r, g, b]`, from the palette of the web pages.
+    pub fn rgb(&self) -> [u8; 3] {
+        match self {
+            Color::Black => [0x2e, 0x34, 0x40],
+            Color::White => [0xec, 0xef, 0xf4],
+            Color::Red => [0xbf, 0x61, 0x6a],
+            Color::Orange => [0xd0, 0x87, 0x70],
+            Color::Yellow => [0xeb, 0xcb, 0x8b],
+            Color::Green => [0xa3, 0xbe, 0x8c],
+            Color::Blue => [0x5e, 0x81, 0xac],
+            Color::Purple => [0xb4, 0x8e, 0xad],
+        }
+    }
+
+    /// Value of the color as a CSS hex code (`#rrggbb`).
+    pub fn hex(&self) -> String {
+        let [r, g, b] = self.rgb();
+        format!("#{r:02x}{g:02x}{b:02x}")
+    }
+}
+
+impl fmt::Display for Color {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// Reads a color from its name.
+impl FromStr for Color {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        Color::ALL
+            .into_iter()
+            .find(|color| color.name() == name)
+            .ok_or_else(|| {
+                let names: Vec<_> = Color::ALL.iter().map(Color::name).collect();
+                format!("unknown color `{name}` (available: {})", names.join(", "))
+            })
+    }
+}
+
+/// A command changing the world, sent by play mode buttons or by a line of code.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Command {
+    /// Move up one line, wrapping around to the last line
+    Up,
+    /// Move down one line, wrapping around to the first line
+    Down,
+    /// Move left one column, wrapping around to the last column
+    Left,
+    /// Move right one column, wrapping around to the first column
+    Right,
+    /// Teleport to `(i, j)`
+    GoTo(u32, u32),
+    /// Change the number of lines of the grid
+    SetLines(u32),
+    /// Change the number of columns of the grid
+    SetColumns(u32),
+    /// Go back to `(0, 0)`, removing the wall there if any
+    Reset,
+    /// Put a wall on cell `(i, j)`
+    Wall(u32, u32),
+    /// Start painting the cells the robot visits, beginning with its current cell
+    PenDown,
+    /// Stop painting
+    PenUp,
+    /// Change the color of the pen
+    PenColor(Color),
+    /// Put one more item on cell `(i, j)`
+    Item(u32, u32),
+    /// Pick up one item from the cell of the robot
+    Pick,
+    /// Drop one carried item on the cell of the robot
+    Drop,
+    /// Move one cell forward, in the direction the robot faces, wrapping around
+    Move,
+    /// Move one cell forward without wrapping around: facing an edge of the
+    /// grid is an error, as in Karel
+    Step,
+    /// Turn a quarter to the left
+    TurnLeft,
+    /// Turn a quarter to the right
+    TurnRight,
+}
+
+impl Command {
+    /// Short name of the command, usable as a metric label or a log field.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Command::Up => "up",
+            Command::Down => "down",
+            Command::Left => "left",
+            Command::Right => "right",
+            Command::GoTo(..) => "go_to",
+            Command::SetLines(_) => "nb_lines",
+            Command::SetColumns(_) => "nb_columns",
+            Command::Reset => "reset",
+            Command::Wall(..) => "wall",
+            Command::PenDown => "pen_down",
+            Command::PenUp => "pen_up",
+            Command::PenColor(_) => "pen_color",
+            Command::Item(..) => "item",
+            Command::Pick => "pick",
+            Command::Drop => "drop",
+            Command::Move => "move",
+            Command::Step => "step",
+            Command::TurnLeft => "turn_left",
+            Command::TurnRight => "turn_right",
+        }
+    }
+
+    /// Whether the command moves the robot (as opposed to changing the grid,
+    /// the pen or the items).
+    pub fn is_move(&self) -> bool {
+        matches!(
+            self,
+            Command::Up
+                | Command::Down
+                | Command::Left
+                | Command::Right
+                | Command::GoTo(..)
+                | Command::Reset
+                | Command::Move
+                | Command::Step
+        )
+    }
+}
+
+/// Writes the command the way it is written in code mode.
+impl fmt::Display for Command {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Command::Up => write!(f, "up"),
+            Command::Down => write!(f, "down"),
+            Command::Left => write!(f, "left"),
+            Command::Right => write!(f, "right"),
+            Command::GoTo(i, j) => write!(f, "go to ({i}, {j})"),
+            Command::SetLines(lines) => write!(f, "nb lines = {lines}"),
+            Command::SetColumns(columns) => write!(f, "nb columns = {columns}"),
+            Command::Reset => write!(f, "reset"),
+            Command::Wall(i, j) => write!(f, "wall ({i}, {j})"),
+            Command::PenDown => write!(f, "pen down"),
+            Command::PenUp => write!(f, "pen up"),
+            Command::PenColor(color) => write!(f, "pen color {color}"),
+            Command::Item(i, j) => write!(f, "item ({i}, {j})"),
+            Command::Pick => write!(f, "pick"),
+            Command::Drop => write!(f, "drop"),
+            Command::Move => write!(f, "move"),
+            Command::Step => write!(f, "step"),
+            Command::TurnLeft => write!(f, "turn left"),
+            Command::TurnRight => write!(f, "turn right"),
+        }
+    }
+}
+
+/// Error returned when a command cannot be applied to the world.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WorldError {
+    /// The target cell is not in the grid
+    OutOfGrid {
+        i: u32,
+        j: u32,
+        lines: u32,
+        columns: u32,
+    },
+    /// The grid would have no line or no column
+    EmptyGrid,
+    /// The robot cannot go on a wall, and a wall cannot be put under the robot
+    Wall { i: u32, j: u32 },
+    /// The robot cannot pick an item from an empty cell
+    NoItem { i: u32, j: u32 },
+    /// The robot cannot drop an item when it carries none
+    EmptyInventory,
+    /// The robot cannot step out of the grid
+    Edge(Heading),
+}
+
+impl fmt::Display for WorldError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WorldError::OutOfGrid {
+                i,
+                j,
+                lines,
+                columns,
+            } => write!(f, "({i}, {j}) is outside the {lines}x{columns} grid"),
+            WorldError::EmptyGrid => write!(f, "the grid needs at least one line and one column"),
+            WorldError::Wall { i, j } => write!(f, "there is a wall at ({i}, {j})"),
+            WorldError::NoItem { i, j } => write!(f, "there is no item to pick at ({i}, {j})"),
+            WorldError::EmptyInventory => write!(f, "the robot carries no item to drop"),
+            WorldError::Edge(heading) => {
+                write!(f, "the robot faces the {} edge of the grid", heading.name())
+            }
+        }
+    }
+}
+
+impl std::error::Error for WorldError {}
+
+/// A grid of `lines` x `columns` cells with the robot somewhere in it.
+///
+/// Some cells can hold a wall, and the last `TRAIL_LEN` positions of the robot
+/// are kept as its trail. While its pen is down, the robot paints the cells it
+/// visits, and they stay painted after the pen is up. Cells can also hold
+/// items, which the robot picks up into its inventory and drops elsewhere.
+/// The robot faces a `Heading`, used by `Command::Move` and `Command::Step`
+/// (moves with the arrows or `go to` don't change it). The robot is always
+/// inside the grid and never on a wall: every way of building or changing a
+/// `World` checks it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct World {
+    lines: u32,
+    columns: u32,
+    robot: Robot,
+    heading: Heading,
+    /// Cells `(i, j)` holding a wall
+    walls: BTreeSet<(u32, u32)>,
+    /// Positions of the robot, oldest first, ending with the current one
+    trail: Vec<Robot>,
+    /// Whether the robot paints the cells it visits
+    pen: bool,
+    pen_color: Color,
+    /// Cells `(i, j)` painted, with their color
+    paint: BTreeMap<(u32, u32), Color>,
+    /// Cells `(i, j)` holding items, with their number (never 0)
+    items: BTreeMap<(u32, u32), u32>,
+    /// Number of items carried by the robot
+    inventory: u32,
+}
+
+impl World {
+    /// Creates a grid with the robot at `(0, 0)`.
+    ///
+    /// # Errors
+    ///
+    /// Returns `WorldError::EmptyGrid` if `lines` or `columns` is 0.
+    pub fn new(lines: u32, columns: u32) -> Result<World, WorldError> {
+        World::with_robot(lines, columns, Robot::default())
+    }
+
+    /// Creates a grid with the robot at the given position.
+    ///
+    /// # Errors
+    ///
+    /// Returns `WorldError::EmptyGrid` if `lines` or `columns` is 0, and
+    /// `WorldError::OutOfGrid` if the robot is not in the grid.
+    pub fn with_robot(lines: u32, columns: u32, robot: Robot) -> Result<World, WorldError> {
+        if lines == 0 || columns == 0 {
+            return Err(WorldError::EmptyGrid);
+        }
+        let world = World {
+            lines,
+            columns,
+            robot,
+            heading: Heading::default(),
+            walls: BTreeSet::new(),
+            trail: vec![robot],
+            pen: false,
+            pen_color: Color::default(),
+            paint: BTreeMap::new(),
+            items: BTreeMap::new(),
+            inventory: 0,
+        };
+        world.check_in_grid(robot.i, robot.j)?;
+        Ok(world)
+    }
+
+    /// Number of lines of the grid.
+    pub fn lines(&self) -> u32 {
+        self.lines
+    }
+
+    /// Number of columns of the grid.
+    pub fn columns(&self) -> u32 {
+        self.columns
+    }
+
+    /// Current position of the robot.
+    pub fn robot(&self) -> Robot {
+        self.robot
+    }
+
+    /// Direction the robot faces.
+    pub fn heading(&self) -> Heading {
+        self.heading
+    }
+
+    /// Turns the robot, for example to restore a saved world.
+    pub fn set_heading(&mut self, heading: Heading) {
+        self.heading = heading;
+    }
+
+    /// Whether the robot can take one step towards `heading` without leaving
+    /// the grid or hitting a wall. Unlike moves, this doesn't wrap around: the
+    /// edges of the grid count as blocked, as in Karel.
+    pub fn is_clear(&self, heading: Heading) -> bool {
+        let Robot { i, j } = self.robot;
+        let next = match heading {
+            Heading::North => i.checked_sub(1).map(|i| (i, j)),
+            Heading::South => Some((i + 1, j)).filter(|&(i, _)| i < self.lines),
+            Heading::West => j.checked_sub(1).map(|j| (i, j)),
+            Heading::East => Some((i, j + 1)).filter(|&(_, j)| j < self.columns),
+        };
+        next.is_some_and(|(i, j)| !self.is_wall(i, j))
+    }
+
+    /// Cells `(i, j)` holding a wall, sorted by line then column.
+    pub fn walls(&self) -> impl Iterator<Item = (u32, u32)> + '_ {
+        self.walls.iter().copied()
+    }
+
+    /// Whether cell `(i, j)` holds a wall.
+    pub fn is_wall(&self, i: u32, j: u32) -> bool {
+        self.walls.contains(&(i, j))
+    }
+
+    /// Last positions of the robot, oldest first. The last one is the current position.
+    pub fn trail(&self) -> &[Robot] {
+        &self.trail
+    }
+
+    /// Replaces the trail, for example to restore a saved world.
+    ///
+    /// Positions outside of the grid are dropped, only the last `TRAIL_LEN`
+    /// are kept, and the current position is added at the end if missing.
+    ///
+    /// # Arguments
+    ///
+    /// * `trail` - Positions of the robot, oldest first.
+    pub fn set_trail(&mut self, trail: impl IntoIterator<Item = Robot>) {
+        self.trail = trail
+            .into_iter()
+            .filter(|cell| cell.i < self.lines && cell.j < self.columns)
+            .collect();
+        self.push_trail();
+    }
+
+    /// Whether the pen is down (the robot paints the cells it visits).
+    pub fn is_pen_down(&self) -> bool {
+        self.pen
+    }
+
+    /// Current color of the pen (kept while the pen is up).
+    pub fn pen_color(&self) -> Color {
+        self.pen_color
+    }
+
+    /// Sets the pen, for example to restore a saved world. Unlike `Command::PenDown`,
+    /// this doesn't paint the cell of the robot.
+    ///
+    /// # Arguments
+    ///
+    /// * `down` - Whether the pen is down.
+    /// * `color` - Color of the pen.
+    pub fn set_pen(&mut self, down: bool, color: Color) {
+        self.pen = down;
+        self.pen_color = color;
+    }
+
+    /// Painted cells `(i, j)` with their color, sorted by line then column.
+    pub fn painted(&self) -> impl Iterator<Item = ((u32, u32), Color)> + '_ {
+        self.paint.iter().map(|(&cell, &color)| (cell, color))
+    }
+
+    /// Color of cell `(i, j)`, if it was painted.
+    pub fn paint_at(&self, i: u32, j: u32) -> Option<Color> {
+        self.paint.get(&(i, j)).copied()
+    }
+
+    /// Paints cell `(i, j)`, for example to restore a saved world.
+    ///
+    /// # Errors
+    ///
+    /// Returns `WorldError::OutOfGrid` if the cell is not in the grid.
+    pub fn paint(&mut self, i: u32, j: u32, color: Color) -> Result<(), WorldError> {
+        self.check_in_grid(i, j)?;
+        self.paint.insert((i, j), color);
+        Ok(())
+    }
+
+    /// Cells `(i, j)` holding items, with their number, sorted by line then column.
+    pub fn items(&self) -> impl Iterator<Item = ((u32, u32), u32)> + '_ {
+        self.items.iter().map(|(&cell, &count)| (cell, count))
+    }
+
+    /// Number of items on cell `(i, j)`.
+    pub fn items_at(&self, i: u32, j: u32) -> u32 {
+        self.items.get(&(i, j)).copied().unwrap_or(0)
+    }
+
+    /// Puts `count` more items on cell `(i, j)`.
+    ///
+    /// # Errors
+    ///
+    /// Returns `WorldError::OutOfGrid` if the cell is not in the grid, and
+    /// `WorldError::Wall` if it holds a wall.
+    pub fn put_items(&mut self, i: u32, j: u32, count: u32) -> Result<(), WorldError> {
+        self.check_free(i, j)?;
+        if count > 0 {
+            let items = self.items.entry((i, j)).or_default();
+            *items = items.saturating_add(count);
+        }
+        Ok(())
+    }
+
+    /// Number of items carried by the robot.
+    pub fn inventory(&self) -> u32 {
+        self.inventory
+    }
+
+    /// Sets the number of items carried by the robot, for example to restore a
+    /// saved world.
+    pub fn set_inventory(&mut self, inventory: u32) {
+        self.inventory = inventory;
+    }
+
+    /// Paints the cell of the robot, if the pen is down.
+    fn paint_under_robot(&mut self) {
+        if self.pen {
+            self.paint
+                .insert((self.robot.i, self.robot.j), self.pen_color);
+        }
+    }
+
+    /// Adds the current position at the end of the trail, if it changed.
+    fn push_trail(&mut self) {
+        if self.trail.last() != Some(&self.robot) {
+            self.trail.push(self.robot);
+        }
+        if self.trail.len() > TRAIL_LEN {
+            self.trail.drain(..self.trail.len() - TRAIL_LEN);
+        }
+    }
+
+    /// Checks that `(i, j)` is a cell of the grid.
+    fn check_in_grid(&self, i: u32, j: u32) -> Result<(), WorldError> {
+        if i >= self.lines || j >= self.columns {
+            return Err(WorldError::OutOfGrid {
+                i,
+                j,
+                lines: self.lines,
+                columns: self.columns,
+            });
+        }
+        Ok(())
+    }
+
+    /// Checks that the robot can go to cell `(i, j)`.
+    fn check_free(&self, i: u32, j: u32) -> Result<(), WorldError> {
+        self.check_in_grid(i, j)?;
+        if self.is_wall(i, j) {
+            return Err(WorldError::Wall { i, j });
+        }
+        Ok(())
+    }
+
+    /// Position of the neighbour of the robot towards `heading`, wrapping
+    /// around the edges of the grid.
+    fn neighbour(&self, heading: Heading) -> Robot {
+        let Robot { i, j } = self.robot;
+        match heading {
+            Heading::North => Robot {
+                i: if i == 0 { self.lines - 1 } else { i - 1 },
+                j,
+            },
+            Heading::South => Robot {
+                i: if i == self.lines - 1 { 0 } else { i + 1 },
+                j,
+            },
+            Heading::West => Robot {
+                i,
+                j: if j == 0 { self.columns - 1 } else { j - 1 },
+            },
+            Heading::East => Robot {
+                i,
+                j: if j == self.columns - 1 { 0 } else { j + 1 },
+            },
+        }
+    }
+
+    /// Puts a wall on cell `(i, j)` (nothing happens if there is already one).
+    /// Items on the cell disappear.
+    ///
+    /// # Errors
+    ///
+    /// Returns `WorldError::OutOfGrid` if the cell is not in the grid, and
+    /// `WorldError::Wall` if the robot is on it.
+    pub fn add_wall(&mut self, i: u32, j: u32) -> Result<(), WorldError> {
+        self.check_in_grid(i, j)?;
+        if self.robot == (Robot { i, j }) {
+            return Err(WorldError::Wall { i, j });
+        }
+        self.walls.insert((i, j));
+        self.items.remove(&(i, j));
+        Ok(())
+    }
+
+    /// Applies a command to the world.
+    ///
+    /// Moves other than `Command::Step` wrap around the edges of the grid.
+    /// When the grid shrinks, the robot is moved back onto its last line or
+    /// column if needed, and walls, paint and items left outside of the grid
+    /// (or walls under the robot) disappear. If the pen is down, the cell
+    /// where the robot ends up is painted.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `WorldError` (and leaves the world unchanged) if the command
+    /// would put the robot out of the grid or on a wall, step over an edge of
+    /// the grid, empty the grid, pick an item from an empty cell or drop an
+    /// item the robot doesn't carry.
+    pub fn apply(&mut self, command: Command) -> Result<(), WorldError> {
+        match command {
+            Command::Up | Command::Down | Command::Left | Command::Right | Command::Move => {
+                let heading = match command {
+                    Command::Up => Heading::North,
+                    Command::Down => Heading::South,
+                    Command::Left => Heading::West,
+                    Command::Right => Heading::East,
+                    _ => self.heading,
+                };
+                let Robot { i, j } = self.neighbour(heading);
+                self.check_free(i, j)?;
+                self.robot = Robot { i, j };
+            }
+            Command::Step => {
+                let heading = self.heading;
+                let Robot { i, j } = self.robot;
+                let at_edge = match heading {
+                    Heading::North => i == 0,
+                    Heading::South => i == self.lines - 1,
+                    Heading::West => j == 0,
+                    Heading::East => j == self.columns - 1,
+                };
+                if at_edge {
+                    return Err(WorldError::Edge(heading));
+                }
+                let Robot { i, j } = self.neighbour(heading);
+                self.check_free(i, j)?;
+                self.robot = Robot { i, j };
+            }
+            Command::TurnLeft => self.heading = self.heading.left(),
+            Command::TurnRight => self.heading = self.heading.right(),
+            Command::GoTo(i, j) => {
+                self.check_free(i, j)?;
+                self.robot = Robot { i, j };
+            }
+            Command::SetLines(lines) => {
+                if lines == 0 {
+                    return Err(WorldError::EmptyGrid);
+                }
+                self.lines = lines;
+                self.robot.i = self.robot.i.min(lines - 1);
+                self.crop();
+            }
+            Command::SetColumns(columns) => {
+                if columns == 0 {
+                    return Err(WorldError::EmptyGrid);
+                }
+                self.columns = columns;
+                self.robot.j = self.robot.j.min(columns - 1);
+                self.crop();
+            }
+            Command::Reset => {
+                self.walls.remove(&(0, 0));
+                self.robot = Robot::default();
+            }
+            Command::Wall(i, j) => return self.add_wall(i, j),
+            Command::PenDown => self.pen = true,
+            Command::PenUp => self.pen = false,
+            Command::PenColor(color) => self.pen_color = color,
+            Command::Item(i, j) => return self.put_items(i, j, 1),
+            Command::Pick => {
+                let Robot { i, j } = self.robot;
+                match self.items.get_mut(&(i, j)) {
+                    Some(1) => {
+                        self.items.remove(&(i, j));
+                    }
+                    Some(count) => *count -= 1,
+                    None => return Err(WorldError::NoItem { i, j }),
+                }
+                self.inventory = self.inventory.saturating_add(1);
+            }
+            Command::Drop => {
+                if self.inventory == 0 {
+                    return Err(WorldError::EmptyInventory);
+                }
+                self.inventory -= 1;
+                let Robot { i, j } = self.robot;
+                let items = self.items.entry((i, j)).or_default();
+                *items = items.saturating_add(1);
+            }
+        }
+        self.push_trail();
+        self.paint_under_robot();
+        Ok(())
+    }
+
+    /// Removes the walls, paint, items and trail positions left outside of the grid
+    /// after a resize, and the wall under the robot if it was moved onto one.
+    fn crop(&mut self) {
+        let (lines, columns, robot) = (self.lines, self.columns, self.robot);
+        self.walls
+            .retain(|&(i, j)| i < lines && j < columns && (Robot { i, j }) != robot);
+        self.paint.retain(|&(i, j), _| i < lines && j < columns);
+        self.items.retain(|&(i, j), _| i < lines && j < columns);
+        self.trail.retain(|cell| cell.i < lines && cell.j < columns);
+    }
+}
@@ -0,0 +1,392 @@
+//! Programs as a JSON abstract syntax tree, for block-based editors.
+//!
+//! Drag-and-drop editors build programs as trees of blocks rather than text.
+//! They can send them as a JSON document, checked against the schema served
+//! on `/ast/schema` (`static/ast.schema.json`), and run by the same
+//! interpreter as text programs:
+//!
+//! ```json
+//! {
+//!   "version": 1,
+//!   "procedures": [
+//!     { "name": "turn_around", "body": [{ "type": "turn", "direction": "left" }, { "type": "turn", "direction": "left" }] }
+//!   ],
+//!   "statements": [
+//!     { "type": "repeat", "count": 2, "body": [
+//!       { "type": "if", "condition": { "type": "front_is_clear" },
+//!         "then": [{ "type": "move", "direction": "forward" }],
+//!         "else": [{ "type": "call", "name": "turn_around" }] }
+//!     ] }
+//!   ]
+//! }
+//! ```
+//!
+//! A tree is turned into text of the code mode language (see `Program`'s
+//! `Display`) and parsed back, so it goes through the same checks as text,
+//! and errors point at lines of that text (given by `/ast/to-text`). The other
+//! way, any program in any dialect can be turned into a tree.
+
+use rustbot_core::{
+    Color, Command, Condition, Heading, Procedure, Program, Statement, StatementKind,
+};
+use serde::{Deserialize, Serialize};
+
+/// Version of the document written by `AstProgram::new`.
+pub const VERSION: u32 = 1;
+
+/// JSON Schema of `AstProgram`, served on `/ast/schema`.
+pub const SCHEMA: &str = include_str!("../static/ast.schema.json");
+
+/// A whole program.
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct AstProgram {
+    /// Version of the document
+    pub version: u32,
+    /// Procedures, called by `call` statements
+    #[serde(default)]
+    pub procedures: Vec<AstProcedure>,
+    pub statements: Vec<AstStatement>,
+}
+
+/// A named list of statements.
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct AstProcedure {
+    pub name: String,
+    pub body: Vec<AstStatement>,
+}
+
+/// Direction of a `move` statement.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Direction {
+    /// The direction the robot faces
+    Forward,
+    Up,
+    Down,
+    Left,
+    Right,
+}
+
+/// Side of a `turn` statement.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Side {
+    Left,
+    Right,
+}
+
+/// A statement, with its `type`.
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum AstStatement {
+    Move {
+        direction: Direction,
+    },
+    /// A move forward which fails at the edges of the grid (Karel's `move()`)
+    Step,
+    Turn {
+        direction: Side,
+    },
+    GoTo {
+        i: u32,
+        j: u32,
+    },
+    Wall {
+        i: u32,
+        j: u32,
+    },
+    Item {
+        i: u32,
+        j: u32,
+    },
+    SetLines {
+        lines: u32,
+    },
+    SetColumns {
+        columns: u32,
+    },
+    Pen {
+        down: bool,
+    },
+    PenColor {
+        /// Name of the color (see `rustbot_core::Color`)
+        color: String,
+    },
+    Pick,
+    Drop,
+    Repeat {
+        count: u32,
+        body: Vec<AstStatement>,
+    },
+    If {
+        condition: AstCondition,
+        then: Vec<AstStatement>,
+        #[serde(rename = "else", default, skip_serializing_if = "Vec::is_empty")]
+        otherwise: Vec<AstStatement>,
+    },
+    While {
+        condition: AstCondition,
+        body: Vec<AstStatement>,
+    },
+    Call {
+        name: String,
+    },
+}
+
+/// A condition of `if` and `while`, with its `type`.
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum AstCondition {
+    OnItem,
+    CarryingItem,
+    FrontIsClear,
+    LeftIsClear,
+    RightIsClear,
+    Facing {
+        /// Name of the heading (see `rustbot_core::Heading`)
+        heading: String,
+    },
+    Not {
+        condition: Box<AstCondition>,
+    },
+    And {
+        left: Box<AstCondition>,
+        right: Box<AstCondition>,
+    },
+    Or {
+        left: Box<AstCondition>,
+        right: Box<AstCondition>,
+    },
+}
+
+impl AstProgram {
+    /// Builds the tree of a parsed program.
+    pub fn new(program: &Program) -> Self {
+        AstProgram {
+            version: VERSION,
+            procedures: program
+                .procedures
+                .iter()
+                .map(|(name, procedure)| AstProcedure {
+                    name: name.clone(),
+                    body: statements(&procedure.body),
+                })
+                .collect(),
+            statements: statements(&program.statements),
+        }
+    }
+
+    /// Writes the program in the code mode language.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error message for an unknown version, color or heading, a
+    /// procedure name that is not a single word or is defined twice, or a call
+    /// to an unknown procedure. Other mistakes (a procedure named like a command…) are found
+    /// when parsing the text.
+    pub fn to_text(&self) -> Result<String, String> {
+        if self.version != VERSION {
+            return Err(format!(
+                "unsupported AST version {} (this server reads version {VERSION})",
+                self.version
+            ));
+        }
+        let names: Vec<&str> = self.procedures.iter().map(|p| p.name.as_str()).collect();
+        for (index, name) in names.iter().enumerate() {
+            if name.is_empty() || !name.chars().all(|c| c.is_alphanumeric() || c == '_') {
+                return Err(format!("`{name}` can't be the name of a procedure"));
+            }
+            if names[..index].contains(name) {
+                return Err(format!("procedure `{name}` is already defined"));
+            }
+        }
+        let mut program = Program {
+            statements: program_statements(&self.statements, &names)?,
+            ..Program::default()
+        };
+        for procedure in &self.procedures {
+            let body = program_statements(&procedure.body, &names)?;
+            program
+                .procedures
+                .insert(procedure.name.clone(), Procedure { line: 0, body });
+        }
+        Ok(program.to_string())
+    }
+}
+
+/// Builds the trees of statements.
+fn statements(statements: &[Statement]) -> Vec<AstStatement> {
+    statements.iter().map(statement).collect()
+}
+
+/// Builds the tree of a statement.
+fn statement(statement: &Statement) -> AstStatement {
+    match &statement.kind {
+        StatementKind::Command(command) => match *command {
+            Command::Move => AstStatement::Move {
+                direction: Direction::Forward,
+            },
+            Command::Step => AstStatement::Step,
+            Command::Up => AstStatement::Move {
+                direction: Direction::Up,
+            },
+            Command::Down => AstStatement::Move {
+                direction: Direction::Down,
+            },
+            Command::Left => AstStatement::Move {
+                direction: Direction::Left,
+            },
+            Command::Right => AstStatement::Move {
+                direction: Direction::Right,
+            },
+            Command::TurnLeft => AstStatement::Turn {
+                direction: Side::Left,
+            },
+            Command::TurnRight => AstStatement::Turn {
+                direction: Side::Right,
+            },
+            Command::GoTo(i, j) => AstStatement::GoTo { i, j },
+            // Only sent by play mode, never parsed from a program
+            Command::Reset => AstStatement::GoTo { i: 0, j: 0 },
+            Command::Wall(i, j) => AstStatement::Wall { i, j },
+            Command::Item(i, j) => AstStatement::Item { i, j },
+            Command::SetLines(lines) => AstStatement::SetLines { lines },
+            Command::SetColumns(columns) => AstStatement::SetColumns { columns },
+            Command::PenDown => AstStatement::Pen { down: true },
+            Command::PenUp => AstStatement::Pen { down: false },
+            Command::PenColor(color) => AstStatement::PenColor {
+                color: color.to_string(),
+            },
+            Command::Pick => AstStatement::Pick,
+            Command::Drop => AstStatement::Drop,
+        },
+        StatementKind::If {
+            condition: test,
+            then,
+            otherwise,
+        } => AstStatement::If {
+            condition: condition(test),
+            then: statements(then),
+            otherwise: statements(otherwise),
+        },
+        StatementKind::While {
+            condition: test,
+            body,
+        } => AstStatement::While {
+            condition: condition(test),
+            body: statements(body),
+        },
+        StatementKind::Repeat { count, body } => AstStatement::Repeat {
+            count: *count,
+            body: statements(body),
+        },
+        StatementKind::Call(name) => AstStatement::Call { name: name.clone() },
+    }
+}
+
+/// Builds the tree of a condition.
+fn condition(condition: &Condition) -> AstCondition {
+    let boxed = |inner| Box::new(self::condition(inner));
+    match condition {
+        Condition::OnItem => AstCondition::OnItem,
+        Condition::CarryingItem => AstCondition::CarryingItem,
+        Condition::FrontIsClear => AstCondition::FrontIsClear,
+        Condition::LeftIsClear => AstCondition::LeftIsClear,
+        Condition::RightIsClear => AstCondition::RightIsClear,
+        Condition::Facing(heading) => AstCondition::Facing {
+            heading: heading.to_string(),
+        },
+        Condition::Not(inner) => AstCondition::Not {
+            condition: boxed(inner),
+        },
+        Condition::And(left, right) => AstCondition::And {
+            left: boxed(left),
+            right: boxed(right),
+        },
+        Condition::Or(left, right) => AstCondition::Or {
+            left: boxed(left),
+            right: boxed(right),
+        },
+    }
+}
+
+/// Rebuilds statements from their trees (without line numbers), calling the
+/// procedures of `names`.
+fn program_statements(
+    statements: &[AstStatement],
+    names: &[&str],
+) -> Result<Vec<Statement>, String> {
+    statements
+        .iter()
+        .map(|statement| program_statement(statement, names))
+        .collect()
+}
+
+/// Rebuilds a statement from its tree (without line number).
+fn program_statement(statement: &AstStatement, names: &[&str]) -> Result<Statement, String> {
+    let command = |command| StatementKind::Command(command);
+    let kind = match statement {
+        AstStatement::Move { direction } => command(match direction {
+            Direction::Forward => Command::Move,
+            Direction::Up => Command::Up,
+            Direction::Down => Command::Down,
+            Direction::Left => Command::Left,
+            Direction::Right => Command::Right,
+        }),
+        AstStatement::Step => command(Command::Step),
+        AstStatement::Turn { direction } => command(match direction {
+            Side::Left => Command::TurnLeft,
+            Side::Right => Command::TurnRight,
+        }),
+        AstStatement::GoTo { i, j } => command(Command::GoTo(*i, *j)),
+        AstStatement::Wall { i, j } => command(Command::Wall(*i, *j)),
+        AstStatement::Item { i, j } => command(Command::Item(*i, *j)),
+        AstStatement::SetLines { lines } => command(Command::SetLines(*lines)),
+        AstStatement::SetColumns { columns } => command(Command::SetColumns(*columns)),
+        AstStatement::Pen { down: true } => command(Command::PenDown),
+        AstStatement::Pen { down: false } => command(Command::PenUp),
+        AstStatement::PenColor { color } => command(Command::PenColor(color.parse::<Color>()?)),
+        AstStatement::Pick => command(Command::Pick),
+        AstStatement::Drop => command(Command::Drop),
+        AstStatement::Repeat { count, body } => StatementKind::Repeat {
+            count: *count,
+            body: program_statements(body, names)?,
+        },
+        AstStatement::If {
+            condition,
+            then,
+            otherwise,
+        } => StatementKind::If {
+            condition: program_condition(condition)?,
+            then: program_statements(then, names)?,
+            otherwise: program_statements(otherwise, names)?,
+        },
+        AstStatement::While { condition, body } => StatementKind::While {
+            condition: program_condition(condition)?,
+            body: program_statements(body, names)?,
+        },
+        AstStatement::Call { name } if names.contains(&name.as_str()) => {
+            StatementKind::Call(name.clone())
+        }
+        AstStatement::Call { name } => return Err(format!("unknown procedure `{name}`")),
+    };
+    Ok(Statement { line: 0, kind })
+}
+
+/// Rebuilds a condition from its tree.
+fn program_condition(condition: &AstCondition) -> Result<Condition, String> {
+    let boxed = |inner| program_condition(inner).map(Box::new);
+    Ok(match condition {
+        AstCondition::OnItem => Condition::OnItem,
+        AstCondition::CarryingItem => Condition::CarryingItem,
+        AstCondition::FrontIsClear => Condition::FrontIsClear,
+        AstCondition::LeftIsClear => Condition::LeftIsClear,
+        AstCondition::RightIsClear => Condition::RightIsClear,
+        AstCondition::Facing { heading } => Condition::Facing(heading.parse::<Heading>()?),
+        AstCondition::Not { condition } => Condition::Not(boxed(condition)?),
+        AstCondition::And { left, right } => Condition::And(boxed(left)?, boxed(right)?),
+        AstCondition::Or { left, right } => Condition::Or(boxed(left)?, boxed(right)?),
+    })
+}
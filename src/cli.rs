@@ -0,0 +1,253 @@
+//! Command-line tools working offline, without the web server.
+
+use std::io::Read;
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+use rustbot_core::{Dialect, Limits, Robot, World, karel, render};
+
+use crate::config::InterpreterConfig;
+use crate::repl::{self, ReplArgs};
+use crate::tui::{self, TuiArgs};
+
+/// Subcommands of the `rustbot` binary. Without any, the web server is started.
+#[derive(clap::Subcommand)]
+pub enum Commands {
+    /// Run a code mode program and print the trace, the final position and the grid
+    Run(RunArgs),
+    /// Type code mode commands one at a time and see the grid after each one
+    Repl(ReplArgs),
+    /// Format a code mode program: indentation, spaces and lowercase keywords
+    Fmt(FmtArgs),
+    /// Play mode in the terminal: move the robot with the arrow keys
+    Tui(TuiArgs),
+}
+
+/// Starting world, shared by the subcommands.
+#[derive(clap::Args)]
+pub struct WorldArgs {
+    /// Number of lines of the grid
+    #[arg(long, visible_alias = "lines", default_value_t = 5)]
+    rows: u32,
+    /// Number of columns of the grid
+    #[arg(long, visible_alias = "columns", default_value_t = 5)]
+    cols: u32,
+    /// Starting position of the robot, as `i,j`
+    #[arg(long, default_value = "0,0", value_parser = parse_position)]
+    start: Robot,
+    /// Karel world file to start from, instead of the options above
+    #[arg(long, value_name = "FILE", conflicts_with_all = ["rows", "cols", "start"])]
+    karel_world: Option<PathBuf>,
+}
+
+impl WorldArgs {
+    /// Builds the starting world. Lines of a Karel world file that can't be
+    /// imported are printed as warnings.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error message for a grid empty or bigger than the limits of
+    /// programs, a start outside of it, or an unreadable or invalid Karel
+    /// world file.
+    pub fn world(&self) -> Result<World, String> {
+        let limits = Limits::default();
+        let Some(path) = &self.karel_world else {
+            if self.rows == 0
+                || self.cols == 0
+                || self.rows > limits.max_lines
+                || self.cols > limits.max_columns
+            {
+                return Err(format!(
+                    "the grid must be between 1x1 and {}x{}, not {}x{}",
+                    limits.max_lines, limits.max_columns, self.rows, self.cols
+                ));
+            }
+            return World::with_robot(self.rows, self.cols, self.start).map_err(|e| e.to_string());
+        };
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| format!("cannot read {}: {e}", path.display()))?;
+        let imported =
+            karel::parse_world(&text, &limits).map_err(|e| format!("{}: {e}", path.display()))?;
+        for warning in &imported.warnings {
+            eprintln!("warning: {}: {warning}", path.display());
+        }
+        Ok(imported.world)
+    }
+}
+
+/// Arguments of `rustbot run`.
+#[derive(clap::Args)]
+pub struct RunArgs {
+    /// File containing the program (`-` to read it from stdin)
+    program: PathBuf,
+    #[command(flatten)]
+    world: WorldArgs,
+    /// Language of the program: `rustbot`, `karel` or `logo`
+    #[arg(long, default_value_t = Dialect::default())]
+    dialect: Dialect,
+    /// Maximum number of steps (commands, conditions, loop turns and calls)
+    #[arg(long, default_value_t = Limits::default().max_steps)]
+    max_steps: usize,
+    /// Maximum size (in bytes) of the program
+    #[arg(long, default_value_t = InterpreterConfig::default().max_program_len)]
+    max_program_len: usize,
+    /// Don't print the executed commands
+    #[arg(long)]
+    no_trace: bool,
+}
+
+/// Arguments of `rustbot fmt`.
+#[derive(clap::Args)]
+pub struct FmtArgs {
+    /// File containing the program (`-` to read it from stdin)
+    program: PathBuf,
+    /// Don't print the program, fail if it is not formatted
+    #[arg(long, conflicts_with = "write")]
+    check: bool,
+    /// Write the formatted program back to the file
+    #[arg(long)]
+    write: bool,
+}
+
+/// Parses a position written `i,j` (spaces allowed).
+fn parse_position(text: &str) -> Result<Robot, String> {
+    let (i, j) = text
+        .split_once(',')
+        .ok_or_else(|| format!("`{text}` is not a position like `2,3`"))?;
+    let i = i.trim().parse().map_err(|e| format!("line `{i}`: {e}"))?;
+    let j = j.trim().parse().map_err(|e| format!("column `{j}`: {e}"))?;
+    Ok(Robot { i, j })
+}
+
+/// Runs a subcommand.
+///
+/// # Returns
+///
+/// The exit code of the process.
+pub fn execute(command: Commands) -> ExitCode {
+    match command {
+        Commands::Run(args) => run(args),
+        Commands::Repl(args) => repl::repl(args),
+        Commands::Fmt(args) => fmt(args),
+        Commands::Tui(args) => tui::tui(args),
+    }
+}
+
+/// Reads a program from a file, or from stdin for `-`.
+fn read_program(path: &PathBuf) -> std::io::Result<String> {
+    if path.as_os_str() == "-" {
+        let mut source = String::new();
+        std::io::stdin().read_to_string(&mut source)?;
+        Ok(source)
+    } else {
+        std::fs::read_to_string(path)
+    }
+}
+
+/// `rustbot run`: executes a program exactly as `/user-code` does.
+///
+/// # Returns
+///
+/// `0` if the program ran, `1` if it failed, `2` if it could not be started
+/// (unreadable or too long file, impossible starting world).
+fn run(args: RunArgs) -> ExitCode {
+    let source = match read_program(&args.program) {
+        Ok(source) => source,
+        Err(e) => {
+            eprintln!("error: cannot read {}: {e}", args.program.display());
+            return ExitCode::from(2);
+        }
+    };
+    if source.len() > args.max_program_len {
+        eprintln!(
+            "error: the program is too long (max {} bytes)",
+            args.max_program_len
+        );
+        return ExitCode::from(2);
+    }
+    let mut world = match args.world.world() {
+        Ok(world) => world,
+        Err(e) => {
+            eprintln!("error: {e}");
+            return ExitCode::from(2);
+        }
+    };
+    let limits = Limits {
+        max_steps: args.max_steps,
+        ..Limits::default()
+    };
+
+    let result = args.dialect.parse(&source).and_then(|program| {
+        // Warnings don't prevent the program from running
+        for warning in rustbot_core::lint(&program, &world) {
+            eprintln!("warning: {warning}");
+        }
+        rustbot_core::run(&program, &mut world, &limits)
+    });
+    match result {
+        Ok(run) => {
+            if !args.no_trace {
+                println!("Trace:");
+                for step in &run.trace {
+                    println!(
+                        "  line {}: {} -> ({}, {})",
+                        step.line, step.command, step.robot.i, step.robot.j
+                    );
+                }
+            }
+            let robot = world.robot();
+            println!(
+                "Final position: ({}, {}) in a {}x{} grid",
+                robot.i,
+                robot.j,
+                world.lines(),
+                world.columns()
+            );
+            print!("{}", render::ascii(&world));
+            ExitCode::SUCCESS
+        }
+        Err(e) => {
+            eprintln!("error: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// `rustbot fmt`: formats a program exactly as `/format` does.
+///
+/// # Returns
+///
+/// `0` if the program was formatted (or already is, with `--check`), `1` if it
+/// is not valid or not formatted with `--check`, `2` if the file could not be
+/// read or written.
+fn fmt(args: FmtArgs) -> ExitCode {
+    let source = match read_program(&args.program) {
+        Ok(source) => source,
+        Err(e) => {
+            eprintln!("error: cannot read {}: {e}", args.program.display());
+            return ExitCode::from(2);
+        }
+    };
+    let formatted = match rustbot_core::format(&source) {
+        Ok(formatted) => formatted,
+        Err(e) => {
+            eprintln!("error: {e}");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    if args.check {
+        if formatted != source {
+            eprintln!("{} is not formatted", args.program.display());
+            return ExitCode::FAILURE;
+        }
+    } else if args.write && args.program.as_os_str() != "-" {
+        if let Err(e) = std::fs::write(&args.program, formatted) {
+            eprintln!("error: cannot write {}: {e}", args.program.display());
+            return ExitCode::from(2);
+        }
+    } else {
+        print!("{formatted}");
+    }
+    ExitCode::SUCCESS
+}
@@ -0,0 +1,365 @@
+//! Runtime configuration of the RustBot server.
+//!
+//! Values are resolved in this order (first found wins):
+//! 1. command-line flags (`--port 3001`),
+//! 2. `RUSTBOT_*` environment variables (`RUSTBOT_PORT=3001`),
+//! 3. the optional TOML file given with `--config`,
+//! 4. built-in defaults.
+//!
+//! Templates are compiled into the binary by Askama, so they are not part of the
+//! runtime configuration: only the static files directory can be changed.
+
+use std::fmt;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::path::PathBuf;
+
+use clap::ValueEnum;
+use serde::Deserialize;
+
+/// Command-line arguments of the server.
+///
+/// Every flag can also be given through the matching `RUSTBOT_*` environment variable.
+#[derive(clap::Args)]
+pub struct ServerArgs {
+    /// Path to a TOML configuration file
+    #[arg(long, short, env = "RUSTBOT_CONFIG")]
+    config: Option<PathBuf>,
+    /// Address to listen on
+    #[arg(long, env = "RUSTBOT_BIND")]
+    bind: Option<IpAddr>,
+    /// Port to listen on
+    #[arg(long, short, env = "RUSTBOT_PORT")]
+    port: Option<u16>,
+    /// Directory served under `/static`
+    #[arg(long, env = "RUSTBOT_STATIC_DIR")]
+    static_dir: Option<PathBuf>,
+    /// Seconds between a shutdown signal and closing the listener, while `/readyz` answers 503
+    #[arg(long, env = "RUSTBOT_DRAIN_DELAY")]
+    drain_delay: Option<u64>,
+    /// Default number of lines in grid
+    #[arg(long, env = "RUSTBOT_DEFAULT_LINES")]
+    default_lines: Option<u32>,
+    /// Default number of columns in grid
+    #[arg(long, env = "RUSTBOT_DEFAULT_COLUMNS")]
+    default_columns: Option<u32>,
+    /// Maximum authorized number of lines
+    #[arg(long, env = "RUSTBOT_MAX_LINES")]
+    max_lines: Option<u32>,
+    /// Maximum authorized number of columns
+    #[arg(long, env = "RUSTBOT_MAX_COLUMNS")]
+    max_columns: Option<u32>,
+    /// Maximum number of items on a cell, or carried by the robot
+    #[arg(long, env = "RUSTBOT_MAX_ITEMS")]
+    max_items: Option<u32>,
+    /// Maximum size (in bytes) of a program sent in code mode
+    #[arg(long, env = "RUSTBOT_MAX_PROGRAM_LEN")]
+    max_program_len: Option<usize>,
+    /// Maximum number of steps (commands, conditions, loop turns and calls) of
+    /// a single program
+    #[arg(long, env = "RUSTBOT_MAX_STEPS")]
+    max_steps: Option<usize>,
+    /// Lifetime of session cookies in seconds (cookies last until the browser closes if unset)
+    #[arg(long, env = "RUSTBOT_SESSION_MAX_AGE")]
+    session_max_age: Option<u64>,
+    /// Only send session cookies over HTTPS
+    #[arg(long, env = "RUSTBOT_SECURE_COOKIES")]
+    secure_cookies: Option<bool>,
+    /// SQLite file where saved programs are stored (created if missing)
+    #[arg(long, env = "RUSTBOT_DATABASE")]
+    database: Option<PathBuf>,
+    /// Format of the logs
+    #[arg(long, env = "RUSTBOT_LOG_FORMAT")]
+    log_format: Option<LogFormat>,
+    /// Minimum level (or `tracing` filter directives) of the logs
+    #[arg(long, env = "RUSTBOT_LOG_LEVEL")]
+    log_level: Option<String>,
+}
+
+/// Full configuration of the server.
+///
+/// Mirrors the layout of the TOML configuration file:
+/// ```toml
+/// [server]
+/// bind = "127.0.0.1"
+/// port = 3001
+///
+/// [grid]
+/// max_lines = 10
+/// ```
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub server: ServerConfig,
+    pub grid: GridConfig,
+    pub interpreter: InterpreterConfig,
+    pub session: SessionConfig,
+    pub storage: StorageConfig,
+    pub log: LogConfig,
+}
+
+/// Where the server listens and what it serves.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerConfig {
+    pub bind: IpAddr,
+    pub port: u16,
+    pub static_dir: PathBuf,
+    /// Seconds between a shutdown signal and closing the listener, during
+    /// which `/readyz` already answers `503` so load balancers stop sending
+    /// new traffic
+    pub drain_delay: u64,
+}
+
+/// Default and maximum grid sizes.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct GridConfig {
+    /// Default number of lines in grid
+    pub default_lines: u32,
+    /// Default number of columns in grid
+    pub default_columns: u32,
+    /// Maximum authorized number of lines
+    pub max_lines: u32,
+    /// Maximum authorized number of columns
+    pub max_columns: u32,
+    /// Maximum number of items on a cell, or carried by the robot
+    pub max_items: u32,
+}
+
+/// Limits applied to programs sent in code mode.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct InterpreterConfig {
+    /// Maximum size (in bytes) of a program
+    pub max_program_len: usize,
+    /// Maximum number of steps (commands, conditions, loop turns and calls) of
+    /// a single program
+    pub max_steps: usize,
+}
+
+/// Settings of the cookies holding the robot state.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SessionConfig {
+    /// Lifetime of cookies in seconds, `None` for browser-session cookies
+    pub max_age: Option<u64>,
+    /// Only send cookies over HTTPS
+    pub secure: bool,
+}
+
+/// Where programs saved in code mode are stored.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct StorageConfig {
+    /// SQLite file, created if missing
+    pub database: PathBuf,
+}
+
+/// How logs are written to stdout.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LogConfig {
+    pub format: LogFormat,
+    /// Minimum level, or `tracing` filter directives such as `rustbot=debug`
+    pub level: String,
+}
+
+/// Output format of the logs.
+#[derive(Debug, Clone, Copy, Default, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    /// Human-readable lines
+    #[default]
+    Text,
+    /// One JSON object per line
+    Json,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        ServerConfig {
+            bind: IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+            port: 3000,
+            static_dir: PathBuf::from("static"),
+            drain_delay: 0,
+        }
+    }
+}
+
+impl Default for GridConfig {
+    fn default() -> Self {
+        GridConfig {
+            default_lines: 5,
+            default_columns: 5,
+            max_lines: 20,
+            max_columns: 20,
+            max_items: 999,
+        }
+    }
+}
+
+impl Default for StorageConfig {
+    fn default() -> Self {
+        StorageConfig {
+            database: PathBuf::from("rustbot.db"),
+        }
+    }
+}
+
+impl Default for LogConfig {
+    fn default() -> Self {
+        LogConfig {
+            format: LogFormat::Text,
+            level: "info".to_string(),
+        }
+    }
+}
+
+impl Default for InterpreterConfig {
+    fn default() -> Self {
+        InterpreterConfig {
+            max_program_len: 10_000,
+            max_steps: 1_000,
+        }
+    }
+}
+
+/// Error returned when the configuration cannot be loaded.
+#[derive(Debug)]
+pub enum ConfigError {
+    /// The configuration file could not be read
+    Io(PathBuf, std::io::Error),
+    /// The configuration file is not valid TOML or has unknown keys
+    Toml(PathBuf, toml::de::Error),
+    /// Values are inconsistent (for example a default bigger than the maximum)
+    Invalid(String),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Io(path, e) => write!(f, "cannot read {}: {e}", path.display()),
+            ConfigError::Toml(path, e) => write!(f, "invalid config file {}: {e}", path.display()),
+            ConfigError::Invalid(msg) => write!(f, "invalid configuration: {msg}"),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+impl Config {
+    /// Builds the configuration from the command line, the environment and the
+    /// optional configuration file.
+    ///
+    /// # Arguments
+    ///
+    /// * `args` - Server flags, already merged with environment variables by clap.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `ConfigError` if the file cannot be read or parsed, or if the
+    /// resulting values are inconsistent.
+    pub fn load(args: ServerArgs) -> Result<Config, ConfigError> {
+        let mut config = match &args.config {
+            Some(path) => {
+                let content =
+                    std::fs::read_to_string(path).map_err(|e| ConfigError::Io(path.clone(), e))?;
+                toml::from_str(&content).map_err(|e| ConfigError::Toml(path.clone(), e))?
+            }
+            None => Config::default(),
+        };
+
+        // Command line and environment override the file
+        if let Some(bind) = args.bind {
+            config.server.bind = bind;
+        }
+        if let Some(port) = args.port {
+            config.server.port = port;
+        }
+        if let Some(static_dir) = args.static_dir {
+            config.server.static_dir = static_dir;
+        }
+        if let Some(drain_delay) = args.drain_delay {
+            config.server.drain_delay = drain_delay;
+        }
+        if let Some(default_lines) = args.default_lines {
+            config.grid.default_lines = default_lines;
+        }
+        if let Some(default_columns) = args.default_columns {
+            config.grid.default_columns = default_columns;
+        }
+        if let Some(max_lines) = args.max_lines {
+            config.grid.max_lines = max_lines;
+        }
+        if let Some(max_columns) = args.max_columns {
+            config.grid.max_columns = max_columns;
+        }
+        if let Some(max_items) = args.max_items {
+            config.grid.max_items = max_items;
+        }
+        if let Some(max_program_len) = args.max_program_len {
+            config.interpreter.max_program_len = max_program_len;
+        }
+        if let Some(max_steps) = args.max_steps {
+            config.interpreter.max_steps = max_steps;
+        }
+        if let Some(max_age) = args.session_max_age {
+            config.session.max_age = Some(max_age);
+        }
+        if let Some(secure) = args.secure_cookies {
+            config.session.secure = secure;
+        }
+        if let Some(database) = args.database {
+            config.storage.database = database;
+        }
+        if let Some(format) = args.log_format {
+            config.log.format = format;
+        }
+        if let Some(level) = args.log_level {
+            config.log.level = level;
+        }
+
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Checks that grid sizes make sense together, and that programs can run
+    /// at all (limits of the interpreter above zero).
+    fn validate(&self) -> Result<(), ConfigError> {
+        let grid = &self.grid;
+        if grid.max_lines == 0 || grid.max_columns == 0 {
+            return Err(ConfigError::Invalid(
+                "maximum grid size must be at least 1x1".to_string(),
+            ));
+        }
+        if grid.default_lines == 0 || grid.default_lines > grid.max_lines {
+            return Err(ConfigError::Invalid(format!(
+                "default_lines must be between 1 and max_lines ({})",
+                grid.max_lines
+            )));
+        }
+        if grid.default_columns == 0 || grid.default_columns > grid.max_columns {
+            return Err(ConfigError::Invalid(format!(
+                "default_columns must be between 1 and max_columns ({})",
+                grid.max_columns
+            )));
+        }
+        let interpreter = &self.interpreter;
+        if interpreter.max_program_len == 0 {
+            return Err(ConfigError::Invalid(
+                "max_program_len must be at least 1".to_string(),
+            ));
+        }
+        if interpreter.max_steps == 0 {
+            return Err(ConfigError::Invalid(
+                "max_steps must be at least 1".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Socket address the server listens on.
+    pub fn address(&self) -> SocketAddr {
+        SocketAddr::new(self.server.bind, self.server.port)
+    }
+}
@@ -0,0 +1,84 @@
+//! Health, readiness and version endpoints, and graceful shutdown.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+use axum::{Json, http::StatusCode, response::IntoResponse};
+use serde::Serialize;
+
+/// Set when the server received a shutdown signal and stops accepting work.
+static SHUTTING_DOWN: AtomicBool = AtomicBool::new(false);
+
+/// Body of the `/version` response.
+#[derive(Serialize)]
+struct Version {
+    name: &'static str,
+    version: &'static str,
+}
+
+/// Handler for `/healthz` (liveness): answers as long as the server runs.
+pub async fn healthz() -> impl IntoResponse {
+    "ok"
+}
+
+/// Handler for `/readyz` (readiness).
+///
+/// # Returns
+///
+/// `200 OK` while the server accepts requests, `503 Service Unavailable` once
+/// a shutdown has started, so load balancers stop sending new traffic during
+/// the drain delay, before the listener closes.
+pub async fn readyz() -> impl IntoResponse {
+    if SHUTTING_DOWN.load(Ordering::Relaxed) {
+        (StatusCode::SERVICE_UNAVAILABLE, "shutting down")
+    } else {
+        (StatusCode::OK, "ready")
+    }
+}
+
+/// Handler for `/version`: name and version of the running binary, as JSON.
+pub async fn version() -> impl IntoResponse {
+    Json(Version {
+        name: env!("CARGO_PKG_NAME"),
+        version: env!("CARGO_PKG_VERSION"),
+    })
+}
+
+/// Waits for `SIGINT` (Ctrl+C) or `SIGTERM`, then marks the server as not ready
+/// and waits `drain_delay` more, still serving requests.
+///
+/// Passed to `with_graceful_shutdown`: once it returns, the server stops accepting
+/// connections and waits for in-flight requests to finish.
+///
+/// # Arguments
+///
+/// * `drain_delay` - Time left to load balancers to see `/readyz` fail.
+///
+/// # Panics
+///
+/// Will panic if the signal handlers cannot be installed.
+pub async fn shutdown_signal(drain_delay: Duration) {
+    let ctrl_c = async {
+        tokio::signal::ctrl_c().await.unwrap();
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+            .unwrap()
+            .recv()
+            .await;
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => tracing::info!(signal = "SIGINT", "shutdown requested"),
+        _ = terminate => tracing::info!(signal = "SIGTERM", "shutdown requested"),
+    }
+    SHUTTING_DOWN.store(true, Ordering::Relaxed);
+    if !drain_delay.is_zero() {
+        tracing::info!(seconds = drain_delay.as_secs(), "draining before shutdown");
+        tokio::time::sleep(drain_delay).await;
+    }
+}
@@ -0,0 +1,81 @@
+//! Structured logs of the server, based on `tracing`.
+
+use std::time::Instant;
+
+use axum::{
+    extract::{MatchedPath, Request},
+    middleware::Next,
+    response::Response,
+};
+use tracing::{Instrument, field};
+use tracing_subscriber::EnvFilter;
+
+use crate::config::{LogConfig, LogFormat};
+use crate::session::SessionId;
+
+/// Installs the global `tracing` subscriber writing logs to stdout.
+///
+/// The `RUST_LOG` environment variable, when set, takes precedence over the
+/// configured level.
+///
+/// # Arguments
+///
+/// * `log_config` - Output format and minimum level of the logs.
+pub fn init(log_config: &LogConfig) {
+    let filter =
+        EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(&log_config.level));
+    let subscriber = tracing_subscriber::fmt().with_env_filter(filter);
+    match log_config.format {
+        LogFormat::Text => subscriber.init(),
+        LogFormat::Json => subscriber
+            .json()
+            .with_current_span(true)
+            .with_span_list(false)
+            .init(),
+    }
+}
+
+/// Middleware opening a span for every request and logging its outcome.
+///
+/// The span records the method, the matched route (`/coords/{i}/{j}` rather than
+/// `/coords/2/3`) and the session id, so every event logged by a handler is
+/// attached to them. Status and latency are logged once the response is ready.
+///
+/// # Arguments
+///
+/// * `request` - The incoming request.
+/// * `next` - The rest of the middleware stack.
+pub async fn log_request(request: Request, next: Next) -> Response {
+    let route = match request.extensions().get::<MatchedPath>() {
+        Some(path) => path.as_str().to_string(),
+        None => request.uri().path().to_string(),
+    };
+    let session_id = match request.extensions().get::<SessionId>() {
+        Some(SessionId(id)) => id.clone(),
+        None => String::new(),
+    };
+    let span = tracing::info_span!(
+        "request",
+        method = %request.method(),
+        route,
+        session_id,
+        status = field::Empty,
+        latency_ms = field::Empty,
+    );
+
+    let start = Instant::now();
+    let response = next.run(request).instrument(span.clone()).await;
+    let latency_ms = start.elapsed().as_secs_f64() * 1000.0;
+    let status = response.status().as_u16();
+
+    span.record("status", status);
+    span.record("latency_ms", latency_ms);
+    span.in_scope(|| {
+        if response.status().is_server_error() {
+            tracing::error!("request failed");
+        } else {
+            tracing::info!("request finished");
+        }
+    });
+    response
+}
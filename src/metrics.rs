@@ -0,0 +1,226 @@
+//! Prometheus metrics of the server, exposed on `/metrics`.
+
+use std::collections::HashMap;
+use std::sync::{LazyLock, Mutex};
+use std::time::{Duration, Instant};
+
+use axum::{
+    extract::{MatchedPath, Request},
+    http::{Method, header},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use prometheus::{
+    Encoder, Histogram, HistogramOpts, HistogramVec, IntCounterVec, IntGauge, Opts, Registry,
+    TextEncoder,
+};
+use rustbot_core::Command;
+
+/// A session is counted as active if it made a request during this window.
+const ACTIVE_SESSION_WINDOW: Duration = Duration::from_secs(5 * 60);
+
+/// Registry holding all RustBot metrics.
+static REGISTRY: LazyLock<Registry> = LazyLock::new(Registry::new);
+
+/// Number of requests, by route, method and status code.
+pub static REQUESTS: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register(IntCounterVec::new(
+        Opts::new("rustbot_requests_total", "Number of HTTP requests"),
+        &["route", "method", "status"],
+    ))
+});
+
+/// Time spent answering requests, by route.
+pub static REQUEST_DURATION: LazyLock<HistogramVec> = LazyLock::new(|| {
+    register(HistogramVec::new(
+        HistogramOpts::new(
+            "rustbot_request_duration_seconds",
+            "Time spent answering HTTP requests",
+        ),
+        &["route"],
+    ))
+});
+
+/// Number of moves executed, in play mode or by programs, by kind of move.
+pub static MOVES: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register(IntCounterVec::new(
+        Opts::new(
+            "rustbot_moves_total",
+            "Number of moves executed by the robot",
+        ),
+        &["move"],
+    ))
+});
+
+/// Number of programs sent in code mode, by outcome (`ok` or the failure reason).
+pub static PROGRAMS: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register(IntCounterVec::new(
+        Opts::new(
+            "rustbot_programs_total",
+            "Number of programs run in code mode",
+        ),
+        &["outcome"],
+    ))
+});
+
+/// Number of warnings reported on programs sent in code mode, by kind of warning.
+pub static LINT_WARNINGS: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register(IntCounterVec::new(
+        Opts::new(
+            "rustbot_lint_warnings_total",
+            "Number of warnings reported on programs run in code mode",
+        ),
+        &["warning"],
+    ))
+});
+
+/// Number of steps executed by each program.
+pub static PROGRAM_STEPS: LazyLock<Histogram> = LazyLock::new(|| {
+    register(Histogram::with_opts(
+        HistogramOpts::new(
+            "rustbot_program_steps",
+            "Number of steps executed by a program",
+        )
+        .buckets(prometheus::exponential_buckets(1.0, 2.0, 12).unwrap()),
+    ))
+});
+
+/// Time spent rendering templates and images.
+pub static RENDER_DURATION: LazyLock<Histogram> = LazyLock::new(|| {
+    register(Histogram::with_opts(
+        HistogramOpts::new(
+            "rustbot_render_duration_seconds",
+            "Time spent rendering HTML templates and images",
+        )
+        .buckets(prometheus::exponential_buckets(0.00001, 4.0, 10).unwrap()),
+    ))
+});
+
+/// Number of sessions seen during the last `ACTIVE_SESSION_WINDOW`.
+static ACTIVE_SESSIONS: LazyLock<IntGauge> = LazyLock::new(|| {
+    register(IntGauge::new(
+        "rustbot_active_sessions",
+        "Number of sessions that made a request in the last 5 minutes",
+    ))
+});
+
+/// Number of requests between two prunings of `SESSIONS_LAST_SEEN` outside of
+/// `/metrics`, so that it stays bounded on servers that are never scraped.
+const PRUNE_EVERY: usize = 1024;
+
+/// Last time each session made a request, and the number of requests since
+/// the last pruning.
+static SESSIONS_LAST_SEEN: LazyLock<Mutex<(HashMap<String, Instant>, usize)>> =
+    LazyLock::new(|| Mutex::new((HashMap::new(), 0)));
+
+/// Registers every metric, so they are all exported from the first scrape on.
+pub fn init() {
+    LazyLock::force(&REQUESTS);
+    LazyLock::force(&REQUEST_DURATION);
+    LazyLock::force(&MOVES);
+    LazyLock::force(&PROGRAMS);
+    LazyLock::force(&LINT_WARNINGS);
+    LazyLock::force(&PROGRAM_STEPS);
+    LazyLock::force(&RENDER_DURATION);
+    LazyLock::force(&ACTIVE_SESSIONS);
+}
+
+/// Registers a metric into `REGISTRY` and returns it.
+///
+/// # Panics
+///
+/// Will panic if the metric is invalid or registered twice, which is a programming error.
+fn register<M: prometheus::core::Collector + Clone + 'static>(
+    metric: Result<M, prometheus::Error>,
+) -> M {
+    let metric = metric.unwrap();
+    REGISTRY.register(Box::new(metric.clone())).unwrap();
+    metric
+}
+
+/// Records that a session made a request, for the active sessions gauge.
+///
+/// # Arguments
+///
+/// * `session_id` - Identifier of the session (see `session.rs`).
+pub fn session_seen(session_id: &str) {
+    let mut guard = SESSIONS_LAST_SEEN.lock().unwrap();
+    let (sessions, requests) = &mut *guard;
+    sessions.insert(session_id.to_string(), Instant::now());
+    // Every cookieless client brings a new session: forget the inactive ones from time to time
+    *requests += 1;
+    if *requests >= PRUNE_EVERY {
+        *requests = 0;
+        sessions.retain(|_, last_seen| last_seen.elapsed() < ACTIVE_SESSION_WINDOW);
+    }
+}
+
+/// Counts a command executed in play mode or by a program, if it moves the robot.
+///
+/// # Arguments
+///
+/// * `command` - The executed command.
+pub fn record_command(command: &Command) {
+    if command.is_move() {
+        MOVES.with_label_values(&[command.name()]).inc();
+    }
+}
+
+/// Forgets sessions inactive for longer than `ACTIVE_SESSION_WINDOW` and updates the gauge.
+fn update_active_sessions() {
+    let (sessions, _) = &mut *SESSIONS_LAST_SEEN.lock().unwrap();
+    sessions.retain(|_, last_seen| last_seen.elapsed() < ACTIVE_SESSION_WINDOW);
+    ACTIVE_SESSIONS.set(sessions.len() as i64);
+}
+
+/// Middleware counting requests and measuring their duration, by matched route.
+///
+/// # Arguments
+///
+/// * `request` - The incoming request.
+/// * `next` - The rest of the middleware stack.
+pub async fn track_request(request: Request, next: Next) -> Response {
+    // Use the route pattern and not the path, to keep a bounded number of labels
+    let route = match request.extensions().get::<MatchedPath>() {
+        Some(path) => path.as_str().to_string(),
+        None => "unmatched".to_string(),
+    };
+    // Custom methods would each add a label value
+    let method = match *request.method() {
+        Method::GET
+        | Method::POST
+        | Method::PUT
+        | Method::DELETE
+        | Method::HEAD
+        | Method::OPTIONS
+        | Method::PATCH
+        | Method::CONNECT
+        | Method::TRACE => request.method().as_str().to_string(),
+        _ => "other".to_string(),
+    };
+
+    let timer = REQUEST_DURATION.with_label_values(&[&route]).start_timer();
+    let response = next.run(request).await;
+    timer.observe_duration();
+
+    REQUESTS
+        .with_label_values(&[&route, &method, response.status().as_str()])
+        .inc();
+    response
+}
+
+/// Handler for `/metrics`, in the Prometheus text format.
+///
+/// # Returns
+///
+/// The current value of every metric, with the matching `Content-Type`.
+pub async fn metrics() -> impl IntoResponse {
+    update_active_sessions();
+    let encoder = TextEncoder::new();
+    let mut buffer = Vec::new();
+    encoder.encode(&REGISTRY.gather(), &mut buffer).unwrap();
+    (
+        [(header::CONTENT_TYPE, encoder.format_type().to_string())],
+        buffer,
+    )
+}
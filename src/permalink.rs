@@ -0,0 +1,207 @@
+//! Shareable links to a code mode setup: `/s/{blob}`.
+//!
+//! The blob holds a program with its dialect and the world it starts from
+//! (grid size, robot position and heading, walls and items), so nothing has to
+//! be stored on the server. It is a small binary document, compressed with
+//! deflate and encoded in URL-safe base64 (without padding):
+//!
+//! ```text
+//! version (1 byte) | lines | columns | i | j | number of walls | (i, j) of each wall
+//!     | number of item cells | (i, j, count) of each cell | heading | dialect
+//!     | program (UTF-8)
+//! ```
+//!
+//! Numbers are unsigned LEB128 varints (7 bits per byte, lowest bits first),
+//! and the heading and the dialect are indexes in `Heading::ALL` and
+//! `Dialect::ALL`.
+
+use std::fmt;
+use std::io::{Read, Write};
+
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use flate2::Compression;
+use flate2::read::DeflateDecoder;
+use flate2::write::DeflateEncoder;
+use rustbot_core::{Dialect, Heading, Robot, World};
+
+use crate::config::Config;
+
+/// Version of the blob layout, its first byte.
+const VERSION: u8 = 1;
+
+/// A program and the world it starts from.
+pub struct Permalink {
+    pub world: World,
+    pub program: String,
+    pub dialect: Dialect,
+}
+
+/// Error returned when a blob cannot be decoded.
+#[derive(Debug)]
+pub enum PermalinkError {
+    /// Not base64, not deflate, or truncated
+    Malformed,
+    /// Not made by this version of RustBot
+    UnknownVersion(u8),
+    /// The world or the program exceeds the limits of the server
+    TooBig,
+    /// The world is not consistent (robot outside the grid, robot on a wall…)
+    InvalidWorld(String),
+}
+
+impl fmt::Display for PermalinkError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PermalinkError::Malformed => write!(f, "this link is damaged or incomplete"),
+            PermalinkError::UnknownVersion(version) => {
+                write!(
+                    f,
+                    "this link was made by another version of RustBot (link version {version})"
+                )
+            }
+            PermalinkError::TooBig => write!(f, "this link exceeds the limits of this server"),
+            PermalinkError::InvalidWorld(e) => write!(f, "this link has an invalid grid: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for PermalinkError {}
+
+/// Appends `value` as an unsigned LEB128 varint.
+fn write_varint(data: &mut Vec<u8>, mut value: u32) {
+    while value >= 0x80 {
+        data.push((value & 0x7f) as u8 | 0x80);
+        value >>= 7;
+    }
+    data.push(value as u8);
+}
+
+/// Reads an unsigned LEB128 varint from the start of `data`, and advances it.
+fn read_varint(data: &mut &[u8]) -> Result<u32, PermalinkError> {
+    let mut value: u32 = 0;
+    for shift in (0..35).step_by(7) {
+        let (&byte, rest) = data.split_first().ok_or(PermalinkError::Malformed)?;
+        *data = rest;
+        value |= u32::from(byte & 0x7f)
+            .checked_shl(shift)
+            .ok_or(PermalinkError::Malformed)?;
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+    Err(PermalinkError::Malformed)
+}
+
+impl Permalink {
+    /// Encodes the permalink into a blob for `/s/{blob}`.
+    ///
+    /// The trail, the pen, the paint and the inventory of the world are not
+    /// part of the link.
+    ///
+    /// # Panics
+    ///
+    /// Will panic if compression fails, which can't happen when writing to memory.
+    pub fn encode(&self) -> String {
+        let world = &self.world;
+        let mut data = vec![VERSION];
+        write_varint(&mut data, world.lines());
+        write_varint(&mut data, world.columns());
+        write_varint(&mut data, world.robot().i);
+        write_varint(&mut data, world.robot().j);
+        write_varint(&mut data, world.walls().count() as u32);
+        for (i, j) in world.walls() {
+            write_varint(&mut data, i);
+            write_varint(&mut data, j);
+        }
+        write_varint(&mut data, world.items().count() as u32);
+        for ((i, j), count) in world.items() {
+            write_varint(&mut data, i);
+            write_varint(&mut data, j);
+            write_varint(&mut data, count);
+        }
+        write_varint(&mut data, world.heading() as u32);
+        write_varint(&mut data, self.dialect as u32);
+        data.extend_from_slice(self.program.as_bytes());
+
+        let mut encoder = DeflateEncoder::new(Vec::new(), Compression::best());
+        encoder.write_all(&data).unwrap();
+        URL_SAFE_NO_PAD.encode(encoder.finish().unwrap())
+    }
+
+    /// Decodes a blob from `/s/{blob}`, checking it against the server limits.
+    ///
+    /// # Arguments
+    ///
+    /// * `blob` - The end of the link.
+    /// * `config` - Server configuration, for the maximum grid and program sizes.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `PermalinkError` if the blob is damaged, from an unknown
+    /// version, too big for this server or describes an invalid world.
+    pub fn decode(blob: &str, config: &Config) -> Result<Permalink, PermalinkError> {
+        let compressed = URL_SAFE_NO_PAD
+            .decode(blob)
+            .map_err(|_| PermalinkError::Malformed)?;
+
+        // Stop reading past the biggest valid link, so small links can't
+        // decompress into huge documents
+        let grid = &config.grid;
+        let max_cells = grid.max_lines as u64 * grid.max_columns as u64;
+        let max_len = config.interpreter.max_program_len as u64 + 15 * max_cells + 32;
+        let mut data = Vec::new();
+        DeflateDecoder::new(compressed.as_slice())
+            .take(max_len + 1)
+            .read_to_end(&mut data)
+            .map_err(|_| PermalinkError::Malformed)?;
+        if data.len() as u64 > max_len {
+            return Err(PermalinkError::TooBig);
+        }
+
+        let (&version, mut data) = data.split_first().ok_or(PermalinkError::Malformed)?;
+        if version != VERSION {
+            return Err(PermalinkError::UnknownVersion(version));
+        }
+        let lines = read_varint(&mut data)?;
+        let columns = read_varint(&mut data)?;
+        if lines > grid.max_lines || columns > grid.max_columns {
+            return Err(PermalinkError::TooBig);
+        }
+        let robot = Robot {
+            i: read_varint(&mut data)?,
+            j: read_varint(&mut data)?,
+        };
+        let mut world = World::with_robot(lines, columns, robot)
+            .map_err(|e| PermalinkError::InvalidWorld(e.to_string()))?;
+        let invalid = |e: rustbot_core::WorldError| PermalinkError::InvalidWorld(e.to_string());
+        let nb_walls = read_varint(&mut data)?;
+        for _ in 0..nb_walls {
+            let (i, j) = (read_varint(&mut data)?, read_varint(&mut data)?);
+            world.add_wall(i, j).map_err(invalid)?;
+        }
+        let nb_items = read_varint(&mut data)?;
+        for _ in 0..nb_items {
+            let (i, j) = (read_varint(&mut data)?, read_varint(&mut data)?);
+            let count = read_varint(&mut data)?;
+            world.put_items(i, j, count).map_err(invalid)?;
+            if world.items_at(i, j) > grid.max_items {
+                return Err(PermalinkError::TooBig);
+            }
+        }
+        let heading = read_varint(&mut data)? as usize;
+        world.set_heading(*Heading::ALL.get(heading).ok_or(PermalinkError::Malformed)?);
+        let index = read_varint(&mut data)? as usize;
+        let dialect = *Dialect::ALL.get(index).ok_or(PermalinkError::Malformed)?;
+
+        if data.len() > config.interpreter.max_program_len {
+            return Err(PermalinkError::TooBig);
+        }
+        let program = String::from_utf8(data.to_vec()).map_err(|_| PermalinkError::Malformed)?;
+        Ok(Permalink {
+            world,
+            program,
+            dialect,
+        })
+    }
+}
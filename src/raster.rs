@@ -0,0 +1,360 @@
+//! Raster images of the grid: PNG pictures and animated GIFs, and PNG
+//! pictures of the cells painted by the robot.
+//!
+//! Images are drawn with the same layout and colors as `rustbot_core::render::svg`,
+//! pixel by pixel, so they don't depend on any font or system library and the
+//! same world always gives the same file.
+
+use rustbot_core::render::{SVG_CELL as CELL, SVG_HEADER as HEADER};
+use rustbot_core::{Color, Robot, World};
+
+/// Colors used in images, as `[r, g, b]`. Pixels hold an index in this table,
+/// followed by the colors of the pen (see `paint_index`).
+const BASE_PALETTE: [[u8; 3]; 10] = [
+    [0x3b, 0x42, 0x52], // background
+    [0xec, 0xef, 0xf4], // text
+    [0x81, 0xa1, 0xc1], // cell
+    [0x25, 0x4e, 0x65], // cell border
+    [0x2e, 0x34, 0x40], // wall, robot outline
+    [0xeb, 0xcb, 0x8b], // trail
+    [0xd8, 0xde, 0xe9], // robot head
+    [0xbf, 0x61, 0x6a], // robot antenna
+    [0xd0, 0x87, 0x70], // robot body
+    [0xa3, 0xbe, 0x8c], // item
+];
+const BACKGROUND: u8 = 0;
+const TEXT: u8 = 1;
+const CELL_FILL: u8 = 2;
+const CELL_BORDER: u8 = 3;
+const WALL: u8 = 4;
+const TRAIL: u8 = 5;
+const ROBOT_HEAD: u8 = 6;
+const ROBOT_ANTENNA: u8 = 7;
+const ROBOT_BODY: u8 = 8;
+const ITEM: u8 = 9;
+
+/// The full palette: `BASE_PALETTE` then every `Color`, as `r, g, b` bytes.
+fn palette() -> Vec<u8> {
+    let pen_colors = Color::ALL.iter().map(Color::rgb);
+    BASE_PALETTE
+        .into_iter()
+        .chain(pen_colors)
+        .flatten()
+        .collect()
+}
+
+/// Index of a pen color in the palette.
+fn paint_index(color: Color) -> u8 {
+    (BASE_PALETTE.len() + color.index()) as u8
+}
+
+/// Digits 0 to 9 and `+` in a 3x5 pixels font, one `u8` per row (3 lowest
+/// bits).
+const DIGITS: [[u8; 5]; 11] = [
+    [0b111, 0b101, 0b101, 0b101, 0b111],
+    [0b010, 0b110, 0b010, 0b010, 0b111],
+    [0b111, 0b001, 0b111, 0b100, 0b111],
+    [0b111, 0b001, 0b111, 0b001, 0b111],
+    [0b101, 0b101, 0b111, 0b001, 0b001],
+    [0b111, 0b100, 0b111, 0b001, 0b111],
+    [0b111, 0b100, 0b111, 0b101, 0b111],
+    [0b111, 0b001, 0b001, 0b001, 0b001],
+    [0b111, 0b101, 0b111, 0b101, 0b111],
+    [0b111, 0b101, 0b111, 0b001, 0b111],
+    [0b000, 0b010, 0b111, 0b010, 0b000],
+];
+
+/// Size of a pixel of the digits font, in image pixels.
+const DIGIT_SCALE: u32 = 2;
+
+/// Maximum number of frames of an animated GIF.
+pub const MAX_GIF_FRAMES: usize = 200;
+
+/// Time each frame of an animated GIF is shown, in hundredths of a second.
+const GIF_FRAME_DELAY: u16 = 40;
+
+/// Time the last frame of an animated GIF is shown, before looping.
+const GIF_LAST_FRAME_DELAY: u16 = 200;
+
+/// An image whose pixels are indexes in `PALETTE`.
+struct Pixmap {
+    width: u32,
+    height: u32,
+    pixels: Vec<u8>,
+}
+
+impl Pixmap {
+    /// Creates an image filled with one color.
+    fn new(width: u32, height: u32, color: u8) -> Self {
+        Pixmap {
+            width,
+            height,
+            pixels: vec![color; (width * height) as usize],
+        }
+    }
+
+    /// Fills a rectangle, clipped to the image.
+    fn fill_rect(&mut self, x: u32, y: u32, width: u32, height: u32, color: u8) {
+        for py in y..(y + height).min(self.height) {
+            let row = (py * self.width) as usize;
+            for px in x..(x + width).min(self.width) {
+                self.pixels[row + px as usize] = color;
+            }
+        }
+    }
+
+    /// Draws the 1 pixel wide border of a rectangle.
+    fn stroke_rect(&mut self, x: u32, y: u32, width: u32, height: u32, color: u8) {
+        self.fill_rect(x, y, width, 1, color);
+        self.fill_rect(x, y + height - 1, width, 1, color);
+        self.fill_rect(x, y, 1, height, color);
+        self.fill_rect(x + width - 1, y, 1, height, color);
+    }
+
+    /// Fills a disc centered on `(cx, cy)`.
+    fn fill_circle(&mut self, cx: u32, cy: u32, radius: u32, color: u8) {
+        let r2 = (radius * radius) as i64;
+        for py in cy.saturating_sub(radius)..=cy + radius {
+            for px in cx.saturating_sub(radius)..=cx + radius {
+                let (dx, dy) = (px as i64 - cx as i64, py as i64 - cy as i64);
+                if dx * dx + dy * dy <= r2 {
+                    self.fill_rect(px, py, 1, 1, color);
+                }
+            }
+        }
+    }
+
+    /// Writes a number centered on `(cx, cy)` with the digits font (`+` when
+    /// it has more than 3 digits, like the terminal grid).
+    fn draw_number(&mut self, cx: u32, cy: u32, number: u32, color: u8) {
+        let text = if number < 1000 {
+            number.to_string()
+        } else {
+            "+".to_string()
+        };
+        let digit_width = 3 * DIGIT_SCALE;
+        let spacing = DIGIT_SCALE;
+        let width = text.len() as u32 * (digit_width + spacing) - spacing;
+        let x0 = cx.saturating_sub(width / 2);
+        let y0 = cy - 5 * DIGIT_SCALE / 2;
+        for (index, digit) in text.bytes().enumerate() {
+            let x = x0 + index as u32 * (digit_width + spacing);
+            let glyph = match digit {
+                b'+' => &DIGITS[10],
+                digit => &DIGITS[(digit - b'0') as usize],
+            };
+            for (row, bits) in glyph.iter().enumerate() {
+                for column in 0..3 {
+                    if bits & (0b100 >> column) != 0 {
+                        self.fill_rect(
+                            x + column * DIGIT_SCALE,
+                            y0 + row as u32 * DIGIT_SCALE,
+                            DIGIT_SCALE,
+                            DIGIT_SCALE,
+                            color,
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    /// Copies another image at the top left corner of this one.
+    fn paste(&mut self, other: &Pixmap) {
+        for y in 0..other.height.min(self.height) {
+            for x in 0..other.width.min(self.width) {
+                self.pixels[(y * self.width + x) as usize] =
+                    other.pixels[(y * other.width + x) as usize];
+            }
+        }
+    }
+}
+
+/// Pixel coordinates of the center of a cell.
+fn center(cell: Robot) -> (u32, u32) {
+    (
+        HEADER + cell.j * CELL + CELL / 2,
+        HEADER + cell.i * CELL + CELL / 2,
+    )
+}
+
+/// Draws the world: line and column numbers, cells, paint, walls, trail, robot
+/// and items.
+fn draw_world(world: &World) -> Pixmap {
+    let width = HEADER + world.columns() * CELL;
+    let height = HEADER + world.lines() * CELL;
+    let mut image = Pixmap::new(width, height, BACKGROUND);
+
+    // Line and column numbers
+    for j in 0..world.columns() {
+        let (x, _) = center(Robot { i: 0, j });
+        image.draw_number(x, HEADER / 2, j, TEXT);
+    }
+    for i in 0..world.lines() {
+        let (_, y) = center(Robot { i, j: 0 });
+        image.draw_number(HEADER / 2, y, i, TEXT);
+    }
+
+    // Cells, paint and walls
+    for i in 0..world.lines() {
+        for j in 0..world.columns() {
+            let (x, y) = (HEADER + j * CELL, HEADER + i * CELL);
+            let fill = match world.paint_at(i, j) {
+                _ if world.is_wall(i, j) => WALL,
+                Some(color) => paint_index(color),
+                None => CELL_FILL,
+            };
+            image.fill_rect(x, y, CELL, CELL, fill);
+            image.stroke_rect(x, y, CELL, CELL, CELL_BORDER);
+        }
+    }
+
+    // Trail: a line between neighbour positions, a dot on every visited cell
+    let trail = world.trail();
+    for pair in trail.windows(2) {
+        let (from, to) = (pair[0], pair[1]);
+        if from.i.abs_diff(to.i) + from.j.abs_diff(to.j) == 1 {
+            let ((x1, y1), (x2, y2)) = (center(from), center(to));
+            let (x, y) = (x1.min(x2), y1.min(y2));
+            image.fill_rect(
+                x - 2,
+                y - 2,
+                x1.abs_diff(x2) + 4,
+                y1.abs_diff(y2) + 4,
+                TRAIL,
+            );
+        }
+    }
+    for &cell in trail {
+        let (x, y) = center(cell);
+        image.fill_circle(x, y, 4, TRAIL);
+    }
+
+    // Robot: antenna, head with eyes and mouth, body
+    let robot = world.robot();
+    let (x, y) = (HEADER + robot.j * CELL, HEADER + robot.i * CELL);
+    image.fill_rect(x + 19, y + 4, 2, 6, WALL);
+    image.fill_circle(x + 20, y + 4, 3, ROBOT_ANTENNA);
+    image.fill_rect(x + 8, y + 10, 24, 16, ROBOT_HEAD);
+    image.stroke_rect(x + 8, y + 10, 24, 16, WALL);
+    image.fill_circle(x + 15, y + 17, 2, WALL);
+    image.fill_circle(x + 25, y + 17, 2, WALL);
+    image.fill_rect(x + 15, y + 22, 11, 1, WALL);
+    image.fill_rect(x + 11, y + 26, 18, 10, ROBOT_BODY);
+    image.stroke_rect(x + 11, y + 26, 18, 10, WALL);
+
+    // Items, drawn last so the robot doesn't hide them
+    for ((i, j), count) in world.items() {
+        let (x, y) = (HEADER + j * CELL + 9, HEADER + i * CELL + 9);
+        image.fill_circle(x, y, 8, WALL);
+        image.fill_circle(x, y, 7, ITEM);
+        image.draw_number(x, y, count, WALL);
+    }
+
+    image
+}
+
+/// Renders the world as a PNG image.
+///
+/// # Arguments
+///
+/// * `world` - The world to draw.
+///
+/// # Panics
+///
+/// Will panic if PNG encoding fails, which can't happen when writing to memory.
+pub fn png(world: &World) -> Vec<u8> {
+    let image = draw_world(world);
+    let mut data = Vec::new();
+    let mut encoder = png::Encoder::new(&mut data, image.width, image.height);
+    encoder.set_color(png::ColorType::Indexed);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder.set_palette(palette());
+    let mut writer = encoder.write_header().unwrap();
+    writer.write_image_data(&image.pixels).unwrap();
+    writer.finish().unwrap();
+    data
+}
+
+/// Renders only the painted cells as a PNG image: one `scale` x `scale` pixels
+/// square per cell, the other cells being transparent.
+///
+/// # Arguments
+///
+/// * `world` - The world whose paint to draw.
+/// * `scale` - Side of a cell, in pixels.
+///
+/// # Panics
+///
+/// Will panic if PNG encoding fails, which can't happen when writing to memory.
+pub fn canvas_png(world: &World, scale: u32) -> Vec<u8> {
+    // Index 0 is transparent, then one index per color
+    let mut image = Pixmap::new(world.columns() * scale, world.lines() * scale, 0);
+    for ((i, j), color) in world.painted() {
+        let index = 1 + color.index() as u8;
+        image.fill_rect(j * scale, i * scale, scale, scale, index);
+    }
+    let palette: Vec<u8> = [[0, 0, 0]]
+        .into_iter()
+        .chain(Color::ALL.iter().map(Color::rgb))
+        .flatten()
+        .collect();
+
+    let mut data = Vec::new();
+    let mut encoder = png::Encoder::new(&mut data, image.width, image.height);
+    encoder.set_color(png::ColorType::Indexed);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder.set_palette(palette);
+    encoder.set_trns(vec![0]);
+    let mut writer = encoder.write_header().unwrap();
+    writer.write_image_data(&image.pixels).unwrap();
+    writer.finish().unwrap();
+    data
+}
+
+/// Renders successive states of a world as an animated GIF, looping forever.
+///
+/// Frames all have the size of the biggest world: smaller grids are drawn in
+/// the top left corner.
+///
+/// # Arguments
+///
+/// * `worlds` - The states to show, in order (at least one).
+///
+/// # Panics
+///
+/// Will panic if `worlds` is empty, if a frame is bigger than 65535 pixels, or
+/// if GIF encoding fails, which can't happen when writing to memory.
+pub fn gif(worlds: &[World]) -> Vec<u8> {
+    let width = worlds.iter().map(|w| HEADER + w.columns() * CELL).max();
+    let height = worlds.iter().map(|w| HEADER + w.lines() * CELL).max();
+    let (width, height) = (width.unwrap(), height.unwrap());
+
+    let mut data = Vec::new();
+    let mut encoder = gif::Encoder::new(
+        &mut data,
+        u16::try_from(width).unwrap(),
+        u16::try_from(height).unwrap(),
+        &palette(),
+    )
+    .unwrap();
+    encoder.set_repeat(gif::Repeat::Infinite).unwrap();
+    for (index, world) in worlds.iter().enumerate() {
+        let mut frame_image = Pixmap::new(width, height, BACKGROUND);
+        frame_image.paste(&draw_world(world));
+        let frame = gif::Frame {
+            width: width as u16,
+            height: height as u16,
+            delay: if index + 1 == worlds.len() {
+                GIF_LAST_FRAME_DELAY
+            } else {
+                GIF_FRAME_DELAY
+            },
+            buffer: frame_image.pixels.into(),
+            ..gif::Frame::default()
+        };
+        encoder.write_frame(&frame).unwrap();
+    }
+    drop(encoder);
+    data
+}
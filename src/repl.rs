@@ -0,0 +1,223 @@
+//! `rustbot repl`: an interactive prompt for the code mode language.
+//!
+//! Every line is parsed and run like a program sent to `/user-code`, so what
+//! works here works in the browser. An `if`, `while`, `repeat` or `def` line
+//! starts a block, which runs once its `end` is typed. Procedures defined with
+//! `def` (on the prompt or in a loaded file) stay available to the next lines,
+//! until they are defined again.
+
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::process::ExitCode;
+
+use rustbot_core::parser::parse_with_procedures;
+use rustbot_core::{Limits, Procedure, World, render};
+use rustyline::DefaultEditor;
+use rustyline::error::ReadlineError;
+
+use crate::cli::WorldArgs;
+
+/// Help printed by `:help`.
+const HELP: &str = "\
+Type a command (`right`, `go to (2, 3)`, `nb lines = 8`…) to run it.
+Blocks (`if on item` … `end`, `repeat 3` … `end`…) run at their `end`.
+Procedures (`def name` … `end`) can be called by name on the next lines.
+Meta-commands:
+  :reset        go back to the starting world and forget the history
+  :load FILE    run the program in FILE
+  :save FILE    save the commands run so far as a program in FILE
+  :history      print the commands run so far
+  :help         print this help
+  :quit         leave (Ctrl+D works too)";
+
+/// Arguments of `rustbot repl`.
+#[derive(clap::Args)]
+pub struct ReplArgs {
+    #[command(flatten)]
+    world: WorldArgs,
+}
+
+/// State of a REPL session.
+struct Session {
+    /// World the session started with, restored by `:reset`
+    start: World,
+    world: World,
+    limits: Limits,
+    /// Commands successfully run so far, written as in code mode
+    history: Vec<String>,
+    /// Procedures defined so far, callable from the next lines
+    procedures: BTreeMap<String, Procedure>,
+}
+
+impl Session {
+    /// Runs some code on the world, all or nothing, and records it in the history.
+    ///
+    /// # Returns
+    ///
+    /// `true` if the code ran, `false` if it failed (the error is printed).
+    fn run(&mut self, source: &str) -> bool {
+        let mut new_world = self.world.clone();
+        let result = parse_with_procedures(source, &self.procedures).and_then(|program| {
+            let run = rustbot_core::run(&program, &mut new_world, &self.limits)?;
+            Ok((program, run))
+        });
+        match result {
+            Ok((program, run)) => {
+                self.world = new_world;
+                self.procedures = program.procedures;
+                self.history
+                    .extend(run.trace.iter().map(|step| step.command.to_string()));
+                true
+            }
+            Err(e) => {
+                eprintln!("error: {e}");
+                false
+            }
+        }
+    }
+
+    /// Prints the position of the robot and the grid.
+    fn draw(&self) {
+        let robot = self.world.robot();
+        println!(
+            "({}, {}) in a {}x{} grid",
+            robot.i,
+            robot.j,
+            self.world.lines(),
+            self.world.columns()
+        );
+        print!("{}", render::ascii(&self.world));
+    }
+
+    /// Runs a meta-command (a line starting with `:`).
+    ///
+    /// # Returns
+    ///
+    /// `false` if the session must end.
+    fn meta(&mut self, line: &str) -> bool {
+        let (name, argument) = match line.split_once(char::is_whitespace) {
+            Some((name, argument)) => (name, argument.trim()),
+            None => (line, ""),
+        };
+        match (name, argument) {
+            (":quit" | ":q", _) => return false,
+            (":help", _) => println!("{HELP}"),
+            (":reset", _) => {
+                self.world = self.start.clone();
+                self.history.clear();
+                self.procedures.clear();
+                self.draw();
+            }
+            (":history", _) => {
+                for command in &self.history {
+                    println!("{command}");
+                }
+            }
+            (":load", file) if !file.is_empty() => match std::fs::read_to_string(file) {
+                Ok(source) => {
+                    if self.run(&source) {
+                        self.draw();
+                    }
+                }
+                Err(e) => eprintln!("error: cannot read {file}: {e}"),
+            },
+            (":save", file) if !file.is_empty() => {
+                let mut program = self.history.join("\n");
+                program.push('\n');
+                match std::fs::write(Path::new(file), program) {
+                    Ok(()) => println!("{} commands saved to {file}", self.history.len()),
+                    Err(e) => eprintln!("error: cannot write {file}: {e}"),
+                }
+            }
+            (":load" | ":save", _) => eprintln!("error: {name} needs a file name"),
+            _ => eprintln!("error: unknown meta-command {name} (try :help)"),
+        }
+        true
+    }
+}
+
+/// Change in the number of open blocks after a line of code: `1` for an `if`,
+/// a `while`, a `repeat` or a `def`, `-1` for an `end`, `0` otherwise.
+fn block_depth_change(line: &str) -> isize {
+    match line.split_whitespace().next() {
+        Some("if" | "while" | "repeat" | "def") => 1,
+        Some("end") => -1,
+        _ => 0,
+    }
+}
+
+/// Runs the REPL until `:quit` or end of input.
+///
+/// # Returns
+///
+/// The exit code of the process: `2` if the starting world is impossible or
+/// the terminal cannot be used.
+pub fn repl(args: ReplArgs) -> ExitCode {
+    let start = match args.world.world() {
+        Ok(world) => world,
+        Err(e) => {
+            eprintln!("error: {e}");
+            return ExitCode::from(2);
+        }
+    };
+    let mut editor = match DefaultEditor::new() {
+        Ok(editor) => editor,
+        Err(e) => {
+            eprintln!("error: {e}");
+            return ExitCode::from(2);
+        }
+    };
+    let mut session = Session {
+        world: start.clone(),
+        start,
+        limits: Limits::default(),
+        history: Vec::new(),
+        procedures: BTreeMap::new(),
+    };
+
+    println!("RustBot REPL, type :help for help");
+    session.draw();
+    // Lines of a block waiting for its `end`, and the number of open blocks
+    let mut pending = String::new();
+    let mut depth: isize = 0;
+    loop {
+        let prompt = if depth > 0 { "   ...> " } else { "rustbot> " };
+        let line = match editor.readline(prompt) {
+            Ok(line) => line,
+            Err(ReadlineError::Interrupted) => {
+                pending.clear();
+                depth = 0;
+                continue;
+            }
+            Err(ReadlineError::Eof) => break,
+            Err(e) => {
+                eprintln!("error: {e}");
+                return ExitCode::FAILURE;
+            }
+        };
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        let _ = editor.add_history_entry(line);
+
+        if line.starts_with(':') && depth == 0 {
+            if !session.meta(line) {
+                break;
+            }
+            continue;
+        }
+        pending.push_str(line);
+        pending.push('\n');
+        depth += block_depth_change(line);
+        if depth > 0 {
+            continue;
+        }
+        if session.run(&pending) {
+            session.draw();
+        }
+        pending.clear();
+        depth = 0;
+    }
+    ExitCode::SUCCESS
+}
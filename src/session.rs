@@ -0,0 +1,54 @@
+//! Identification of the browser sessions.
+//!
+//! The robot state itself lives in cookies (see `web.rs`), but each browser
+//! also gets a random `session` cookie so logs and metrics can tell users apart.
+
+use std::sync::Arc;
+
+use axum::{
+    extract::{Request, State},
+    middleware::Next,
+    response::Response,
+};
+use axum_cookie::prelude::*;
+use uuid::Uuid;
+
+use crate::config::Config;
+use crate::metrics;
+use crate::web::new_cookie;
+
+/// Random identifier of a browser session, stored in the `session` cookie.
+///
+/// Inserted in the request extensions by `ensure_session`.
+#[derive(Clone, Debug)]
+pub struct SessionId(pub String);
+
+/// Middleware giving a `session` cookie to browsers that don't have one yet.
+///
+/// The cookie is refreshed on every request so its lifetime follows the
+/// `session.max_age` setting, and the identifier is made available to the
+/// next layers as a `SessionId` extension.
+///
+/// # Arguments
+///
+/// * `config` - Server configuration, for the cookie settings.
+/// * `cookie` - The `CookieManager` provided by `CookieLayer`.
+/// * `request` - The incoming request.
+/// * `next` - The rest of the middleware stack.
+pub async fn ensure_session(
+    State(config): State<Arc<Config>>,
+    cookie: CookieManager,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    let session_id = match cookie.get("session") {
+        Some(session_cookie) if Uuid::try_parse(session_cookie.value()).is_ok() => {
+            session_cookie.value().to_string()
+        }
+        _ => Uuid::new_v4().simple().to_string(),
+    };
+    cookie.add(new_cookie("session", session_id.clone(), &config.session));
+    metrics::session_seen(&session_id);
+    request.extensions_mut().insert(SessionId(session_id));
+    next.run(request).await
+}
@@ -0,0 +1,278 @@
+//! Export and import of a whole session as a JSON document.
+//!
+//! The document holds everything needed to restore a session: the world
+//! stored in cookies (grid size, robot position and heading, walls, trail,
+//! pen, paint, items and inventory) and the current program with its dialect
+//! (the draft, see `storage.rs`). It is versioned so snapshots attached to
+//! bug reports or used in tests keep loading after the format evolves:
+//!
+//! ```json
+//! {
+//!   "version": 1,
+//!   "position": { "i": 1, "j": 2 },
+//!   "heading": "south",
+//!   "max-i": 5,
+//!   "max-j": 5,
+//!   "walls": [{ "i": 0, "j": 3 }],
+//!   "history": [{ "i": 0, "j": 2 }, { "i": 1, "j": 2 }],
+//!   "pen": { "down": true, "color": "red" },
+//!   "paint": [{ "i": 1, "j": 2, "color": "red" }],
+//!   "items": [{ "i": 4, "j": 4, "count": 2 }],
+//!   "inventory": 1,
+//!   "program": "down\nwall (0, 3)\npen color red\npen down",
+//!   "dialect": "rustbot"
+//! }
+//! ```
+
+use std::fmt;
+use std::str::FromStr;
+
+use rustbot_core::{Color, Dialect, Heading, Robot, World};
+use serde::{Deserialize, Serialize};
+
+use crate::config::Config;
+
+/// Version of the document written by `SessionState::new`.
+pub const VERSION: u32 = 1;
+
+/// A cell of the grid.
+#[derive(Serialize, Deserialize, Clone, Copy)]
+pub struct Position {
+    pub i: u32,
+    pub j: u32,
+}
+
+/// State of the pen.
+#[derive(Serialize, Deserialize, Default)]
+pub struct Pen {
+    pub down: bool,
+    /// Name of the color (see `rustbot_core::Color`)
+    pub color: String,
+}
+
+/// A painted cell.
+#[derive(Serialize, Deserialize)]
+pub struct Paint {
+    pub i: u32,
+    pub j: u32,
+    /// Name of the color (see `rustbot_core::Color`)
+    pub color: String,
+}
+
+/// A cell holding items.
+#[derive(Serialize, Deserialize)]
+pub struct Items {
+    pub i: u32,
+    pub j: u32,
+    /// Number of items on the cell
+    pub count: u32,
+}
+
+/// The whole state of a session.
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SessionState {
+    /// Version of the document
+    pub version: u32,
+    /// Position of the robot
+    pub position: Position,
+    /// Direction the robot faces (east if missing)
+    #[serde(default)]
+    pub heading: String,
+    /// Number of lines of the grid
+    #[serde(rename = "max-i")]
+    pub max_i: u32,
+    /// Number of columns of the grid
+    #[serde(rename = "max-j")]
+    pub max_j: u32,
+    /// Cells with a wall
+    #[serde(default)]
+    pub walls: Vec<Position>,
+    /// Last positions of the robot, oldest first (its trail)
+    #[serde(default)]
+    pub history: Vec<Position>,
+    /// Pen of the robot (up and black if missing)
+    #[serde(default)]
+    pub pen: Pen,
+    /// Painted cells
+    #[serde(default)]
+    pub paint: Vec<Paint>,
+    /// Cells holding items
+    #[serde(default)]
+    pub items: Vec<Items>,
+    /// Number of items carried by the robot
+    #[serde(default)]
+    pub inventory: u32,
+    /// Current program of code mode
+    #[serde(default)]
+    pub program: String,
+    /// Language of the program (see `rustbot_core::Dialect`, `rustbot` if missing)
+    #[serde(default)]
+    pub dialect: String,
+}
+
+/// Error returned when a document cannot be imported.
+#[derive(Debug)]
+pub enum StateError {
+    /// The document was written by a newer version of RustBot
+    UnknownVersion(u32),
+    /// The grid or the program exceeds the limits of the server
+    TooBig(String),
+    /// The world is not consistent (robot outside the grid, robot on a wall…)
+    InvalidWorld(String),
+    /// A color, heading or dialect name is unknown
+    UnknownName(String),
+}
+
+impl fmt::Display for StateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StateError::UnknownVersion(version) => write!(
+                f,
+                "unsupported state version {version} (this server reads version {VERSION})"
+            ),
+            StateError::TooBig(msg) => write!(f, "{msg}"),
+            StateError::InvalidWorld(e) => write!(f, "invalid world: {e}"),
+            StateError::UnknownName(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+impl std::error::Error for StateError {}
+
+/// Reads a color, heading or dialect name of the document: the default one
+/// if it is missing (empty).
+fn parse_name<T: Default + FromStr<Err = String>>(name: &str) -> Result<T, StateError> {
+    match name {
+        "" => Ok(T::default()),
+        name => name.parse().map_err(StateError::UnknownName),
+    }
+}
+
+impl From<Robot> for Position {
+    fn from(robot: Robot) -> Self {
+        Position {
+            i: robot.i,
+            j: robot.j,
+        }
+    }
+}
+
+impl From<Position> for Robot {
+    fn from(position: Position) -> Self {
+        Robot {
+            i: position.i,
+            j: position.j,
+        }
+    }
+}
+
+impl SessionState {
+    /// Builds the document of a session.
+    ///
+    /// # Arguments
+    ///
+    /// * `world` - The world stored in cookies.
+    /// * `program` - The current program of code mode.
+    /// * `dialect` - Language of the program.
+    pub fn new(world: &World, program: String, dialect: Dialect) -> Self {
+        SessionState {
+            version: VERSION,
+            position: world.robot().into(),
+            heading: world.heading().to_string(),
+            max_i: world.lines(),
+            max_j: world.columns(),
+            walls: world.walls().map(|(i, j)| Position { i, j }).collect(),
+            history: world.trail().iter().map(|&cell| cell.into()).collect(),
+            pen: Pen {
+                down: world.is_pen_down(),
+                color: world.pen_color().to_string(),
+            },
+            paint: world
+                .painted()
+                .map(|((i, j), color)| Paint {
+                    i,
+                    j,
+                    color: color.to_string(),
+                })
+                .collect(),
+            items: world
+                .items()
+                .map(|((i, j), count)| Items { i, j, count })
+                .collect(),
+            inventory: world.inventory(),
+            program,
+            dialect: dialect.to_string(),
+        }
+    }
+
+    /// Checks the document against the server limits and rebuilds the session.
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - Server configuration, for the maximum grid and program
+    ///   sizes and numbers of items.
+    ///
+    /// # Returns
+    ///
+    /// The world, the program and its dialect. History positions outside of
+    /// the grid are dropped (see `World::set_trail`).
+    ///
+    /// # Errors
+    ///
+    /// Returns a `StateError` if the document has an unknown version, exceeds
+    /// the limits of the server or describes an invalid world.
+    pub fn restore(self, config: &Config) -> Result<(World, String, Dialect), StateError> {
+        if self.version != VERSION {
+            return Err(StateError::UnknownVersion(self.version));
+        }
+        let grid = &config.grid;
+        if self.max_i > grid.max_lines || self.max_j > grid.max_columns {
+            return Err(StateError::TooBig(format!(
+                "grid is bigger than {}x{}",
+                grid.max_lines, grid.max_columns
+            )));
+        }
+        let max_items = grid.max_items;
+        if self.inventory > max_items {
+            return Err(StateError::TooBig(format!(
+                "the robot can't carry more than {max_items} items"
+            )));
+        }
+        let max_program_len = config.interpreter.max_program_len;
+        if self.program.len() > max_program_len {
+            return Err(StateError::TooBig(format!(
+                "program is too long (max {max_program_len} bytes)"
+            )));
+        }
+
+        let invalid = |e: rustbot_core::WorldError| StateError::InvalidWorld(e.to_string());
+        let mut world =
+            World::with_robot(self.max_i, self.max_j, self.position.into()).map_err(invalid)?;
+        for wall in self.walls {
+            world.add_wall(wall.i, wall.j).map_err(invalid)?;
+        }
+        world.set_trail(self.history.into_iter().map(Robot::from));
+        world.set_heading(parse_name::<Heading>(&self.heading)?);
+        let color = parse_name::<Color>;
+        world.set_pen(self.pen.down, color(&self.pen.color)?);
+        for paint in self.paint {
+            world
+                .paint(paint.i, paint.j, color(&paint.color)?)
+                .map_err(invalid)?;
+        }
+        for items in self.items {
+            world
+                .put_items(items.i, items.j, items.count)
+                .map_err(invalid)?;
+        }
+        // Cells may be listed more than once: their counts add up
+        if world.items().any(|(_, count)| count > max_items) {
+            return Err(StateError::TooBig(format!(
+                "a cell can't hold more than {max_items} items"
+            )));
+        }
+        world.set_inventory(self.inventory);
+        Ok((world, self.program, parse_name(&self.dialect)?))
+    }
+}
@@ -0,0 +1,305 @@
+//! Server-side storage of programs, in a SQLite file.
+//!
+//! The robot state lives in cookies (see `web.rs`), but programs can be long,
+//! so they are kept on the server, per browser session (see `session.rs`):
+//! - named programs, saved and managed from the code mode page,
+//! - the draft, i.e. the last program sent, shown again in the textarea.
+
+use std::fmt;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use rusqlite::{Connection, OptionalExtension, params};
+
+/// Maximum length of a program name, in characters.
+pub const MAX_NAME_LEN: usize = 64;
+
+/// Maximum number of named programs per session.
+pub const MAX_PROGRAMS: usize = 100;
+
+/// Tables of the database, created when it is opened.
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS programs (
+    session TEXT NOT NULL,
+    name TEXT NOT NULL,
+    code TEXT NOT NULL,
+    updated_at INTEGER NOT NULL,
+    PRIMARY KEY (session, name)
+);
+CREATE TABLE IF NOT EXISTS drafts (
+    session TEXT PRIMARY KEY,
+    code TEXT NOT NULL,
+    updated_at INTEGER NOT NULL
+);
+";
+
+/// Error returned by `Storage` operations.
+#[derive(Debug)]
+pub enum StorageError {
+    /// The name is empty, too long or has control characters
+    InvalidName,
+    /// No program has this name
+    NotFound(String),
+    /// Another program already has this name
+    AlreadyExists(String),
+    /// The session already has `MAX_PROGRAMS` programs
+    TooMany,
+    /// The database could not be read or written
+    Database(rusqlite::Error),
+}
+
+impl fmt::Display for StorageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StorageError::InvalidName => write!(
+                f,
+                "program names must have 1 to {MAX_NAME_LEN} characters, without control characters"
+            ),
+            StorageError::NotFound(name) => write!(f, "no program named \"{name}\""),
+            StorageError::AlreadyExists(name) => {
+                write!(f, "a program named \"{name}\" already exists")
+            }
+            StorageError::TooMany => write!(f, "too many saved programs (max {MAX_PROGRAMS})"),
+            StorageError::Database(e) => write!(f, "database error: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for StorageError {}
+
+impl From<rusqlite::Error> for StorageError {
+    fn from(e: rusqlite::Error) -> Self {
+        StorageError::Database(e)
+    }
+}
+
+/// Checks a program name, and returns it without surrounding spaces.
+fn check_name(name: &str) -> Result<&str, StorageError> {
+    let name = name.trim();
+    if name.is_empty() || name.chars().count() > MAX_NAME_LEN || name.chars().any(char::is_control)
+    {
+        return Err(StorageError::InvalidName);
+    }
+    Ok(name)
+}
+
+/// Current time, in seconds since the Unix epoch.
+fn now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs() as i64)
+}
+
+/// Connection to the programs database.
+///
+/// SQLite calls block, so queries are run on the blocking threads of tokio
+/// (not on the workers running the handlers), one at a time.
+pub struct Storage {
+    connection: Arc<Mutex<Connection>>,
+}
+
+impl Storage {
+    /// Opens (or creates) the database file and its tables.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Path of the SQLite file.
+    ///
+    /// # Errors
+    ///
+    /// Returns `StorageError::Database` if the file cannot be opened or is not
+    /// a SQLite database.
+    pub fn open(path: &Path) -> Result<Storage, StorageError> {
+        let connection = Connection::open(path)?;
+        connection.execute_batch(SCHEMA)?;
+        Ok(Storage {
+            connection: Arc::new(Mutex::new(connection)),
+        })
+    }
+
+    /// Runs `f` with the connection, on a blocking thread.
+    ///
+    /// # Panics
+    ///
+    /// Will panic if `f` panics, or if another query panicked while holding
+    /// the connection.
+    async fn with<T: Send + 'static>(
+        &self,
+        f: impl FnOnce(&mut Connection) -> Result<T, StorageError> + Send + 'static,
+    ) -> Result<T, StorageError> {
+        let connection = Arc::clone(&self.connection);
+        tokio::task::spawn_blocking(move || f(&mut connection.lock().unwrap()))
+            .await
+            .unwrap()
+    }
+
+    /// Names of the programs of a session, in alphabetical order.
+    pub async fn programs(&self, session: &str) -> Result<Vec<String>, StorageError> {
+        let session = session.to_string();
+        self.with(move |connection| {
+            let mut statement =
+                connection.prepare("SELECT name FROM programs WHERE session = ?1 ORDER BY name")?;
+            let names = statement
+                .query_map([session], |row| row.get(0))?
+                .collect::<Result<_, _>>()?;
+            Ok(names)
+        })
+        .await
+    }
+
+    /// Code of a program.
+    ///
+    /// # Errors
+    ///
+    /// Returns `StorageError::NotFound` if the session has no program with this name.
+    pub async fn program(&self, session: &str, name: &str) -> Result<String, StorageError> {
+        let (session, name) = (session.to_string(), name.to_string());
+        self.with(move |connection| {
+            connection
+                .query_row(
+                    "SELECT code FROM programs WHERE session = ?1 AND name = ?2",
+                    params![session, name],
+                    |row| row.get(0),
+                )
+                .optional()?
+                .ok_or_else(|| StorageError::NotFound(name.to_string()))
+        })
+        .await
+    }
+
+    /// Saves a program, replacing the one with the same name if any.
+    ///
+    /// # Returns
+    ///
+    /// The name the program was saved under (without surrounding spaces).
+    ///
+    /// # Errors
+    ///
+    /// Returns `StorageError::InvalidName` if the name is not valid, or
+    /// `StorageError::TooMany` if this is a new program and the session
+    /// already has `MAX_PROGRAMS` programs.
+    pub async fn save_program(
+        &self,
+        session: &str,
+        name: &str,
+        code: &str,
+    ) -> Result<String, StorageError> {
+        let name = check_name(name).map(str::to_string)?;
+        let (session, code) = (session.to_string(), code.to_string());
+        self.with(move |connection| {
+            let transaction = connection.transaction()?;
+            let exists = transaction
+                .query_row(
+                    "SELECT 1 FROM programs WHERE session = ?1 AND name = ?2",
+                    params![session, name],
+                    |_| Ok(()),
+                )
+                .optional()?
+                .is_some();
+            let count: i64 = transaction.query_row(
+                "SELECT COUNT(*) FROM programs WHERE session = ?1",
+                [&session],
+                |row| row.get(0),
+            )?;
+            if !exists && count >= MAX_PROGRAMS as i64 {
+                return Err(StorageError::TooMany);
+            }
+            transaction.execute(
+                "INSERT INTO programs (session, name, code, updated_at) VALUES (?1, ?2, ?3, ?4)
+                 ON CONFLICT (session, name) DO UPDATE SET code = ?3, updated_at = ?4",
+                params![session, name, code, now()],
+            )?;
+            transaction.commit()?;
+            Ok(name)
+        })
+        .await
+    }
+
+    /// Renames a program.
+    ///
+    /// # Returns
+    ///
+    /// The new name (without surrounding spaces).
+    ///
+    /// # Errors
+    ///
+    /// Returns `StorageError::InvalidName` if the new name is not valid,
+    /// `StorageError::NotFound` if there is no program named `name`, or
+    /// `StorageError::AlreadyExists` if another program is named `new_name`.
+    pub async fn rename_program(
+        &self,
+        session: &str,
+        name: &str,
+        new_name: &str,
+    ) -> Result<String, StorageError> {
+        let new_name = check_name(new_name).map(str::to_string)?;
+        let (session, name) = (session.to_string(), name.to_string());
+        self.with(move |connection| {
+            let result = connection.execute(
+                "UPDATE programs SET name = ?3, updated_at = ?4 WHERE session = ?1 AND name = ?2",
+                params![session, name, new_name, now()],
+            );
+            match result {
+                Ok(0) => Err(StorageError::NotFound(name)),
+                Ok(_) => Ok(new_name),
+                Err(rusqlite::Error::SqliteFailure(e, _))
+                    if e.code == rusqlite::ErrorCode::ConstraintViolation =>
+                {
+                    Err(StorageError::AlreadyExists(new_name))
+                }
+                Err(e) => Err(e.into()),
+            }
+        })
+        .await
+    }
+
+    /// Deletes a program.
+    ///
+    /// # Errors
+    ///
+    /// Returns `StorageError::NotFound` if the session has no program with this name.
+    pub async fn delete_program(&self, session: &str, name: &str) -> Result<(), StorageError> {
+        let (session, name) = (session.to_string(), name.to_string());
+        self.with(move |connection| {
+            match connection.execute(
+                "DELETE FROM programs WHERE session = ?1 AND name = ?2",
+                params![session, name],
+            )? {
+                0 => Err(StorageError::NotFound(name.to_string())),
+                _ => Ok(()),
+            }
+        })
+        .await
+    }
+
+    /// Last program sent by a session, or an empty string.
+    pub async fn draft(&self, session: &str) -> Result<String, StorageError> {
+        let session = session.to_string();
+        self.with(move |connection| {
+            let code = connection
+                .query_row(
+                    "SELECT code FROM drafts WHERE session = ?1",
+                    [session],
+                    |row| row.get(0),
+                )
+                .optional()?;
+            Ok(code.unwrap_or_default())
+        })
+        .await
+    }
+
+    /// Replaces the draft of a session.
+    pub async fn save_draft(&self, session: &str, code: &str) -> Result<(), StorageError> {
+        let (session, code) = (session.to_string(), code.to_string());
+        self.with(move |connection| {
+            connection.execute(
+                "INSERT INTO drafts (session, code, updated_at) VALUES (?1, ?2, ?3)
+                 ON CONFLICT (session) DO UPDATE SET code = ?2, updated_at = ?3",
+                params![session, code, now()],
+            )?;
+            Ok(())
+        })
+        .await
+    }
+}
@@ -0,0 +1,399 @@
+//! `rustbot tui`: play mode in the terminal, for machines without a browser.
+//!
+//! It mirrors `/play`: arrow keys move the robot, Backspace resets it, a click
+//! on a cell teleports it there, `p` toggles the pen and a form changes the
+//! size of the grid.
+
+use std::io;
+use std::process::ExitCode;
+
+use ratatui::{
+    DefaultTerminal, Frame,
+    crossterm::{
+        event::{
+            self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyEvent, KeyEventKind,
+            KeyModifiers, MouseButton, MouseEvent, MouseEventKind,
+        },
+        execute,
+    },
+    layout::{Constraint, Layout, Position, Rect},
+    style::{Color, Modifier, Style},
+    text::{Line, Span},
+    widgets::{Block, Paragraph},
+};
+use rustbot_core::{Color as PenColor, Command, Limits, World};
+
+use crate::cli::WorldArgs;
+
+/// Width of a cell of the grid, in terminal columns.
+const CELL_WIDTH: u16 = 3;
+
+/// Width of the line numbers on the left of the grid.
+const HEADER_WIDTH: u16 = 3;
+
+/// Arguments of `rustbot tui`.
+#[derive(clap::Args)]
+pub struct TuiArgs {
+    #[command(flatten)]
+    world: WorldArgs,
+    /// Maximum number of lines accepted by the grid size form
+    #[arg(long, default_value_t = Limits::default().max_lines)]
+    max_lines: u32,
+    /// Maximum number of columns accepted by the grid size form
+    #[arg(long, default_value_t = Limits::default().max_columns)]
+    max_columns: u32,
+}
+
+/// Field of the grid size form being edited.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Field {
+    Lines,
+    Columns,
+}
+
+/// Where the keys go.
+enum Focus {
+    /// Keys move the robot
+    Grid,
+    /// Keys edit the grid size form
+    Form {
+        field: Field,
+        lines: String,
+        columns: String,
+    },
+}
+
+/// State of the terminal UI.
+struct App {
+    world: World,
+    max_lines: u32,
+    max_columns: u32,
+    focus: Focus,
+    /// Message shown in the side panel (last error…)
+    message: Option<String>,
+    /// Where the grid was last drawn, to find the cell under a click
+    grid_area: Rect,
+    quit: bool,
+}
+
+impl App {
+    /// Applies a command to the world, keeping the error to show it.
+    fn apply(&mut self, command: Command) {
+        self.message = match self.world.apply(command) {
+            Ok(()) => None,
+            Err(e) => Some(e.to_string()),
+        };
+    }
+
+    /// Handles a key press.
+    fn on_key(&mut self, key: KeyEvent) {
+        if key.modifiers.contains(KeyModifiers::CONTROL) && key.code == KeyCode::Char('c') {
+            self.quit = true;
+            return;
+        }
+        match &mut self.focus {
+            Focus::Grid => match key.code {
+                KeyCode::Right => self.apply(Command::Right),
+                KeyCode::Left => self.apply(Command::Left),
+                KeyCode::Up => self.apply(Command::Up),
+                KeyCode::Down => self.apply(Command::Down),
+                KeyCode::Backspace => self.apply(Command::Reset),
+                KeyCode::Char('p') if self.world.is_pen_down() => self.apply(Command::PenUp),
+                KeyCode::Char('p') => self.apply(Command::PenDown),
+                KeyCode::Char('c') => {
+                    // Next color of the pen, back to the first one after the last
+                    let index = (self.world.pen_color().index() + 1) % PenColor::ALL.len();
+                    self.apply(Command::PenColor(PenColor::ALL[index]));
+                }
+                KeyCode::Char('s') => {
+                    self.focus = Focus::Form {
+                        field: Field::Lines,
+                        lines: self.world.lines().to_string(),
+                        columns: self.world.columns().to_string(),
+                    }
+                }
+                KeyCode::Char('q') | KeyCode::Esc => self.quit = true,
+                _ => {}
+            },
+            Focus::Form {
+                field,
+                lines,
+                columns,
+            } => {
+                if key.code == KeyCode::Enter {
+                    // An empty field is refused by `World::new` like a 0
+                    let lines = lines.parse().unwrap_or(0);
+                    let columns = columns.parse().unwrap_or(0);
+                    self.change_max(lines, columns);
+                    return;
+                }
+                let value = match field {
+                    Field::Lines => lines,
+                    Field::Columns => columns,
+                };
+                match key.code {
+                    KeyCode::Char(c) if c.is_ascii_digit() && value.len() < 4 => value.push(c),
+                    KeyCode::Backspace => {
+                        value.pop();
+                    }
+                    KeyCode::Tab | KeyCode::BackTab | KeyCode::Up | KeyCode::Down => {
+                        *field = match field {
+                            Field::Lines => Field::Columns,
+                            Field::Columns => Field::Lines,
+                        }
+                    }
+                    KeyCode::Esc => self.focus = Focus::Grid,
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    /// Changes the size of the grid and puts the robot back at `(0, 0)`, like `/change-max`.
+    ///
+    /// # Arguments
+    ///
+    /// * `lines` - New number of lines, limited to `max_lines`.
+    /// * `columns` - New number of columns, limited to `max_columns`.
+    fn change_max(&mut self, lines: u32, columns: u32) {
+        match World::new(lines.min(self.max_lines), columns.min(self.max_columns)) {
+            Ok(world) => {
+                self.world = world;
+                self.focus = Focus::Grid;
+                self.message = None;
+            }
+            Err(e) => self.message = Some(e.to_string()),
+        }
+    }
+
+    /// Handles a mouse event: a left click on a cell teleports the robot there.
+    fn on_mouse(&mut self, mouse: MouseEvent) {
+        if mouse.kind != MouseEventKind::Down(MouseButton::Left) {
+            return;
+        }
+        let area = self.grid_area;
+        // First line and first columns of the grid area are the headers
+        let x = mouse.column.checked_sub(area.x + HEADER_WIDTH);
+        let y = mouse.row.checked_sub(area.y + 1);
+        if let (Some(x), Some(y)) = (x, y) {
+            let (i, j) = (u32::from(y), u32::from(x / CELL_WIDTH));
+            if i < self.world.lines() && j < self.world.columns() {
+                self.apply(Command::GoTo(i, j));
+            }
+        }
+    }
+
+    /// Draws the grid on the left and the side panel on the right.
+    fn draw(&mut self, frame: &mut Frame) {
+        let [grid_area, panel_area] =
+            Layout::horizontal([Constraint::Min(0), Constraint::Length(34)]).areas(frame.area());
+
+        let grid_block = Block::bordered().title(" RustBot 🦀 ");
+        self.grid_area = grid_block.inner(grid_area);
+        frame.render_widget(
+            Paragraph::new(self.grid_lines()).block(grid_block),
+            grid_area,
+        );
+
+        let panel = Paragraph::new(self.panel_lines()).block(Block::bordered().title(" Play "));
+        frame.render_widget(panel, panel_area);
+
+        // Show the cursor in the form field being edited
+        if let Focus::Form {
+            field,
+            lines,
+            columns,
+        } = &self.focus
+        {
+            let (row, value) = match field {
+                Field::Lines => (9, lines),
+                Field::Columns => (10, columns),
+            };
+            frame.set_cursor_position(Position::new(
+                panel_area.x + 1 + 10 + value.len() as u16,
+                panel_area.y + 1 + row,
+            ));
+        }
+    }
+
+    /// Lines of the grid, with line and column numbers as in code mode.
+    fn grid_lines(&self) -> Vec<Line<'static>> {
+        let header_style = Style::new().fg(Color::DarkGray);
+        let robot = self.world.robot();
+
+        let mut header = vec![Span::raw(" ".repeat(HEADER_WIDTH as usize))];
+        for j in 0..self.world.columns() {
+            header.push(Span::styled(format!("{j:^3}"), header_style));
+        }
+        let mut lines = vec![Line::from(header)];
+
+        for i in 0..self.world.lines() {
+            let mut line = vec![Span::styled(format!("{i:>2} "), header_style)];
+            for j in 0..self.world.columns() {
+                if i == robot.i && j == robot.j {
+                    let style = Style::new()
+                        .fg(Color::Black)
+                        .bg(Color::Yellow)
+                        .add_modifier(Modifier::BOLD);
+                    line.push(Span::styled(" R ", style));
+                    continue;
+                }
+                // Number of items (`+` when it doesn't fit) on top of the paint
+                let mut style = Style::new();
+                if let Some(color) = self.world.paint_at(i, j) {
+                    let [r, g, b] = color.rgb();
+                    style = style.bg(Color::Rgb(r, g, b));
+                }
+                match self.world.items_at(i, j) {
+                    0 if style == Style::new() => line.push(Span::raw(" · ")),
+                    0 => line.push(Span::styled("   ", style)),
+                    count => {
+                        let text = if count < 1000 {
+                            format!("{count:^3}")
+                        } else {
+                            " + ".to_string()
+                        };
+                        let style = style.fg(Color::Green).add_modifier(Modifier::BOLD);
+                        line.push(Span::styled(text, style));
+                    }
+                }
+            }
+            lines.push(Line::from(line));
+        }
+        lines
+    }
+
+    /// Lines of the side panel: coordinates, grid size form and help.
+    fn panel_lines(&self) -> Vec<Line<'static>> {
+        let robot = self.world.robot();
+        let (lines, columns, field) = match &self.focus {
+            Focus::Grid => (
+                self.world.lines().to_string(),
+                self.world.columns().to_string(),
+                None,
+            ),
+            Focus::Form {
+                field,
+                lines,
+                columns,
+            } => (lines.clone(), columns.clone(), Some(*field)),
+        };
+        let field_style = |this: Field| {
+            if field == Some(this) {
+                Style::new().add_modifier(Modifier::REVERSED)
+            } else {
+                Style::new()
+            }
+        };
+
+        let mut text = vec![
+            Line::from(format!("Current coordinates: ({}, {})", robot.i, robot.j)),
+            Line::from(format!(
+                "Grid: {} x {}",
+                self.world.lines(),
+                self.world.columns()
+            )),
+            Line::from(format!(
+                "Pen: {} ({})",
+                if self.world.is_pen_down() {
+                    "down"
+                } else {
+                    "up"
+                },
+                self.world.pen_color()
+            )),
+            Line::from(format!("Heading: {}", self.world.heading())),
+            Line::from(format!("Items carried: {}", self.world.inventory())),
+            Line::from(""),
+            Line::from("Change grid size (robot position"),
+            Line::from("will be reset to (0, 0)):"),
+            Line::from(""),
+            Line::from(vec![
+                Span::raw("Lines:    "),
+                Span::styled(lines, field_style(Field::Lines)),
+                Span::raw(format!(" (1-{})", self.max_lines)),
+            ]),
+            Line::from(vec![
+                Span::raw("Columns:  "),
+                Span::styled(columns, field_style(Field::Columns)),
+                Span::raw(format!(" (1-{})", self.max_columns)),
+            ]),
+            Line::from(""),
+        ];
+        let help: &[&str] = match self.focus {
+            Focus::Grid => &[
+                "←↑→↓      move",
+                "Backspace reset to (0, 0)",
+                "click     teleport",
+                "p         pen down/up",
+                "c         pen color",
+                "s         change grid size",
+                "q         quit",
+            ],
+            Focus::Form { .. } => &[
+                "Tab       other field",
+                "Enter     update",
+                "Esc       cancel",
+            ],
+        };
+        text.extend(help.iter().map(|line| Line::from(*line)));
+        if let Some(message) = &self.message {
+            text.push(Line::from(""));
+            text.push(Line::styled(message.clone(), Style::new().fg(Color::Red)));
+        }
+        text
+    }
+
+    /// Draws the UI and handles events until the user quits.
+    fn run(&mut self, terminal: &mut DefaultTerminal) -> io::Result<()> {
+        while !self.quit {
+            terminal.draw(|frame| self.draw(frame))?;
+            match event::read()? {
+                Event::Key(key) if key.kind == KeyEventKind::Press => self.on_key(key),
+                Event::Mouse(mouse) => self.on_mouse(mouse),
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Runs the terminal UI until the user quits.
+///
+/// # Returns
+///
+/// The exit code of the process: `2` if the starting world is impossible,
+/// `1` if the terminal cannot be used.
+pub fn tui(args: TuiArgs) -> ExitCode {
+    let world = match args.world.world() {
+        Ok(world) => world,
+        Err(e) => {
+            eprintln!("error: {e}");
+            return ExitCode::from(2);
+        }
+    };
+    let mut app = App {
+        world,
+        max_lines: args.max_lines,
+        max_columns: args.max_columns,
+        focus: Focus::Grid,
+        message: None,
+        grid_area: Rect::default(),
+        quit: false,
+    };
+
+    let result = ratatui::try_init().and_then(|mut terminal| {
+        execute!(io::stdout(), EnableMouseCapture)?;
+        let result = app.run(&mut terminal);
+        execute!(io::stdout(), DisableMouseCapture)?;
+        result
+    });
+    ratatui::restore();
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("error: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
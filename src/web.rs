@@ -0,0 +1,2267 @@
+//! The RustBot web server: routes, templates and cookie storage of the world.
+//!
+//! All the robot logic lives in `rustbot-core`; handlers only load the `World`
+//! from cookies, apply commands to it and store it back.
+
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use askama::Template;
+use axum::{
+    Extension, Json, Router,
+    extract::{Form, Path, Query, State},
+    http::{HeaderMap, StatusCode, header},
+    middleware,
+    response::{Html, IntoResponse, Redirect, Response},
+    routing::{get, post},
+};
+use axum_cookie::prelude::*;
+use rustbot_core::{
+    Color, Command, Dialect, Limits, Robot, World, karel, lang, render as core_render,
+};
+use serde::{Deserialize, Serialize};
+use tower_http::services::ServeDir;
+
+use crate::ast::{self, AstProgram};
+use crate::config::{Config, GridConfig, SessionConfig};
+use crate::permalink::Permalink;
+use crate::session::SessionId;
+use crate::state::SessionState;
+use crate::storage::{Storage, StorageError};
+use crate::{health, logging, metrics, raster, session};
+
+/// Struct representing the new grid sizes submitted via a form.
+///
+/// Used to deserialize the POST request payload from `/change-max`.
+#[derive(Deserialize)]
+struct MaxGridSizes {
+    change_max_i: u32,
+    change_max_j: u32,
+}
+
+/// Struct representing the pen color chosen in play mode.
+///
+/// Used to deserialize the POST request payload from `/pen-color`.
+#[derive(Deserialize)]
+struct PenColor {
+    color: String,
+}
+
+/// Struct representing the program submitted in code mode.
+///
+/// Used to deserialize the POST request payload from `/user-code`.
+#[derive(Deserialize)]
+struct UserCode {
+    user_code: String,
+    /// Language of the program (see `rustbot_core::Dialect`), `rustbot` if missing
+    dialect: Option<String>,
+}
+
+/// Reads the dialect of a submitted program, `rustbot` if missing.
+///
+/// # Errors
+///
+/// Returns an error message for an unknown dialect.
+fn read_dialect(name: Option<&str>) -> Result<Dialect, String> {
+    match name {
+        Some(name) => name.parse(),
+        None => Ok(Dialect::default()),
+    }
+}
+
+impl UserCode {
+    /// Reads the dialect of the program.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error message for an unknown dialect.
+    fn dialect(&self) -> Result<Dialect, String> {
+        read_dialect(self.dialect.as_deref())
+    }
+}
+
+/// Body of the `/optimize` response (see `rustbot_core::optimize`).
+#[derive(Serialize)]
+struct OptimizedProgram {
+    /// Number of commands executed by the submitted program
+    steps: usize,
+    /// Position and heading of the robot after the program
+    i: u32,
+    j: u32,
+    heading: &'static str,
+    /// Shortest program leaving the robot at the same position, with the same heading
+    program: String,
+    /// Number of commands of `program`
+    optimal_steps: usize,
+    /// Whether `program` also leaves the rest of the world as the submitted one
+    equivalent: bool,
+}
+
+/// Struct representing the cursor in a program being written.
+///
+/// Used to deserialize the POST request payload from `/lang/complete`.
+#[derive(Deserialize)]
+struct Cursor {
+    user_code: String,
+    /// Offset of the cursor, in characters from the start of `user_code`
+    offset: usize,
+    /// Language of the program, which must be `rustbot` if present
+    dialect: Option<String>,
+}
+
+/// A token of `/lang/tokens` (see `rustbot_core::lang::TokenSpan`).
+#[derive(Serialize)]
+struct TokenBody {
+    line: usize,
+    start: usize,
+    end: usize,
+    kind: &'static str,
+}
+
+/// A completion of `/lang/complete` (see `rustbot_core::lang::Completion`).
+#[derive(Serialize)]
+struct CompletionBody {
+    text: String,
+    start: usize,
+    kind: &'static str,
+}
+
+/// Struct representing a program to save, submitted from code mode.
+///
+/// Used to deserialize the POST request payload from `/programs`.
+#[derive(Deserialize)]
+struct SavedProgram {
+    name: String,
+    user_code: String,
+}
+
+/// Struct representing the new name of a saved program.
+///
+/// Used to deserialize the POST request payload from `/programs/{name}/rename`.
+#[derive(Deserialize)]
+struct NewName {
+    new_name: String,
+}
+
+/// Format of the response to a program, chosen with `?format=` on `/user-code`.
+#[derive(Deserialize, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+enum Format {
+    /// The code mode page
+    #[default]
+    Html,
+    /// A PNG image of the grid after the program
+    Png,
+    /// An animated GIF with one frame per executed command
+    Gif,
+    /// Only the diagnostics panel of the code mode page, for `robot_code.js`
+    Fragment,
+}
+
+/// Query string of `/user-code`.
+#[derive(Deserialize)]
+struct FormatQuery {
+    #[serde(default)]
+    format: Format,
+}
+
+/// Query string of `/fragment/grid`.
+#[derive(Deserialize)]
+struct GridQuery {
+    /// Show line and column numbers around the grid, as in code mode
+    #[serde(default)]
+    headers: bool,
+}
+
+/// Biggest side of a cell in `/canvas.png` and `/canvas.svg`, in pixels.
+const MAX_CANVAS_SCALE: u32 = 64;
+
+/// Query string of `/canvas.png` and `/canvas.svg`.
+#[derive(Deserialize)]
+struct CanvasQuery {
+    /// Side of a cell, in pixels (1 to `MAX_CANVAS_SCALE`)
+    #[serde(default = "default_canvas_scale")]
+    scale: u32,
+}
+
+/// Default `scale` of `/canvas.png` and `/canvas.svg`: one pixel per cell.
+fn default_canvas_scale() -> u32 {
+    1
+}
+
+/// Template context for the grid, shared by both modes.
+///
+/// Passed to Askama to render `partials/grid.html`, also included by the pages.
+#[derive(Template)]
+#[template(path = "partials/grid.html")]
+struct GridFragment {
+    rustbot_i: u32,
+    rustbot_j: u32,
+    grid_max_i: u32,
+    grid_max_j: u32,
+    world: World,
+    /// Show line and column numbers
+    headers: bool,
+}
+
+/// Template context for the coordinates panel of play mode.
+///
+/// Passed to Askama to render `partials/coords.html`, also included by `template_play.html`.
+#[derive(Template)]
+#[template(path = "partials/coords.html")]
+struct CoordsFragment {
+    rustbot_i: u32,
+    rustbot_j: u32,
+}
+
+/// Template context for the pen controls of play mode (toggle and color).
+///
+/// Passed to Askama to render `partials/pen.html`, also included by `template_play.html`.
+#[derive(Template)]
+#[template(path = "partials/pen.html")]
+struct PenFragment {
+    world: World,
+}
+
+/// Template context for the diagnostics panel of code mode (the error and the
+/// warnings of the last program).
+///
+/// Passed to Askama to render `partials/diagnostics.html`, also included by `template_code.html`.
+#[derive(Template)]
+#[template(path = "partials/diagnostics.html")]
+struct DiagnosticsFragment {
+    error: Option<String>,
+    warnings: Vec<String>,
+}
+
+/// Template context for the list of saved programs of code mode.
+///
+/// Passed to Askama to render `partials/programs.html`, also included by `template_code.html`.
+#[derive(Template)]
+#[template(path = "partials/programs.html")]
+struct ProgramsFragment {
+    programs: Vec<String>,
+}
+
+/// Template context for the root page.
+///
+/// Passed to Askama to render `template_root.html`.
+#[derive(Template)]
+#[template(path = "template_root.html")]
+struct RootTemplate {}
+
+/// Template context for the play mode page.
+///
+/// Passed to Askama to render `template_play.html`.
+#[derive(Template)]
+#[template(path = "template_play.html")]
+struct PlayTemplate {
+    rustbot_i: u32,
+    rustbot_j: u32,
+    grid_max_i: u32,
+    grid_max_j: u32,
+    max_lines: u32,
+    max_columns: u32,
+    world: World,
+}
+
+impl PlayTemplate {
+    /// Builds the play mode page context from the world.
+    fn new(world: &World, grid_config: &GridConfig) -> Self {
+        PlayTemplate {
+            rustbot_i: world.robot().i,
+            rustbot_j: world.robot().j,
+            grid_max_i: world.lines(),
+            grid_max_j: world.columns(),
+            max_lines: grid_config.max_lines,
+            max_columns: grid_config.max_columns,
+            world: world.clone(),
+        }
+    }
+}
+
+/// Template context for the code mode page.
+///
+/// Passed to Askama to render `template_code.html`.
+#[derive(Template)]
+#[template(path = "template_code.html")]
+struct CodeTemplate {
+    rustbot_i: u32,
+    rustbot_j: u32,
+    grid_max_i: u32,
+    grid_max_j: u32,
+    /// Error of the last program, if it failed
+    error: Option<String>,
+    /// Warnings about the last program (see `rustbot_core::lint`)
+    warnings: Vec<String>,
+    world: World,
+    /// Content of the textarea: the last program sent
+    code: String,
+    /// Names of the saved programs
+    programs: Vec<String>,
+    /// Language of the program
+    dialect: Dialect,
+}
+
+impl CodeTemplate {
+    /// Builds the code mode page context from the world and the stored programs.
+    fn new(
+        world: &World,
+        error: Option<String>,
+        code: String,
+        programs: Vec<String>,
+        dialect: Dialect,
+    ) -> Self {
+        CodeTemplate {
+            rustbot_i: world.robot().i,
+            rustbot_j: world.robot().j,
+            grid_max_i: world.lines(),
+            grid_max_j: world.columns(),
+            error,
+            warnings: Vec::new(),
+            world: world.clone(),
+            code,
+            programs,
+            dialect,
+        }
+    }
+}
+
+/// Builds the Axum application, defining all routes for controlling the bot
+/// and serving static files.
+///
+/// # Routes
+/// - `/` → `root`: main menu
+/// - `/play` → `play`: play mode page
+/// - `/reset` → `reset`: reset coordinates to (0, 0)
+/// - `/right` → `right`: move bot right
+/// - `/left` → `left`: move bot left
+/// - `/down` → `down`: move bot down
+/// - `/up` → `up`: move bot up
+/// - `/coords/{i}/{j}` → `teleport`: teleport bot to specific coordinates
+/// - `/change-max` → `change_max`: update grid size via form submission
+/// - `/pen` → `pen`: put the pen down or lift it
+/// - `/pen-color` → `pen_color`: change the color of the pen via form submission
+/// - `/code` → `code`: code mode page
+/// - `/user-code` → `user_code`: run a program sent from code mode
+/// - `/user-code/ast` → `user_code_ast`: run a program sent as a JSON tree of blocks
+/// - `/ast/schema` → `ast_schema`: JSON Schema of trees of blocks
+/// - `/ast/to-text`, `/ast/from-text` → `ast_to_text`, `ast_from_text`: convert programs
+/// - `/format` → `format_program`: format a program
+/// - `/programs` → `list_programs`, `save_program`: saved programs of the session
+/// - `/programs/{name}` → `load_program`, `delete_program`: one saved program
+/// - `/programs/{name}/rename` → `rename_program`: rename a saved program
+/// - `/share` → `share`: permalink to the grid and a program
+/// - `/s/{blob}` → `open_permalink`: restore the grid and program of a permalink
+/// - `/state/export` → `export_state`: the whole session as JSON
+/// - `/state/import` → `import_state`: restore a session exported as JSON
+/// - `/karel/world` → `import_karel_world`: replace the grid by a Karel world file
+/// - `/render.svg` → `render_svg`: SVG image of the grid
+/// - `/render.png` → `render_png`: PNG image of the grid
+/// - `/canvas.svg` → `canvas_svg`: SVG image of the painted cells only
+/// - `/canvas.png` → `canvas_png`: PNG image of the painted cells only
+/// - `/fragment/grid` → `fragment_grid`: the grid alone, to update pages
+/// - `/fragment/coords` → `fragment_coords`: the coordinates panel alone
+/// - `/fragment/pen` → `fragment_pen`: the pen controls alone
+/// - `/fragment/programs` → `fragment_programs`: the list of saved programs alone
+/// - `/metrics` → `metrics::metrics`: Prometheus metrics
+/// - `/healthz`, `/readyz`, `/version` → see `health.rs`
+/// - `/static` → serves static files from the configured static directory
+///
+/// # Notes
+/// - Uses `CookieLayer` for storing coordinates in cookies.
+/// - Programs are stored in `storage`, shared with handlers as an `Extension`.
+/// - Every request is logged (see `logging.rs`) with its session id (see `session.rs`).
+pub fn app(config: Arc<Config>, storage: Arc<Storage>) -> Router {
+    let static_dir = config.server.static_dir.clone();
+    Router::new()
+        // Root: main page
+        .route("/", get(root))
+        // Play mode:
+        .route("/play", get(play))
+        .route("/reset", get(reset).post(reset))
+        .route("/right", get(right).post(right))
+        .route("/left", get(left).post(left))
+        .route("/down", get(down).post(down))
+        .route("/up", get(up).post(up))
+        .route("/coords/{i}/{j}", get(teleport).post(teleport))
+        .route("/change-max", post(change_max))
+        .route("/pen", get(pen).post(pen))
+        .route("/pen-color", post(pen_color))
+        // Code mode:
+        .route("/code", get(code))
+        .route("/user-code", post(user_code))
+        .route("/user-code/ast", post(user_code_ast))
+        .route("/ast/schema", get(ast_schema))
+        .route("/ast/to-text", post(ast_to_text))
+        .route("/ast/from-text", post(ast_from_text))
+        .route("/format", post(format_program))
+        .route("/optimize", post(optimize_program))
+        .route("/lang/tokens", post(lang_tokens))
+        .route("/lang/complete", post(lang_complete))
+        .route("/programs", get(list_programs).post(save_program))
+        .route("/programs/{name}", get(load_program).delete(delete_program))
+        .route("/programs/{name}/rename", post(rename_program))
+        .route("/share", post(share))
+        .route("/s/{blob}", get(open_permalink))
+        // Session snapshots:
+        .route("/state/export", get(export_state))
+        .route("/state/import", post(import_state))
+        .route("/karel/world", post(import_karel_world))
+        // Images:
+        .route("/render.svg", get(render_svg))
+        .route("/render.png", get(render_png))
+        .route("/canvas.svg", get(canvas_svg))
+        .route("/canvas.png", get(canvas_png))
+        // Page fragments, swapped in by the scripts:
+        .route("/fragment/grid", get(fragment_grid))
+        .route("/fragment/coords", get(fragment_coords))
+        .route("/fragment/pen", get(fragment_pen))
+        .route("/fragment/programs", get(fragment_programs))
+        // Static pages
+        .nest_service("/static", ServeDir::new(static_dir))
+        // Layers: the last one added is the first one run
+        .layer(Extension(storage))
+        .layer(middleware::from_fn(metrics::track_request))
+        .layer(middleware::from_fn(logging::log_request))
+        .layer(middleware::from_fn_with_state(
+            config.clone(),
+            session::ensure_session,
+        ))
+        .layer(CookieLayer::default())
+        // Monitoring: added after the layers so probes don't get sessions
+        .route("/metrics", get(metrics::metrics))
+        .route("/healthz", get(health::healthz))
+        .route("/readyz", get(health::readyz))
+        .route("/version", get(health::version))
+        .with_state(config)
+}
+
+/// Binds a TCP listener on the configured address and serves the application
+/// until `SIGINT`/`SIGTERM` (and the drain delay), after in-flight requests
+/// are done.
+///
+/// # Arguments
+///
+/// * `config` - Server configuration.
+/// * `storage` - The opened programs database.
+///
+/// # Panics
+///
+/// Will panic if the address cannot be bound.
+pub async fn serve(config: Config, storage: Storage) {
+    let address = config.address();
+    let drain_delay = Duration::from_secs(config.server.drain_delay);
+    let app = app(Arc::new(config), Arc::new(storage));
+
+    // run app with hyper, listening on configured address
+    let listener = tokio::net::TcpListener::bind(address).await.unwrap();
+    tracing::info!(%address, "rustbot listening");
+    axum::serve(listener, app)
+        .with_graceful_shutdown(health::shutdown_signal(drain_delay))
+        .await
+        .unwrap();
+    // Robot state lives in the browsers' cookies and programs are written to the
+    // database as they are saved: nothing else to save before exiting
+    tracing::info!("rustbot stopped");
+}
+
+/// Interpreter limits matching the configuration.
+fn limits(config: &Config) -> Limits {
+    Limits {
+        max_lines: config.grid.max_lines,
+        max_columns: config.grid.max_columns,
+        max_items: config.grid.max_items,
+        max_steps: config.interpreter.max_steps,
+    }
+}
+
+/// Checks that a submitted program is not too long.
+///
+/// # Arguments
+///
+/// * `config` - Server configuration, for the maximum program length.
+/// * `user_code` - The submitted program.
+///
+/// # Returns
+///
+/// A `413 Payload Too Large` response if it is longer than `max_program_len`.
+fn check_program_len(config: &Config, user_code: &str) -> Option<Response> {
+    let max_program_len = config.interpreter.max_program_len;
+    (user_code.len() > max_program_len).then(|| {
+        (
+            StatusCode::PAYLOAD_TOO_LARGE,
+            format!("Program is too long (max {max_program_len} bytes)"),
+        )
+            .into_response()
+    })
+}
+
+/// Reads the current grid size from cookies.
+///
+/// This function checks the cookies `"max-i"` and `"max-j"` to determine
+/// the number of rows (`i`) and columns (`j`) of the grid. If the cookies
+/// are not present, it returns default values (`default_lines` and `default_columns`
+/// of the configuration).
+///
+/// If parse fails (for exemple if user changes cookies for absurd values), returns
+/// default values. Also forbids to have values set bigger than `max_lines`/`max_columns`.
+///
+/// # Parameters
+/// - `cookie`: Reference to the `CookieManager` from which to read the cookies.
+/// - `grid_config`: Default and maximum grid sizes.
+///
+/// # Returns
+/// A tuple `(grid_max_i, grid_max_j)` representing the number of rows and columns.
+fn get_grid_size(cookie: &CookieManager, grid_config: &GridConfig) -> (u32, u32) {
+    let mut grid_max_i = grid_config.default_lines;
+    let mut grid_max_j = grid_config.default_columns;
+    if let Some(max_i_cookie) = cookie.get("max-i") {
+        match max_i_cookie.value().parse() {
+            Ok(cookie_max_i) => {
+                if cookie_max_i > grid_config.max_lines {
+                    grid_max_i = grid_config.max_lines;
+                } else {
+                    grid_max_i = cookie_max_i;
+                }
+            }
+            Err(_) => grid_max_i = grid_config.default_lines,
+        }
+    }
+    if let Some(max_j_cookie) = cookie.get("max-j") {
+        match max_j_cookie.value().parse() {
+            Ok(cookie_max_j) => {
+                if cookie_max_j > grid_config.max_columns {
+                    grid_max_j = grid_config.max_columns;
+                } else {
+                    grid_max_j = cookie_max_j;
+                }
+            }
+            Err(_) => grid_max_j = grid_config.default_columns,
+        }
+    }
+    (grid_max_i, grid_max_j)
+}
+
+/// Retrieves Rustbot's current coordinates from cookies.
+///
+/// If the cookies `"i"` or `"j"` are not present, defaults to `(0, 0)`.
+///
+/// If parse fails (for exemple if user changes cookies for absurd values), returns
+/// to 0. Also forbids to have values outside of the grid.
+///
+/// # Arguments
+///
+/// * `cookie` - A reference to the `CookieManager` containing the cookies.
+/// * `grid_size` - Number of rows and columns of the grid (see `get_grid_size`).
+///
+/// # Returns
+///
+/// A tuple `(i_coord, j_coord)` representing Rustbot's row and column positions.
+fn get_rustbot_coordinates(cookie: &CookieManager, grid_size: (u32, u32)) -> (u32, u32) {
+    let (grid_size_i, grid_size_j) = grid_size;
+    let mut i_coord = 0;
+    let mut j_coord = 0;
+    if let Some(i_cookie) = cookie.get("i") {
+        match i_cookie.value().parse() {
+            Ok(cookie_i) => {
+                if cookie_i >= grid_size_i {
+                    i_coord = grid_size_i.saturating_sub(1);
+                } else {
+                    i_coord = cookie_i;
+                }
+            }
+            Err(_) => i_coord = 0,
+        }
+    }
+    if let Some(j_cookie) = cookie.get("j") {
+        match j_cookie.value().parse() {
+            Ok(cookie_j) => {
+                if cookie_j >= grid_size_j {
+                    j_coord = grid_size_j.saturating_sub(1);
+                } else {
+                    j_coord = cookie_j;
+                }
+            }
+            Err(_) => j_coord = 0,
+        }
+    }
+    (i_coord, j_coord)
+}
+
+/// Writes a list of cells as a cookie value: `i.j` pairs separated by `-`.
+///
+/// # Arguments
+///
+/// * `cells` - The `(i, j)` cells to write.
+fn encode_cells(cells: impl IntoIterator<Item = (u32, u32)>) -> String {
+    cells
+        .into_iter()
+        .map(|(i, j)| format!("{i}.{j}"))
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+/// Reads a list of cells written by `encode_cells`, skipping invalid ones.
+///
+/// # Arguments
+///
+/// * `value` - The cookie value.
+fn decode_cells(value: &str) -> Vec<(u32, u32)> {
+    value
+        .split('-')
+        .filter_map(|cell| {
+            let (i, j) = cell.split_once('.')?;
+            Some((i.parse().ok()?, j.parse().ok()?))
+        })
+        .collect()
+}
+
+/// Writes the painted cells as a cookie value: `i.j.c` triples separated by `-`,
+/// where `c` is the index of the color (see `Color::index`).
+///
+/// # Arguments
+///
+/// * `world` - The world whose paint to write.
+fn encode_paint(world: &World) -> String {
+    world
+        .painted()
+        .map(|((i, j), color)| format!("{i}.{j}.{}", color.index()))
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+/// Reads painted cells written by `encode_paint`, skipping invalid ones.
+///
+/// # Arguments
+///
+/// * `value` - The cookie value.
+fn decode_paint(value: &str) -> Vec<(u32, u32, Color)> {
+    value
+        .split('-')
+        .filter_map(|cell| {
+            let mut parts = cell.split('.');
+            let i = parts.next()?.parse().ok()?;
+            let j = parts.next()?.parse().ok()?;
+            let color = *Color::ALL.get(parts.next()?.parse::<usize>().ok()?)?;
+            Some((i, j, color))
+        })
+        .collect()
+}
+
+/// Writes the cells holding items as a cookie value: `i.j.n` triples separated
+/// by `-`, where `n` is the number of items.
+///
+/// # Arguments
+///
+/// * `world` - The world whose items to write.
+fn encode_items(world: &World) -> String {
+    world
+        .items()
+        .map(|((i, j), count)| format!("{i}.{j}.{count}"))
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+/// Reads cells holding items written by `encode_items`, skipping invalid ones.
+///
+/// # Arguments
+///
+/// * `value` - The cookie value.
+fn decode_items(value: &str) -> Vec<(u32, u32, u32)> {
+    value
+        .split('-')
+        .filter_map(|cell| {
+            let mut parts = cell.split('.');
+            let i = parts.next()?.parse().ok()?;
+            let j = parts.next()?.parse().ok()?;
+            let count = parts.next()?.parse().ok()?;
+            Some((i, j, count))
+        })
+        .collect()
+}
+
+/// Rebuilds the world (grid size, Rustbot's position and heading, walls,
+/// trail, pen, paint, items and inventory) from cookies.
+///
+/// Falls back to an empty default grid if the cookies describe an impossible
+/// world (for example a grid with 0 lines). Walls outside of the grid or under
+/// the robot, and paint and items outside of the grid or on walls, are ignored.
+/// Numbers of items on a cell and carried by the robot are cut to `max_items`.
+///
+/// # Arguments
+///
+/// * `cookie` - A reference to the `CookieManager` containing the cookies.
+/// * `grid_config` - Default and maximum grid sizes, and maximum number of items.
+fn get_world(cookie: &CookieManager, grid_config: &GridConfig) -> World {
+    let (grid_max_i, grid_max_j) = get_grid_size(cookie, grid_config);
+    let (i, j) = get_rustbot_coordinates(cookie, (grid_max_i, grid_max_j));
+    let mut world =
+        World::with_robot(grid_max_i, grid_max_j, Robot { i, j }).unwrap_or_else(|_| {
+            World::new(grid_config.default_lines, grid_config.default_columns).unwrap()
+        });
+    if let Some(heading_cookie) = cookie.get("heading") {
+        world.set_heading(heading_cookie.value().parse().unwrap_or_default());
+    }
+    if let Some(walls_cookie) = cookie.get("walls") {
+        for (i, j) in decode_cells(walls_cookie.value()) {
+            let _ = world.add_wall(i, j);
+        }
+    }
+    if let Some(trail_cookie) = cookie.get("trail") {
+        let trail = decode_cells(trail_cookie.value());
+        world.set_trail(trail.into_iter().map(|(i, j)| Robot { i, j }));
+    }
+    if let Some(pen_cookie) = cookie.get("pen")
+        && let Some((state, color)) = pen_cookie.value().split_once('.')
+    {
+        world.set_pen(state == "down", color.parse().unwrap_or_default());
+    }
+    if let Some(paint_cookie) = cookie.get("paint") {
+        for (i, j, color) in decode_paint(paint_cookie.value()) {
+            let _ = world.paint(i, j, color);
+        }
+    }
+    if let Some(items_cookie) = cookie.get("items") {
+        for (i, j, count) in decode_items(items_cookie.value()) {
+            let room = grid_config.max_items.saturating_sub(world.items_at(i, j));
+            let _ = world.put_items(i, j, count.min(room));
+        }
+    }
+    if let Some(inventory_cookie) = cookie.get("inventory") {
+        let inventory: u32 = inventory_cookie.value().parse().unwrap_or(0);
+        world.set_inventory(inventory.min(grid_config.max_items));
+    }
+    world
+}
+
+/// Creates a cookie available site-wide, following the session settings.
+///
+/// # Arguments
+///
+/// * `name` - Name of the cookie.
+/// * `value` - Value of the cookie.
+/// * `session_config` - Lifetime and `Secure` flag to apply.
+pub fn new_cookie(
+    name: &'static str,
+    value: String,
+    session_config: &SessionConfig,
+) -> Cookie<'static> {
+    let mut cookie = Cookie::new(name, value);
+    // Need set_path("/") to avoid duplicating the cookies for different URLs
+    cookie.set_path("/");
+    if let Some(max_age) = session_config.max_age {
+        cookie.set_max_age(Duration::from_secs(max_age));
+    }
+    if session_config.secure {
+        cookie.set_secure(true);
+    }
+    cookie
+}
+
+/// Updates Rustbot's coordinates, heading, grid size, walls, trail, pen,
+/// paint, items and inventory cookies.
+///
+/// Sets the cookies `"i"`, `"j"`, `"heading"`, `"max-i"`, `"max-j"`,
+/// `"walls"`, `"trail"`, `"pen"` (`down.color` or `up.color`), `"paint"`,
+/// `"items"` and `"inventory"` to the values of the world, and ensures their
+/// path is `/` to make them available site-wide.
+///
+/// # Arguments
+///
+/// * `world` - The world to store.
+/// * `session_config` - Lifetime and `Secure` flag of the cookies.
+/// * `cookie` - A mutable reference to the `CookieManager` used to store the cookies.
+fn update_cookie(world: &World, session_config: &SessionConfig, cookie: &mut CookieManager) {
+    let robot = world.robot();
+    cookie.add(new_cookie("i", format!("{}", robot.i), session_config));
+    cookie.add(new_cookie("j", format!("{}", robot.j), session_config));
+    cookie.add(new_cookie(
+        "heading",
+        world.heading().to_string(),
+        session_config,
+    ));
+    cookie.add(new_cookie(
+        "max-i",
+        format!("{}", world.lines()),
+        session_config,
+    ));
+    cookie.add(new_cookie(
+        "max-j",
+        format!("{}", world.columns()),
+        session_config,
+    ));
+    cookie.add(new_cookie(
+        "walls",
+        encode_cells(world.walls()),
+        session_config,
+    ));
+    cookie.add(new_cookie(
+        "trail",
+        encode_cells(world.trail().iter().map(|cell| (cell.i, cell.j))),
+        session_config,
+    ));
+    let pen = if world.is_pen_down() { "down" } else { "up" };
+    cookie.add(new_cookie(
+        "pen",
+        format!("{pen}.{}", world.pen_color()),
+        session_config,
+    ));
+    cookie.add(new_cookie("paint", encode_paint(world), session_config));
+    cookie.add(new_cookie("items", encode_items(world), session_config));
+    cookie.add(new_cookie(
+        "inventory",
+        world.inventory().to_string(),
+        session_config,
+    ));
+}
+
+/// Reads the dialect of code mode from its cookie (the default one if the
+/// cookie is missing or unknown).
+///
+/// # Arguments
+///
+/// * `cookie` - A reference to the `CookieManager` containing the cookies.
+fn get_dialect(cookie: &CookieManager) -> Dialect {
+    cookie
+        .get("dialect")
+        .and_then(|dialect| dialect.value().parse().ok())
+        .unwrap_or_default()
+}
+
+/// Stores the dialect of code mode in the `"dialect"` cookie, to select it
+/// again on the next visit.
+///
+/// # Arguments
+///
+/// * `dialect` - The dialect to store.
+/// * `session_config` - Lifetime and `Secure` flag of the cookie.
+/// * `cookie` - A mutable reference to the `CookieManager` used to store the cookie.
+fn update_dialect(dialect: Dialect, session_config: &SessionConfig, cookie: &mut CookieManager) {
+    cookie.add(new_cookie("dialect", dialect.to_string(), session_config));
+}
+
+/// Renders a template into an HTML response, recording the time spent.
+///
+/// # Arguments
+///
+/// * `template` - The template context to render.
+///
+/// # Returns
+///
+/// An `Html<String>` response containing the rendered template.
+///
+/// # Panics
+///
+/// Will panic if rendering the template fails (`unwrap()` on `template.render()`).
+fn render<T: Template>(template: &T) -> Html<String> {
+    let start = Instant::now();
+    let html = template.render().unwrap();
+    metrics::RENDER_DURATION.observe(start.elapsed().as_secs_f64());
+    Html(html)
+}
+
+/// Whether the client prefers plain text to HTML, from its `Accept` header.
+///
+/// Compares the quality of `text/plain` with the one of `text/html` (or of a
+/// wildcard, since HTML is the default): `curl -H 'Accept: text/plain'` gets
+/// text, browsers get HTML.
+///
+/// # Arguments
+///
+/// * `headers` - Request headers.
+fn wants_text(headers: &HeaderMap) -> bool {
+    let Some(accept) = headers
+        .get(header::ACCEPT)
+        .and_then(|accept| accept.to_str().ok())
+    else {
+        return false;
+    };
+    let (mut text, mut html) = (0.0, 0.0);
+    for media_range in accept.split(',') {
+        let mut parameters = media_range.split(';');
+        let media_type = parameters.next().unwrap_or("").trim().to_ascii_lowercase();
+        let quality = parameters
+            .find_map(|parameter| parameter.trim().strip_prefix("q="))
+            .and_then(|quality| quality.parse().ok())
+            .unwrap_or(1.0);
+        match media_type.as_str() {
+            "text/plain" => text = f32::max(text, quality),
+            "text/html" | "text/*" | "*/*" => html = f32::max(html, quality),
+            _ => {}
+        }
+    }
+    text > html
+}
+
+/// Renders a page, or the grid as ASCII art if the client asked for plain text.
+///
+/// The ASCII grid is the one of `rustbot_core::render::ascii`: `R` for the
+/// robot, `#` for walls, the number of items (`+` for 10 or more), `*` for
+/// painted cells and `.` for empty cells, one line of text per line of the
+/// grid.
+///
+/// # Arguments
+///
+/// * `headers` - Request headers, checked by `wants_text`.
+/// * `world` - The world to show as text.
+/// * `template` - The page to render otherwise.
+fn render_page<T: Template>(headers: &HeaderMap, world: &World, template: &T) -> Response {
+    // Same URL, different bodies: caches must look at `Accept`
+    let vary = [(header::VARY, "Accept")];
+    if wants_text(headers) {
+        let start = Instant::now();
+        let text = core_render::ascii(world);
+        metrics::RENDER_DURATION.observe(start.elapsed().as_secs_f64());
+        (
+            vary,
+            [(header::CONTENT_TYPE, "text/plain; charset=utf-8")],
+            text,
+        )
+            .into_response()
+    } else {
+        (vary, render(template)).into_response()
+    }
+}
+
+/// Applies a play mode command to the world stored in cookies.
+///
+/// Retrieves the world from cookies, applies the command, updates the cookies
+/// and renders the play mode template.
+///
+/// # Arguments
+///
+/// * `config` - Server configuration.
+/// * `headers` - Request headers, to answer in plain text if asked (see `render_page`).
+/// * `cookie` - The `CookieManager` used to read and update cookies.
+/// * `command` - The command to apply.
+///
+/// # Returns
+///
+/// The rendered play mode page (or the ASCII grid, see `render_page`), or
+/// `400 Bad Request` if the command cannot be applied (for example a teleport
+/// outside of the grid).
+fn play_command(
+    config: &Config,
+    headers: &HeaderMap,
+    cookie: &mut CookieManager,
+    command: Command,
+) -> Response {
+    let mut world = get_world(cookie, &config.grid);
+    if let Err(e) = world.apply(command) {
+        return (StatusCode::BAD_REQUEST, e.to_string()).into_response();
+    }
+    metrics::record_command(&command);
+
+    // Add cookies
+    update_cookie(&world, &config.session, cookie);
+
+    // Create html (or text) response
+    render_page(headers, &world, &PlayTemplate::new(&world, &config.grid))
+}
+
+/// Handler for the root path `/`.
+///
+/// # Returns
+///
+/// An `Html<String>` response containing the rendered menu, implementing `IntoResponse`.
+async fn root() -> impl IntoResponse {
+    // Create html response
+    let html = RootTemplate {};
+    render(&html)
+}
+
+/// Handler for the play path `/play`.
+///
+/// Retrieves Rustbot's coordinates and the grid size from cookies, updates them if necessary,
+/// and renders the play mode template.
+///
+/// # Arguments
+///
+/// * `config` - Server configuration.
+/// * `headers` - Request headers, to answer in plain text if asked (see `render_page`).
+/// * `cookie` - The `CookieManager` provided by Axum, used to read and update cookies.
+///
+/// # Returns
+///
+/// The rendered template, or the ASCII grid if the client asked for plain text (see `render_page`).
+async fn play(
+    State(config): State<Arc<Config>>,
+    headers: HeaderMap,
+    mut cookie: CookieManager,
+) -> Response {
+    // Retrieve cookies if already existing
+    let world = get_world(&cookie, &config.grid);
+
+    // Add cookies
+    update_cookie(&world, &config.session, &mut cookie);
+
+    // Create html (or text) response
+    render_page(&headers, &world, &PlayTemplate::new(&world, &config.grid))
+}
+
+/// Handler for resetting Rustbot's coordinates to `(0, 0)`.
+///
+/// # Arguments
+///
+/// * `config` - Server configuration.
+/// * `headers` - Request headers, to answer in plain text if asked (see `render_page`).
+/// * `cookie` - The `CookieManager` provided by Axum, used to read and update cookies.
+///
+/// # Returns
+///
+/// The rendered play mode page (see `play_command`).
+async fn reset(
+    State(config): State<Arc<Config>>,
+    headers: HeaderMap,
+    mut cookie: CookieManager,
+) -> Response {
+    play_command(&config, &headers, &mut cookie, Command::Reset)
+}
+
+/// Handler to move Rustbot **down** by one row in the grid, wrapping around
+/// to 0 if it reaches the maximum.
+///
+/// # Arguments
+///
+/// * `config` - Server configuration.
+/// * `headers` - Request headers, to answer in plain text if asked (see `render_page`).
+/// * `cookie` - The `CookieManager` provided by Axum, used to read and update cookies.
+///
+/// # Returns
+///
+/// The rendered play mode page (see `play_command`).
+async fn down(
+    State(config): State<Arc<Config>>,
+    headers: HeaderMap,
+    mut cookie: CookieManager,
+) -> Response {
+    play_command(&config, &headers, &mut cookie, Command::Down)
+}
+
+/// Handler to move Rustbot **up** by one row in the grid, wrapping around to
+/// the maximum if it reaches 0.
+///
+/// # Arguments
+///
+/// * `config` - Server configuration.
+/// * `headers` - Request headers, to answer in plain text if asked (see `render_page`).
+/// * `cookie` - The `CookieManager` provided by Axum, used to read and update cookies.
+///
+/// # Returns
+///
+/// The rendered play mode page (see `play_command`).
+async fn up(
+    State(config): State<Arc<Config>>,
+    headers: HeaderMap,
+    mut cookie: CookieManager,
+) -> Response {
+    play_command(&config, &headers, &mut cookie, Command::Up)
+}
+
+/// Handler to move Rustbot **right** by one column in the grid, wrapping
+/// around to 0 if it reaches the maximum.
+///
+/// # Arguments
+///
+/// * `config` - Server configuration.
+/// * `headers` - Request headers, to answer in plain text if asked (see `render_page`).
+/// * `cookie` - The `CookieManager` provided by Axum, used to read and update cookies.
+///
+/// # Returns
+///
+/// The rendered play mode page (see `play_command`).
+async fn right(
+    State(config): State<Arc<Config>>,
+    headers: HeaderMap,
+    mut cookie: CookieManager,
+) -> Response {
+    play_command(&config, &headers, &mut cookie, Command::Right)
+}
+
+/// Handler to move Rustbot **left** by one column in the grid, wrapping
+/// around to the maximum if it reaches 0.
+///
+/// # Arguments
+///
+/// * `config` - Server configuration.
+/// * `headers` - Request headers, to answer in plain text if asked (see `render_page`).
+/// * `cookie` - The `CookieManager` provided by Axum, used to read and update cookies.
+///
+/// # Returns
+///
+/// The rendered play mode page (see `play_command`).
+async fn left(
+    State(config): State<Arc<Config>>,
+    headers: HeaderMap,
+    mut cookie: CookieManager,
+) -> Response {
+    play_command(&config, &headers, &mut cookie, Command::Left)
+}
+
+/// Handler to teleport Rustbot to specific coordinates `(i, j)` in the grid.
+///
+/// # Arguments
+///
+/// * `config` - Server configuration.
+/// * `headers` - Request headers, to answer in plain text if asked (see `render_page`).
+/// * `cookie` - The `CookieManager` provided by Axum, used to read and update cookies.
+/// * `Path((i_teleport, j_teleport))` - The target coordinates provided in the URL path.
+///
+/// # Returns
+///
+/// The rendered play mode page, or `400 Bad Request` if the target is outside
+/// of the grid (see `play_command`).
+async fn teleport(
+    State(config): State<Arc<Config>>,
+    headers: HeaderMap,
+    mut cookie: CookieManager,
+    Path((i_teleport, j_teleport)): Path<(u32, u32)>,
+) -> Response {
+    play_command(
+        &config,
+        &headers,
+        &mut cookie,
+        Command::GoTo(i_teleport, j_teleport),
+    )
+}
+
+/// Handler to put the pen down (painting the current cell) if it is up, or
+/// to lift it if it is down.
+///
+/// # Arguments
+///
+/// * `config` - Server configuration.
+/// * `headers` - Request headers, to answer in plain text if asked (see `render_page`).
+/// * `cookie` - The `CookieManager` provided by Axum, used to read and update cookies.
+///
+/// # Returns
+///
+/// The rendered play mode page (see `play_command`).
+async fn pen(
+    State(config): State<Arc<Config>>,
+    headers: HeaderMap,
+    mut cookie: CookieManager,
+) -> Response {
+    let command = if get_world(&cookie, &config.grid).is_pen_down() {
+        Command::PenUp
+    } else {
+        Command::PenDown
+    };
+    play_command(&config, &headers, &mut cookie, command)
+}
+
+/// Handler to change the color of the pen.
+///
+/// # Arguments
+///
+/// * `config` - Server configuration.
+/// * `headers` - Request headers, to answer in plain text if asked (see `render_page`).
+/// * `cookie` - The `CookieManager` provided by Axum, used to read and update cookies.
+/// * `Form(pen_color)` - The submitted form data containing the color name.
+///
+/// # Returns
+///
+/// The rendered play mode page (see `play_command`), or `400 Bad Request`
+/// for an unknown color.
+async fn pen_color(
+    State(config): State<Arc<Config>>,
+    headers: HeaderMap,
+    mut cookie: CookieManager,
+    Form(pen_color): Form<PenColor>,
+) -> Response {
+    match pen_color.color.parse::<Color>() {
+        Ok(color) => play_command(&config, &headers, &mut cookie, Command::PenColor(color)),
+        Err(e) => (StatusCode::BAD_REQUEST, e).into_response(),
+    }
+}
+
+/// Handler to change the grid size.
+///
+/// Receives new grid dimensions from a submitted form (`MaxGridSizes`),
+/// resets Rustbot's coordinates to `(0, 0)`, updates the cookies with
+/// the new grid size, and renders the play mode template. Sizes bigger than
+/// the configured maximum are set back to the maximum.
+///
+/// # Arguments
+///
+/// * `config` - Server configuration.
+/// * `headers` - Request headers, to answer in plain text if asked (see `render_page`).
+/// * `cookie` - The `CookieManager` provided by Axum, used to read and update cookies.
+/// * `Form(max_grid_sizes)` - The submitted form data containing the new grid dimensions.
+///
+/// # Returns
+///
+/// The rendered play mode page with Rustbot reset at `(0, 0)` and the updated
+/// grid size, or `400 Bad Request` for an empty grid.
+async fn change_max(
+    State(config): State<Arc<Config>>,
+    headers: HeaderMap,
+    mut cookie: CookieManager,
+    Form(max_grid_sizes): Form<MaxGridSizes>,
+) -> Response {
+    let grid_max_i = max_grid_sizes.change_max_i.min(config.grid.max_lines);
+    let grid_max_j = max_grid_sizes.change_max_j.min(config.grid.max_columns);
+    let world = match World::new(grid_max_i, grid_max_j) {
+        Ok(world) => world,
+        Err(e) => return (StatusCode::BAD_REQUEST, e.to_string()).into_response(),
+    };
+
+    // Add cookies
+    update_cookie(&world, &config.session, &mut cookie);
+
+    // Create html (or text) response
+    render_page(&headers, &world, &PlayTemplate::new(&world, &config.grid))
+}
+
+/// Handler for the code mode page `/code`.
+///
+/// # Arguments
+///
+/// * `config` - Server configuration.
+/// * `storage` - Programs database, for the draft and the saved programs.
+/// * `session` - Session id of the browser.
+/// * `headers` - Request headers, to answer in plain text if asked (see `render_page`).
+/// * `cookie` - The `CookieManager` provided by Axum, used to read and update cookies.
+///
+/// # Returns
+///
+/// The rendered template, or the ASCII grid if the client asked for plain text (see `render_page`).
+async fn code(
+    State(config): State<Arc<Config>>,
+    Extension(storage): Extension<Arc<Storage>>,
+    Extension(session): Extension<SessionId>,
+    headers: HeaderMap,
+    mut cookie: CookieManager,
+) -> Response {
+    // Retrieve cookies if already existing
+    let world = get_world(&cookie, &config.grid);
+
+    // Add cookies
+    update_cookie(&world, &config.session, &mut cookie);
+
+    // Create html (or text) response
+    let code = or_default(storage.draft(&session.0).await);
+    let programs = or_default(storage.programs(&session.0).await);
+    render_page(
+        &headers,
+        &world,
+        &CodeTemplate::new(&world, None, code, programs, get_dialect(&cookie)),
+    )
+}
+
+/// Handler running a program sent from code mode.
+///
+/// The program, written in the chosen dialect (remembered for the next
+/// visit), is run by `rustbot_core` on the world stored in cookies. It is
+/// all or nothing: if any line fails, the world is left unchanged and the error
+/// is shown on the page. The program is kept as the draft of the session, to
+/// fill the textarea on the next visit.
+///
+/// Before running, the program is checked by `rustbot_core::lint`: its
+/// warnings (unreachable code, moves that cancel out…) are shown with the
+/// page and the diagnostics panel, but never prevent it from running.
+///
+/// With `?format=png`, the response is a PNG image of the grid after the
+/// program, and with `?format=gif` an animated GIF of the run (the starting
+/// grid, then one frame per executed command). With `?format=fragment`, it is
+/// only the diagnostics panel, the script then fetching the grid from
+/// `/fragment/grid`.
+///
+/// # Arguments
+///
+/// * `config` - Server configuration.
+/// * `storage` - Programs database, where the draft is saved.
+/// * `session` - Session id of the browser.
+/// * `headers` - Request headers, to answer in plain text if asked (see `render_page`).
+/// * `cookie` - The `CookieManager` provided by Axum, used to read and update cookies.
+/// * `Query(query)` - The requested response format.
+/// * `Form(user_code)` - The submitted program.
+///
+/// # Returns
+///
+/// The rendered code mode page (or image), with `422 Unprocessable Entity` if
+/// the program failed, `413 Payload Too Large` if it is longer than
+/// `max_program_len`, or `400 Bad Request` for an unknown dialect. Images of
+/// failed programs are replaced by the error text.
+async fn user_code(
+    State(config): State<Arc<Config>>,
+    Extension(storage): Extension<Arc<Storage>>,
+    Extension(session): Extension<SessionId>,
+    headers: HeaderMap,
+    cookie: CookieManager,
+    Query(query): Query<FormatQuery>,
+    Form(user_code): Form<UserCode>,
+) -> Response {
+    let dialect = match user_code.dialect() {
+        Ok(dialect) => dialect,
+        Err(e) => return (StatusCode::BAD_REQUEST, e).into_response(),
+    };
+    let program = Submission {
+        user_code: user_code.user_code,
+        dialect,
+    };
+    run_submission(
+        &config, &storage, &session, &headers, cookie, query, program,
+    )
+    .await
+}
+
+/// Handler running a program sent as a JSON tree of blocks (see `ast.rs`).
+///
+/// The tree is turned into code mode text, then run exactly like a program
+/// sent to `/user-code`: the text becomes the draft of the session, and the
+/// response formats are the same.
+///
+/// # Arguments
+///
+/// * `config` - Server configuration.
+/// * `storage` - Programs database, where the draft is saved.
+/// * `session` - Session id of the browser.
+/// * `headers` - Request headers, to answer in plain text if asked (see `render_page`).
+/// * `cookie` - The `CookieManager` provided by Axum, used to read and update cookies.
+/// * `Query(query)` - The requested response format.
+/// * `Json(tree)` - The submitted program.
+///
+/// # Returns
+///
+/// The same responses as `/user-code`, or `422 Unprocessable Entity` if the
+/// tree doesn't follow the schema.
+async fn user_code_ast(
+    State(config): State<Arc<Config>>,
+    Extension(storage): Extension<Arc<Storage>>,
+    Extension(session): Extension<SessionId>,
+    headers: HeaderMap,
+    cookie: CookieManager,
+    Query(query): Query<FormatQuery>,
+    Json(tree): Json<AstProgram>,
+) -> Response {
+    let user_code = match tree.to_text() {
+        Ok(text) => text,
+        Err(e) => return (StatusCode::UNPROCESSABLE_ENTITY, e).into_response(),
+    };
+    let program = Submission {
+        user_code,
+        dialect: Dialect::RustBot,
+    };
+    run_submission(
+        &config, &storage, &session, &headers, cookie, query, program,
+    )
+    .await
+}
+
+/// A program to run, with the dialect it is written in.
+struct Submission {
+    user_code: String,
+    dialect: Dialect,
+}
+
+/// Runs a submitted program on the world stored in cookies (see `user_code`).
+async fn run_submission(
+    config: &Config,
+    storage: &Storage,
+    session: &SessionId,
+    headers: &HeaderMap,
+    mut cookie: CookieManager,
+    query: FormatQuery,
+    submission: Submission,
+) -> Response {
+    let Submission { user_code, dialect } = submission;
+    let program_len = user_code.len();
+
+    // Enforce interpreter limits
+    if let Some(response) = check_program_len(config, &user_code) {
+        tracing::warn!(program_len, outcome = "too_long", "program rejected");
+        metrics::PROGRAMS.with_label_values(&["too_long"]).inc();
+        return response;
+    }
+
+    // Keep the program for the next visit, even if it fails
+    or_default(storage.save_draft(&session.0, &user_code).await);
+    update_dialect(dialect, &config.session, &mut cookie);
+
+    // Retrieve cookies if already existing
+    let mut world = get_world(&cookie, &config.grid);
+
+    // Run on a copy, to keep the world unchanged if the program fails
+    let mut new_world = world.clone();
+    let mut frames = vec![world.clone()];
+    // Warnings don't stop the program: they are shown next to its result
+    let mut warnings = Vec::new();
+    let result = dialect.parse(&user_code).and_then(|program| {
+        warnings = rustbot_core::lint(&program, &world);
+        rustbot_core::run_observed(&program, &mut new_world, &limits(config), |_, world| {
+            if query.format == Format::Gif && frames.len() <= raster::MAX_GIF_FRAMES {
+                frames.push(world.clone());
+            }
+        })
+    });
+    let (status, error) = match result {
+        Ok(run) => {
+            tracing::info!(
+                program_len,
+                steps = run.steps(),
+                warnings = warnings.len(),
+                outcome = "ok",
+                "program executed"
+            );
+            metrics::PROGRAMS.with_label_values(&["ok"]).inc();
+            metrics::PROGRAM_STEPS.observe(run.steps() as f64);
+            for step in &run.trace {
+                metrics::record_command(&step.command);
+            }
+            world = new_world;
+            (StatusCode::OK, None)
+        }
+        Err(e) => {
+            tracing::warn!(
+                program_len,
+                warnings = warnings.len(),
+                outcome = e.name(),
+                error = %e,
+                "program failed"
+            );
+            metrics::PROGRAMS.with_label_values(&[e.name()]).inc();
+            (StatusCode::UNPROCESSABLE_ENTITY, Some(e.to_string()))
+        }
+    };
+
+    for warning in &warnings {
+        metrics::LINT_WARNINGS
+            .with_label_values(&[warning.name()])
+            .inc();
+    }
+    let warnings: Vec<String> = warnings.iter().map(ToString::to_string).collect();
+
+    // Add cookies
+    update_cookie(&world, &config.session, &mut cookie);
+
+    // Create response in the requested format
+    match (query.format, error) {
+        (Format::Html, Some(error)) if wants_text(headers) => (status, error).into_response(),
+        (Format::Html, error) => {
+            let programs = or_default(storage.programs(&session.0).await);
+            let template = CodeTemplate {
+                warnings,
+                ..CodeTemplate::new(&world, error, user_code, programs, dialect)
+            };
+            (status, render_page(headers, &world, &template)).into_response()
+        }
+        (Format::Fragment, error) => {
+            (status, render(&DiagnosticsFragment { error, warnings })).into_response()
+        }
+        (_, Some(error)) => (status, error).into_response(),
+        (Format::Png, None) => image_response("image/png", || raster::png(&world)),
+        (Format::Gif, None) if frames.len() > raster::MAX_GIF_FRAMES => (
+            StatusCode::UNPROCESSABLE_ENTITY,
+            format!(
+                "Too many steps for an animation (max {})",
+                raster::MAX_GIF_FRAMES - 1
+            ),
+        )
+            .into_response(),
+        (Format::Gif, None) => {
+            // Encoding hundreds of frames takes a while: keep it off the async workers
+            let start = Instant::now();
+            let gif = tokio::task::spawn_blocking(move || raster::gif(&frames))
+                .await
+                .unwrap();
+            metrics::RENDER_DURATION.observe(start.elapsed().as_secs_f64());
+            ([(header::CONTENT_TYPE, "image/gif")], gif).into_response()
+        }
+    }
+}
+
+/// Builds an image response, recording the time spent drawing it.
+///
+/// # Arguments
+///
+/// * `content_type` - MIME type of the image.
+/// * `draw` - Function drawing and encoding the image.
+fn image_response(content_type: &'static str, draw: impl FnOnce() -> Vec<u8>) -> Response {
+    let start = Instant::now();
+    let image = draw();
+    metrics::RENDER_DURATION.observe(start.elapsed().as_secs_f64());
+    ([(header::CONTENT_TYPE, content_type)], image).into_response()
+}
+
+/// Handler for `/render.svg`: an SVG image of the grid stored in cookies.
+///
+/// The image can be embedded anywhere (slides, reports…): it doesn't depend on
+/// the site's CSS or images.
+///
+/// # Arguments
+///
+/// * `config` - Server configuration.
+/// * `cookie` - The `CookieManager` provided by Axum, used to read cookies.
+///
+/// # Returns
+///
+/// The SVG image, with the `image/svg+xml` content type.
+async fn render_svg(State(config): State<Arc<Config>>, cookie: CookieManager) -> Response {
+    let world = get_world(&cookie, &config.grid);
+    image_response("image/svg+xml", || core_render::svg(&world).into_bytes())
+}
+
+/// Handler for `/render.png`: a PNG image of the grid stored in cookies.
+///
+/// # Arguments
+///
+/// * `config` - Server configuration.
+/// * `cookie` - The `CookieManager` provided by Axum, used to read cookies.
+///
+/// # Returns
+///
+/// The PNG image, with the `image/png` content type.
+async fn render_png(State(config): State<Arc<Config>>, cookie: CookieManager) -> Response {
+    let world = get_world(&cookie, &config.grid);
+    image_response("image/png", || raster::png(&world))
+}
+
+/// Builds a canvas image response, after checking its `scale`.
+///
+/// # Arguments
+///
+/// * `scale` - Side of a cell, in pixels.
+/// * `content_type` - MIME type of the image.
+/// * `draw` - Function drawing and encoding the image.
+///
+/// # Returns
+///
+/// The image (see `image_response`), or `400 Bad Request` if `scale` is not
+/// between 1 and `MAX_CANVAS_SCALE`.
+fn canvas_response(
+    scale: u32,
+    content_type: &'static str,
+    draw: impl FnOnce() -> Vec<u8>,
+) -> Response {
+    if scale == 0 || scale > MAX_CANVAS_SCALE {
+        return (
+            StatusCode::BAD_REQUEST,
+            format!("scale must be between 1 and {MAX_CANVAS_SCALE}"),
+        )
+            .into_response();
+    }
+    image_response(content_type, draw)
+}
+
+/// Handler for `/canvas.svg`: an SVG image of the cells painted with the pen,
+/// one square per cell (`?scale=` pixels wide, 1 by default), without the grid.
+///
+/// # Arguments
+///
+/// * `config` - Server configuration.
+/// * `cookie` - The `CookieManager` provided by Axum, used to read cookies.
+/// * `Query(query)` - Side of a cell, in pixels.
+///
+/// # Returns
+///
+/// The SVG image, with the `image/svg+xml` content type, or `400 Bad Request`
+/// for an invalid scale.
+async fn canvas_svg(
+    State(config): State<Arc<Config>>,
+    cookie: CookieManager,
+    Query(query): Query<CanvasQuery>,
+) -> Response {
+    let world = get_world(&cookie, &config.grid);
+    canvas_response(query.scale, "image/svg+xml", || {
+        core_render::canvas_svg(&world, query.scale).into_bytes()
+    })
+}
+
+/// Handler for `/canvas.png`: a PNG image of the cells painted with the pen,
+/// one square per cell (`?scale=` pixels wide, 1 by default), without the grid.
+///
+/// # Arguments
+///
+/// * `config` - Server configuration.
+/// * `cookie` - The `CookieManager` provided by Axum, used to read cookies.
+/// * `Query(query)` - Side of a cell, in pixels.
+///
+/// # Returns
+///
+/// The PNG image, with the `image/png` content type, or `400 Bad Request`
+/// for an invalid scale.
+async fn canvas_png(
+    State(config): State<Arc<Config>>,
+    cookie: CookieManager,
+    Query(query): Query<CanvasQuery>,
+) -> Response {
+    let world = get_world(&cookie, &config.grid);
+    canvas_response(query.scale, "image/png", || {
+        raster::canvas_png(&world, query.scale)
+    })
+}
+
+/// Handler for `/fragment/grid`: the grid stored in cookies, without the page around it.
+///
+/// # Arguments
+///
+/// * `config` - Server configuration.
+/// * `cookie` - The `CookieManager` provided by Axum, used to read cookies.
+/// * `Query(query)` - Whether to show line and column numbers (`?headers=true`).
+///
+/// # Returns
+///
+/// The rendered `partials/grid.html` fragment.
+async fn fragment_grid(
+    State(config): State<Arc<Config>>,
+    cookie: CookieManager,
+    Query(query): Query<GridQuery>,
+) -> impl IntoResponse {
+    let world = get_world(&cookie, &config.grid);
+    render(&GridFragment {
+        rustbot_i: world.robot().i,
+        rustbot_j: world.robot().j,
+        grid_max_i: world.lines(),
+        grid_max_j: world.columns(),
+        world,
+        headers: query.headers,
+    })
+}
+
+/// Handler for `/fragment/coords`: the coordinates panel of play mode.
+///
+/// # Arguments
+///
+/// * `config` - Server configuration.
+/// * `cookie` - The `CookieManager` provided by Axum, used to read cookies.
+///
+/// # Returns
+///
+/// The rendered `partials/coords.html` fragment.
+async fn fragment_coords(
+    State(config): State<Arc<Config>>,
+    cookie: CookieManager,
+) -> impl IntoResponse {
+    let world = get_world(&cookie, &config.grid);
+    render(&CoordsFragment {
+        rustbot_i: world.robot().i,
+        rustbot_j: world.robot().j,
+    })
+}
+
+/// Handler for `/fragment/programs`: the list of saved programs of code mode.
+///
+/// # Arguments
+///
+/// * `storage` - Programs database.
+/// * `session` - Session id of the browser.
+///
+/// # Returns
+///
+/// The rendered `partials/programs.html` fragment.
+async fn fragment_programs(
+    Extension(storage): Extension<Arc<Storage>>,
+    Extension(session): Extension<SessionId>,
+) -> Response {
+    match storage.programs(&session.0).await {
+        Ok(programs) => render(&ProgramsFragment { programs }).into_response(),
+        Err(e) => storage_error(e),
+    }
+}
+
+/// Returns the value of a storage operation, or a default value if it failed.
+///
+/// Used where stored programs are a convenience (the draft, the list of the
+/// code mode page): the error is logged and the page works without them.
+fn or_default<T: Default>(result: Result<T, StorageError>) -> T {
+    result.unwrap_or_else(|e| {
+        tracing::error!(error = %e, "storage error");
+        T::default()
+    })
+}
+
+/// Builds the response to a failed storage operation.
+///
+/// # Arguments
+///
+/// * `error` - The storage error.
+///
+/// # Returns
+///
+/// The error text, with `400 Bad Request` for an invalid name, `404 Not Found`
+/// for a missing program, `409 Conflict` for a name already used or too many
+/// programs, and `500 Internal Server Error` if the database failed.
+fn storage_error(error: StorageError) -> Response {
+    let status = match error {
+        StorageError::InvalidName => StatusCode::BAD_REQUEST,
+        StorageError::NotFound(_) => StatusCode::NOT_FOUND,
+        StorageError::AlreadyExists(_) | StorageError::TooMany => StatusCode::CONFLICT,
+        StorageError::Database(_) => {
+            tracing::error!(error = %error, "storage error");
+            StatusCode::INTERNAL_SERVER_ERROR
+        }
+    };
+    (status, error.to_string()).into_response()
+}
+
+/// Handler for `GET /programs`: names of the saved programs of the session.
+///
+/// # Arguments
+///
+/// * `storage` - Programs database.
+/// * `session` - Session id of the browser.
+///
+/// # Returns
+///
+/// A JSON array of names, in alphabetical order.
+async fn list_programs(
+    Extension(storage): Extension<Arc<Storage>>,
+    Extension(session): Extension<SessionId>,
+) -> Response {
+    match storage.programs(&session.0).await {
+        Ok(programs) => Json(programs).into_response(),
+        Err(e) => storage_error(e),
+    }
+}
+
+/// Handler for `POST /programs`: saves a program under a name, replacing the
+/// program with the same name if any.
+///
+/// # Arguments
+///
+/// * `config` - Server configuration, for the maximum program size.
+/// * `storage` - Programs database.
+/// * `session` - Session id of the browser.
+/// * `Form(program)` - Name and code of the program.
+///
+/// # Returns
+///
+/// The name the program was saved under, with `201 Created`, `413 Payload Too Large`
+/// if it is longer than `max_program_len`, or an error (see `storage_error`).
+async fn save_program(
+    State(config): State<Arc<Config>>,
+    Extension(storage): Extension<Arc<Storage>>,
+    Extension(session): Extension<SessionId>,
+    Form(program): Form<SavedProgram>,
+) -> Response {
+    if let Some(response) = check_program_len(&config, &program.user_code) {
+        return response;
+    }
+    match storage
+        .save_program(&session.0, &program.name, &program.user_code)
+        .await
+    {
+        Ok(name) => {
+            tracing::info!(name, "program saved");
+            (StatusCode::CREATED, name).into_response()
+        }
+        Err(e) => storage_error(e),
+    }
+}
+
+/// Handler for `GET /programs/{name}`: code of a saved program.
+///
+/// # Arguments
+///
+/// * `storage` - Programs database.
+/// * `session` - Session id of the browser.
+/// * `Path(name)` - Name of the program.
+///
+/// # Returns
+///
+/// The program as plain text, or `404 Not Found`.
+async fn load_program(
+    Extension(storage): Extension<Arc<Storage>>,
+    Extension(session): Extension<SessionId>,
+    Path(name): Path<String>,
+) -> Response {
+    match storage.program(&session.0, &name).await {
+        Ok(code) => code.into_response(),
+        Err(e) => storage_error(e),
+    }
+}
+
+/// Handler for `POST /programs/{name}/rename`.
+///
+/// # Arguments
+///
+/// * `storage` - Programs database.
+/// * `session` - Session id of the browser.
+/// * `Path(name)` - Current name of the program.
+/// * `Form(new_name)` - New name of the program.
+///
+/// # Returns
+///
+/// The new name, or an error (see `storage_error`).
+async fn rename_program(
+    Extension(storage): Extension<Arc<Storage>>,
+    Extension(session): Extension<SessionId>,
+    Path(name): Path<String>,
+    Form(new_name): Form<NewName>,
+) -> Response {
+    match storage
+        .rename_program(&session.0, &name, &new_name.new_name)
+        .await
+    {
+        Ok(new_name) => {
+            tracing::info!(name, new_name, "program renamed");
+            new_name.into_response()
+        }
+        Err(e) => storage_error(e),
+    }
+}
+
+/// Handler for `DELETE /programs/{name}`.
+///
+/// # Arguments
+///
+/// * `storage` - Programs database.
+/// * `session` - Session id of the browser.
+/// * `Path(name)` - Name of the program.
+///
+/// # Returns
+///
+/// `204 No Content`, or `404 Not Found`.
+async fn delete_program(
+    Extension(storage): Extension<Arc<Storage>>,
+    Extension(session): Extension<SessionId>,
+    Path(name): Path<String>,
+) -> Response {
+    match storage.delete_program(&session.0, &name).await {
+        Ok(()) => {
+            tracing::info!(name, "program deleted");
+            StatusCode::NO_CONTENT.into_response()
+        }
+        Err(e) => storage_error(e),
+    }
+}
+
+/// Handler for `POST /share`: builds a permalink to the current grid (size,
+/// robot position and heading, walls and items) and a program.
+///
+/// # Arguments
+///
+/// * `config` - Server configuration.
+/// * `cookie` - The `CookieManager` provided by Axum, used to read cookies.
+/// * `Form(user_code)` - The program to share.
+///
+/// # Returns
+///
+/// The path of the permalink (`/s/...`), `413 Payload Too Large` if the
+/// program is longer than `max_program_len`, or `400 Bad Request` for an
+/// unknown dialect.
+async fn share(
+    State(config): State<Arc<Config>>,
+    cookie: CookieManager,
+    Form(user_code): Form<UserCode>,
+) -> Response {
+    let dialect = match user_code.dialect() {
+        Ok(dialect) => dialect,
+        Err(e) => return (StatusCode::BAD_REQUEST, e).into_response(),
+    };
+    if let Some(response) = check_program_len(&config, &user_code.user_code) {
+        return response;
+    }
+    let permalink = Permalink {
+        world: get_world(&cookie, &config.grid),
+        program: user_code.user_code,
+        dialect,
+    };
+    format!("/s/{}", permalink.encode()).into_response()
+}
+
+/// Handler for `/s/{blob}`: restores the grid and the program of a permalink,
+/// then redirects to the code mode page.
+///
+/// The grid replaces the one stored in cookies, and the program becomes the
+/// draft of the session, so it fills the textarea (with its dialect selected).
+///
+/// # Arguments
+///
+/// * `config` - Server configuration.
+/// * `storage` - Programs database, where the draft is saved.
+/// * `session` - Session id of the browser.
+/// * `cookie` - The `CookieManager` provided by Axum, used to update cookies.
+/// * `Path(blob)` - The end of the permalink.
+///
+/// # Returns
+///
+/// A redirection to `/code`, or `400 Bad Request` if the link is invalid.
+async fn open_permalink(
+    State(config): State<Arc<Config>>,
+    Extension(storage): Extension<Arc<Storage>>,
+    Extension(session): Extension<SessionId>,
+    mut cookie: CookieManager,
+    Path(blob): Path<String>,
+) -> Response {
+    match Permalink::decode(&blob, &config) {
+        Ok(permalink) => {
+            update_cookie(&permalink.world, &config.session, &mut cookie);
+            update_dialect(permalink.dialect, &config.session, &mut cookie);
+            or_default(storage.save_draft(&session.0, &permalink.program).await);
+            Redirect::to("/code").into_response()
+        }
+        Err(e) => {
+            tracing::warn!(error = %e, "invalid permalink");
+            (StatusCode::BAD_REQUEST, e.to_string()).into_response()
+        }
+    }
+}
+
+/// Handler for `GET /state/export`: the whole session as a JSON document (see `state.rs`).
+///
+/// # Arguments
+///
+/// * `config` - Server configuration.
+/// * `storage` - Programs database, for the current program.
+/// * `session` - Session id of the browser.
+/// * `cookie` - The `CookieManager` provided by Axum, used to read cookies.
+///
+/// # Returns
+///
+/// The JSON document, as a `rustbot-state.json` attachment.
+async fn export_state(
+    State(config): State<Arc<Config>>,
+    Extension(storage): Extension<Arc<Storage>>,
+    Extension(session): Extension<SessionId>,
+    cookie: CookieManager,
+) -> Response {
+    let world = get_world(&cookie, &config.grid);
+    let program = match storage.draft(&session.0).await {
+        Ok(program) => program,
+        Err(e) => return storage_error(e),
+    };
+    (
+        [(
+            header::CONTENT_DISPOSITION,
+            "attachment; filename=\"rustbot-state.json\"",
+        )],
+        Json(SessionState::new(&world, program, get_dialect(&cookie))),
+    )
+        .into_response()
+}
+
+/// Handler for `POST /state/import`: replaces the session by a JSON document
+/// from `/state/export`.
+///
+/// # Arguments
+///
+/// * `config` - Server configuration.
+/// * `storage` - Programs database, where the program is saved as the draft.
+/// * `session` - Session id of the browser.
+/// * `cookie` - The `CookieManager` provided by Axum, used to update cookies.
+/// * `Json(state)` - The document to import.
+///
+/// # Returns
+///
+/// The imported session, as exported by `/state/export` (history positions
+/// outside of the grid are dropped), or `422 Unprocessable Entity` if the
+/// document is not valid.
+async fn import_state(
+    State(config): State<Arc<Config>>,
+    Extension(storage): Extension<Arc<Storage>>,
+    Extension(session): Extension<SessionId>,
+    mut cookie: CookieManager,
+    Json(state): Json<SessionState>,
+) -> Response {
+    let (world, program, dialect) = match state.restore(&config) {
+        Ok(restored) => restored,
+        Err(e) => {
+            tracing::warn!(error = %e, "invalid state");
+            return (StatusCode::UNPROCESSABLE_ENTITY, e.to_string()).into_response();
+        }
+    };
+    if let Err(e) = storage.save_draft(&session.0, &program).await {
+        return storage_error(e);
+    }
+    update_cookie(&world, &config.session, &mut cookie);
+    update_dialect(dialect, &config.session, &mut cookie);
+    Json(SessionState::new(&world, program, dialect)).into_response()
+}
+
+/// Handler for `POST /format`: formats a program (see `rustbot_core::format`).
+///
+/// # Arguments
+///
+/// * `config` - Server configuration, for the maximum program length.
+/// * `Form(user_code)` - The program and its dialect.
+///
+/// # Returns
+///
+/// The formatted program, `422 Unprocessable Entity` if it is not valid or
+/// not written in the code mode language, `413 Payload Too Large` if it is
+/// longer than `max_program_len`, or `400 Bad Request` for an unknown dialect.
+async fn format_program(
+    State(config): State<Arc<Config>>,
+    Form(user_code): Form<UserCode>,
+) -> Response {
+    match user_code.dialect() {
+        Ok(Dialect::RustBot) => {}
+        Ok(dialect) => {
+            return (
+                StatusCode::UNPROCESSABLE_ENTITY,
+                format!(
+                    "Only {} programs can be formatted, not {}",
+                    Dialect::RustBot.label(),
+                    dialect.label()
+                ),
+            )
+                .into_response();
+        }
+        Err(e) => return (StatusCode::BAD_REQUEST, e).into_response(),
+    }
+    if let Some(response) = check_program_len(&config, &user_code.user_code) {
+        return response;
+    }
+    match rustbot_core::format(&user_code.user_code) {
+        Ok(formatted) => formatted.into_response(),
+        Err(e) => (StatusCode::UNPROCESSABLE_ENTITY, e.to_string()).into_response(),
+    }
+}
+
+/// Handler for `POST /optimize`: the net effect of a program on the robot,
+/// and the shortest program with the same effect (see `rustbot_core::optimize`).
+///
+/// The program runs on a copy of the world stored in cookies, which is left
+/// unchanged, as is the draft.
+///
+/// # Arguments
+///
+/// * `config` - Server configuration, for the limits of the run.
+/// * `cookie` - The `CookieManager` provided by Axum, used to read the world.
+/// * `Form(user_code)` - The program and its dialect.
+///
+/// # Returns
+///
+/// An `OptimizedProgram` as JSON, `422 Unprocessable Entity` if the program
+/// fails, `413 Payload Too Large` if it is longer than `max_program_len`, or
+/// `400 Bad Request` for an unknown dialect.
+async fn optimize_program(
+    State(config): State<Arc<Config>>,
+    cookie: CookieManager,
+    Form(user_code): Form<UserCode>,
+) -> Response {
+    let dialect = match user_code.dialect() {
+        Ok(dialect) => dialect,
+        Err(e) => return (StatusCode::BAD_REQUEST, e).into_response(),
+    };
+    if let Some(response) = check_program_len(&config, &user_code.user_code) {
+        return response;
+    }
+
+    let world = get_world(&cookie, &config.grid);
+    let result = dialect
+        .parse(&user_code.user_code)
+        .and_then(|program| rustbot_core::optimize(&program, &world, &limits(&config)));
+    match result {
+        Ok(optimization) => Json(OptimizedProgram {
+            steps: optimization.steps,
+            i: optimization.robot.i,
+            j: optimization.robot.j,
+            heading: optimization.heading.name(),
+            program: optimization.source(),
+            optimal_steps: optimization.commands.len(),
+            equivalent: optimization.equivalent,
+        })
+        .into_response(),
+        Err(e) => (StatusCode::UNPROCESSABLE_ENTITY, e.to_string()).into_response(),
+    }
+}
+
+/// Checks that a program sent to `/lang/*` is written in the code mode
+/// language, the only one they read.
+///
+/// # Arguments
+///
+/// * `dialect` - The `dialect` field of the request, if any.
+///
+/// # Returns
+///
+/// A `400 Bad Request` response for an unknown dialect or another dialect.
+fn check_code_mode(dialect: Option<&str>) -> Option<Response> {
+    match read_dialect(dialect) {
+        Ok(Dialect::RustBot) => None,
+        Ok(dialect) => Some(
+            (
+                StatusCode::BAD_REQUEST,
+                format!(
+                    "Only {} programs can be highlighted and completed, not {}",
+                    Dialect::RustBot.label(),
+                    dialect.label()
+                ),
+            )
+                .into_response(),
+        ),
+        Err(e) => Some((StatusCode::BAD_REQUEST, e).into_response()),
+    }
+}
+
+/// Handler for `POST /lang/tokens`: the tokens of a program and their kind,
+/// to highlight it (see `rustbot_core::lang::tokens`).
+///
+/// # Arguments
+///
+/// * `config` - Server configuration, for the maximum program length.
+/// * `Form(user_code)` - The program, in the code mode language.
+///
+/// # Returns
+///
+/// The list of tokens as JSON (offsets in characters), `413 Payload Too
+/// Large` if the program is longer than `max_program_len`, or `400 Bad
+/// Request` if its dialect is not `rustbot`.
+async fn lang_tokens(
+    State(config): State<Arc<Config>>,
+    Form(user_code): Form<UserCode>,
+) -> Response {
+    if let Some(response) = check_code_mode(user_code.dialect.as_deref()) {
+        return response;
+    }
+    if let Some(response) = check_program_len(&config, &user_code.user_code) {
+        return response;
+    }
+    let tokens: Vec<TokenBody> = lang::tokens(&user_code.user_code)
+        .into_iter()
+        .map(|token| TokenBody {
+            line: token.line,
+            start: token.start,
+            end: token.end,
+            kind: token.kind.name(),
+        })
+        .collect();
+    Json(tokens).into_response()
+}
+
+/// Handler for `POST /lang/complete`: the completions at the cursor (see
+/// `rustbot_core::lang::complete`).
+///
+/// # Arguments
+///
+/// * `config` - Server configuration, for the maximum program length.
+/// * `Form(cursor)` - The program, in the code mode language, and the cursor.
+///
+/// # Returns
+///
+/// The list of completions as JSON (each replacing the program from its
+/// `start` to the cursor), `413 Payload Too Large` if the program is longer
+/// than `max_program_len`, or `400 Bad Request` if its dialect is not
+/// `rustbot`.
+async fn lang_complete(State(config): State<Arc<Config>>, Form(cursor): Form<Cursor>) -> Response {
+    if let Some(response) = check_code_mode(cursor.dialect.as_deref()) {
+        return response;
+    }
+    if let Some(response) = check_program_len(&config, &cursor.user_code) {
+        return response;
+    }
+    let completions: Vec<CompletionBody> = lang::complete(&cursor.user_code, cursor.offset)
+        .into_iter()
+        .map(|completion| CompletionBody {
+            text: completion.text,
+            start: completion.start,
+            kind: completion.kind.name(),
+        })
+        .collect();
+    Json(completions).into_response()
+}
+
+/// Handler for `/ast/schema`: the JSON Schema of programs sent as trees of
+/// blocks (see `ast.rs`).
+async fn ast_schema() -> impl IntoResponse {
+    (
+        [(header::CONTENT_TYPE, "application/schema+json")],
+        ast::SCHEMA,
+    )
+}
+
+/// Handler for `POST /ast/to-text`: writes a tree of blocks as code mode text.
+///
+/// # Arguments
+///
+/// * `Json(tree)` - The program, as a tree of blocks.
+///
+/// # Returns
+///
+/// The program as text, checked by the parser, or `422 Unprocessable Entity`
+/// if the tree is not a valid program.
+async fn ast_to_text(Json(tree): Json<AstProgram>) -> Response {
+    let checked = tree.to_text().and_then(|text| {
+        rustbot_core::parse(&text).map_err(|e| e.to_string())?;
+        Ok(text)
+    });
+    match checked {
+        Ok(text) => text.into_response(),
+        Err(e) => (StatusCode::UNPROCESSABLE_ENTITY, e).into_response(),
+    }
+}
+
+/// Handler for `POST /ast/from-text`: turns a program, written in any dialect,
+/// into a tree of blocks.
+///
+/// # Arguments
+///
+/// * `config` - Server configuration, for the maximum program length.
+/// * `Form(user_code)` - The program and its dialect.
+///
+/// # Returns
+///
+/// The tree of the program as JSON, `422 Unprocessable Entity` if the
+/// program doesn't parse, `413 Payload Too Large` if it is longer than
+/// `max_program_len`, or `400 Bad Request` for an unknown dialect.
+async fn ast_from_text(
+    State(config): State<Arc<Config>>,
+    Form(user_code): Form<UserCode>,
+) -> Response {
+    let dialect = match user_code.dialect() {
+        Ok(dialect) => dialect,
+        Err(e) => return (StatusCode::BAD_REQUEST, e).into_response(),
+    };
+    if let Some(response) = check_program_len(&config, &user_code.user_code) {
+        return response;
+    }
+    match dialect.parse(&user_code.user_code) {
+        Ok(program) => Json(AstProgram::new(&program)).into_response(),
+        Err(e) => (StatusCode::UNPROCESSABLE_ENTITY, e.to_string()).into_response(),
+    }
+}
+
+/// Handler for `POST /karel/world`: replaces the grid by a Karel world file
+/// (see `rustbot_core::karel::parse_world`), sent as the request body, and
+/// selects the Karel dialect in code mode.
+///
+/// # Arguments
+///
+/// * `config` - Server configuration, for the maximum grid size.
+/// * `cookie` - The `CookieManager` provided by Axum, used to update cookies.
+/// * `text` - Content of the world file.
+///
+/// # Returns
+///
+/// The lines of the file that were ignored, one warning per line (nothing if
+/// the whole file was imported), or `422 Unprocessable Entity` if the file is
+/// not a valid world.
+async fn import_karel_world(
+    State(config): State<Arc<Config>>,
+    mut cookie: CookieManager,
+    text: String,
+) -> Response {
+    let imported = match karel::parse_world(&text, &limits(&config)) {
+        Ok(imported) => imported,
+        Err(e) => {
+            tracing::warn!(error = %e, "invalid Karel world");
+            return (StatusCode::UNPROCESSABLE_ENTITY, e.to_string()).into_response();
+        }
+    };
+    update_cookie(&imported.world, &config.session, &mut cookie);
+    update_dialect(Dialect::Karel, &config.session, &mut cookie);
+    imported.warnings.join("\n").into_response()
+}
+
+/// Handler for `/fragment/pen`: the pen controls of play mode.
+///
+/// # Arguments
+///
+/// * `config` - Server configuration.
+/// * `cookie` - The `CookieManager` provided by Axum, used to read cookies.
+///
+/// # Returns
+///
+/// The rendered `partials/pen.html` fragment.
+async fn fragment_pen(
+    State(config): State<Arc<Config>>,
+    cookie: CookieManager,
+) -> impl IntoResponse {
+    render(&PenFragment {
+        world: get_world(&cookie, &config.grid),
+    })
+}
@@ -15,17 +15,16 @@ async function onClick(event) {
             const form = document.querySelector("#user-code");
             const formData = new FormData(form);
             const response = await fetch(
-                "/user-code",
+                "/user-code?format=fragment",
                 {
                     method: "POST",
                     body: new URLSearchParams(formData),
                     headers: { "Content-Type": "application/x-www-form-urlencoded" }
                 }
             )
-            const body = await response.text();
-            const re = /<table id="robot-grid">.+<\/table>/s;
-            const grid = body.match(re);
-            document.getElementById("robot-grid").innerHTML = grid;
+            // Diagnostics panel first, then the grid (updated or not)
+            document.getElementById("code-error").outerHTML = await response.text();
+            await replaceFragment("robot-grid", "/fragment/grid?headers=true");
         } else {
             console.log(event);
             return;
@@ -40,5 +39,394 @@ async function onClick(event) {
     }
 }
 
+/**
+ * @brief Replace an element of the page by a fragment rendered by the server.
+ *
+ * @param {string} id Id of the element to replace (also the id of the fragment's root).
+ * @param {string} url Route of the fragment.
+ */
+async function replaceFragment(id, url) {
+    const response = await fetch(url);
+    document.getElementById(id).outerHTML = await response.text();
+}
+
 const button_form = document.getElementById("button-form-code");
-button_form.addEventListener("click", onClick);
\ No newline at end of file
+button_form.addEventListener("click", onClick);
+
+/**
+ * @brief Show a permalink to the grid and the program, and copy it to the clipboard.
+ *
+ * @param {MouseEvent} event - The click event.
+ */
+async function onShare(event) {
+    event.preventDefault();
+    const form = document.querySelector("#user-code");
+    const path = await sendForm("POST", "/share", new FormData(form));
+    if (path !== null) {
+        const share_link = document.getElementById("share-link");
+        share_link.value = window.location.origin + path;
+        share_link.hidden = false;
+        share_link.select();
+        try {
+            await navigator.clipboard.writeText(share_link.value);
+        } catch (e) {
+            // Clipboard is only available on HTTPS: the link stays selected
+            console.log(e);
+        }
+    }
+}
+document.getElementById("share").addEventListener("click", onShare);
+
+/**
+ * @brief Send a form to a route, and alert the error message if it fails.
+ *
+ * @param {string} method HTTP method.
+ * @param {string} url Route to call.
+ * @param {Object} fields Fields of the form (none for `GET` and `DELETE`).
+ * @param {string} body Raw body, sent as plain text when there are no fields.
+ * @returns {Promise<string|null>} The response text, or null if the request failed.
+ */
+async function sendForm(method, url, fields, body) {
+    const options = { method: method };
+    if (fields) {
+        options.body = new URLSearchParams(fields);
+        options.headers = { "Content-Type": "application/x-www-form-urlencoded" };
+    } else if (body !== undefined) {
+        options.body = body;
+        options.headers = { "Content-Type": "text/plain" };
+    }
+    const response = await fetch(url, options);
+    const text = await response.text();
+    if (!response.ok) {
+        alert(text);
+        return null;
+    }
+    return text;
+}
+
+/**
+ * @brief Refresh the list of saved programs, and select one of them.
+ *
+ * @param {string} name Name of the program to select ("" for none).
+ */
+async function refreshPrograms(name) {
+    await replaceFragment("program-list", "/fragment/programs");
+    const program_list = document.getElementById("program-list");
+    program_list.value = name;
+    program_list.addEventListener("change", onProgramSelected);
+    document.getElementById("program-name").value = name;
+}
+
+/**
+ * @brief Load the selected saved program into the textarea.
+ */
+async function onProgramSelected() {
+    const name = document.getElementById("program-list").value;
+    document.getElementById("program-name").value = name;
+    if (name) {
+        const code = await sendForm("GET", `/programs/${encodeURIComponent(name)}`);
+        if (code !== null) {
+            document.getElementById("code").value = code;
+            onCodeInput();
+        }
+    }
+}
+document.getElementById("program-list").addEventListener("change", onProgramSelected);
+
+/**
+ * @brief Handle clicks on the buttons of saved programs (save, rename, delete).
+ *
+ * @param {MouseEvent} event - The click event.
+ */
+async function onProgramClick(event) {
+    event.preventDefault();
+    const selected = document.getElementById("program-list").value;
+    const name = document.getElementById("program-name").value;
+    try {
+        if (event.target.id == "save-program") {
+            const code = document.getElementById("code").value;
+            const saved = await sendForm("POST", "/programs", { name: name, user_code: code });
+            if (saved !== null) {
+                await refreshPrograms(saved);
+            }
+        } else if (event.target.id == "rename-program") {
+            if (!selected) {
+                alert("Select a program to rename first!");
+                return;
+            }
+            const url = `/programs/${encodeURIComponent(selected)}/rename`;
+            const renamed = await sendForm("POST", url, { new_name: name });
+            if (renamed !== null) {
+                await refreshPrograms(renamed);
+            }
+        } else if (event.target.id == "delete-program") {
+            if (!selected) {
+                alert("Select a program to delete first!");
+                return;
+            }
+            if (await sendForm("DELETE", `/programs/${encodeURIComponent(selected)}`) !== null) {
+                await refreshPrograms("");
+            }
+        }
+    } catch (e) {
+        console.log(e);
+        alert("Could not reach server!");
+    }
+}
+document.getElementById("save-program").addEventListener("click", onProgramClick);
+document.getElementById("rename-program").addEventListener("click", onProgramClick);
+document.getElementById("delete-program").addEventListener("click", onProgramClick);
+/**
+ * @brief Replace the grid by the Karel world file picked by the user, and
+ * switch the program to the Karel dialect.
+ */
+async function onKarelWorld() {
+    const input = document.getElementById("karel-world");
+    if (input.files.length == 0) {
+        return;
+    }
+    try {
+        const text = await input.files[0].text();
+        const warnings = await sendForm("POST", "/karel/world", null, text);
+        if (warnings !== null) {
+            // Ignored lines of the file are shown like diagnostics
+            document.getElementById("code-error").textContent = warnings;
+            document.getElementById("dialect").value = "karel";
+            onCodeInput();
+            await replaceFragment("robot-grid", "/fragment/grid?headers=true");
+        }
+    } catch (e) {
+        console.log(e);
+        alert("Could not reach server!");
+    }
+    input.value = "";
+}
+document.getElementById("karel-world").addEventListener("change", onKarelWorld);
+
+/**
+ * @brief Format the program of the textarea (indentation, spaces, lowercase keywords).
+ *
+ * @param {MouseEvent} event - The click event.
+ */
+async function onFormat(event) {
+    event.preventDefault();
+    try {
+        const form = document.querySelector("#user-code");
+        const formatted = await sendForm("POST", "/format", new FormData(form));
+        if (formatted !== null) {
+            document.getElementById("code").value = formatted;
+            onCodeInput();
+        }
+    } catch (e) {
+        console.log(e);
+        alert("Could not reach server!");
+    }
+}
+document.getElementById("format").addEventListener("click", onFormat);
+
+/**
+ * @brief Show the shortest program moving the robot like the one of the
+ * textarea, to compare it with the optimum.
+ *
+ * @param {MouseEvent} event - The click event.
+ */
+async function onOptimize(event) {
+    event.preventDefault();
+    try {
+        const form = document.querySelector("#user-code");
+        const text = await sendForm("POST", "/optimize", new FormData(form));
+        if (text === null) {
+            return;
+        }
+        const result = JSON.parse(text);
+        let hint;
+        if (result.optimal_steps >= result.steps) {
+            hint = `Your program runs ${result.steps} commands: it can't be shorter!`;
+        } else {
+            hint = `Your program runs ${result.steps} commands, the robot can end at `
+                + `(${result.i}, ${result.j}) facing ${result.heading} with ${result.optimal_steps}:\n`
+                + (result.program || "(nothing to do)\n");
+        }
+        if (!result.equivalent) {
+            hint += "\nThis only moves the robot: paint, walls, items and the grid size are left out.";
+        }
+        const element = document.getElementById("hint");
+        element.textContent = hint;
+        element.hidden = false;
+    } catch (e) {
+        console.log(e);
+        alert("Could not reach server!");
+    }
+}
+document.getElementById("optimize").addEventListener("click", onOptimize);
+
+const code = document.getElementById("code");
+const highlight = document.getElementById("code-highlight");
+const completions = document.getElementById("completions");
+
+/**
+ * @brief Send a program to one of the `/lang` routes of the code mode language.
+ *
+ * @param {string} url Route to call.
+ * @param {Object} fields Fields of the form, with the program as `user_code`.
+ * @returns {Promise<Array|null>} The answer, or null if the program is not
+ * written in the code mode language or the request failed.
+ */
+async function sendLang(url, fields) {
+    if (document.getElementById("dialect").value != "rustbot") {
+        return null;
+    }
+    const response = await fetch(url, {
+        method: "POST",
+        body: new URLSearchParams(fields),
+        headers: { "Content-Type": "application/x-www-form-urlencoded" }
+    });
+    return response.ok ? await response.json() : null;
+}
+
+/**
+ * @brief Show the program in the layer under the textarea, each token colored by its kind.
+ *
+ * @param {string} source The program.
+ * @param {Array} tokens Tokens of `/lang/tokens` (offsets in characters), none for plain text.
+ */
+function renderHighlight(source, tokens) {
+    const chars = Array.from(source);
+    highlight.textContent = "";
+    let position = 0;
+    for (const token of tokens) {
+        highlight.append(chars.slice(position, token.start).join(""));
+        const span = document.createElement("span");
+        span.className = "token-" + token.kind;
+        span.textContent = chars.slice(token.start, token.end).join("");
+        highlight.append(span);
+        position = token.end;
+    }
+    // The textarea shows an empty last line after a final line break, the layer needs one more
+    highlight.append(chars.slice(position).join("") + "\n");
+    highlight.scrollTop = code.scrollTop;
+}
+
+/**
+ * @brief Highlight the program of the textarea.
+ */
+async function refreshHighlight() {
+    const source = code.value;
+    try {
+        const tokens = await sendLang("/lang/tokens", { user_code: source });
+        if (tokens !== null && code.value == source) {
+            renderHighlight(source, tokens);
+        }
+    } catch (e) {
+        console.log(e);
+    }
+}
+
+let highlightTimer;
+
+/**
+ * @brief Show the new program at once, and highlight it when the typing pauses.
+ */
+function onCodeInput() {
+    renderHighlight(code.value, []);
+    clearTimeout(highlightTimer);
+    highlightTimer = setTimeout(refreshHighlight, 150);
+}
+
+let shownCompletions = [];
+let selectedCompletion = 0;
+
+/**
+ * @brief Show the completions at the cursor, while a word is being typed.
+ */
+async function refreshCompletions() {
+    const source = code.value;
+    const before = source.slice(0, code.selectionStart);
+    if (!/[\p{L}\p{N}_]$/u.test(before)) {
+        showCompletions([], 0);
+        return;
+    }
+    const offset = Array.from(before).length;
+    try {
+        const list = await sendLang("/lang/complete", { user_code: source, offset: offset });
+        if (code.value == source) {
+            showCompletions(list || [], offset);
+        }
+    } catch (e) {
+        console.log(e);
+    }
+}
+
+/**
+ * @brief Fill the list of completions under the textarea (hidden if empty).
+ *
+ * @param {Array} list Completions of `/lang/complete`.
+ * @param {number} offset The cursor they were asked for, in characters.
+ */
+function showCompletions(list, offset) {
+    shownCompletions = list.map((completion) => ({ ...completion, offset: offset }));
+    selectedCompletion = 0;
+    completions.textContent = "";
+    for (const [index, completion] of shownCompletions.entries()) {
+        const item = document.createElement("li");
+        item.textContent = completion.text;
+        item.className = "token-" + completion.kind + (index == 0 ? " selected" : "");
+        // Before the textarea loses the focus
+        item.addEventListener("mousedown", (event) => {
+            event.preventDefault();
+            applyCompletion(completion);
+        });
+        completions.append(item);
+    }
+    completions.hidden = shownCompletions.length == 0;
+}
+
+/**
+ * @brief Replace the words being completed by a completion.
+ *
+ * @param {Object} completion The completion, with the cursor it was asked for.
+ */
+function applyCompletion(completion) {
+    const chars = Array.from(code.value);
+    const before = chars.slice(0, completion.start).join("") + completion.text;
+    code.value = before + chars.slice(completion.offset).join("");
+    code.selectionStart = code.selectionEnd = before.length;
+    showCompletions([], 0);
+    onCodeInput();
+}
+
+/**
+ * @brief Browse the completions with the arrows, insert one with Tab or Enter.
+ *
+ * @param {KeyboardEvent} event - The key event.
+ */
+function onCodeKeyDown(event) {
+    if (completions.hidden) {
+        return;
+    }
+    const count = shownCompletions.length;
+    if (event.key == "ArrowDown" || event.key == "ArrowUp") {
+        const step = event.key == "ArrowDown" ? 1 : count - 1;
+        selectedCompletion = (selectedCompletion + step) % count;
+        for (const [index, item] of Array.from(completions.children).entries()) {
+            item.classList.toggle("selected", index == selectedCompletion);
+        }
+    } else if (event.key == "Tab" || event.key == "Enter") {
+        applyCompletion(shownCompletions[selectedCompletion]);
+    } else if (event.key == "Escape") {
+        showCompletions([], 0);
+    } else {
+        return;
+    }
+    event.preventDefault();
+}
+
+code.addEventListener("input", () => {
+    onCodeInput();
+    refreshCompletions();
+});
+code.addEventListener("keydown", onCodeKeyDown);
+code.addEventListener("blur", () => showCompletions([], 0));
+code.addEventListener("scroll", () => { highlight.scrollTop = code.scrollTop; });
+document.getElementById("dialect").addEventListener("change", onCodeInput);
+onCodeInput();
@@ -26,11 +26,14 @@ async function onKeyDown(event) {
             } else if (event.key == "Backspace") {
                 event.preventDefault();
                 await fetch("/reset", { method: "POST" });
+            } else if (event.key == "p" || event.key == "P") {
+                event.preventDefault();
+                await fetch("/pen", { method: "POST" });
             } else {
                 console.log(event);
                 return;
             }
-            replaceGrid();
+            await replaceGrid();
         } catch {
             alert("Could not reach server!");
         }
@@ -60,6 +63,8 @@ async function onClick(event) {
             await fetch("/left", { method: "POST" });
         } else if (event.target.id == "reset") {
             await fetch("/reset", { method: "POST" });
+        } else if (event.target.id == "pen-toggle") {
+            await fetch("/pen", { method: "POST" });
         } else if (event.target.id == "button-form") {
             const form = document.querySelector("#grid-size");
             const formData = new FormData(form);
@@ -75,13 +80,48 @@ async function onClick(event) {
             console.log(event);
             return;
         }
-        replaceGrid();
+        await replaceGrid();
     } catch {
         alert("Could not reach server!");
     }
 }
-const robot_grid = document.getElementById("robot-grid");
-robot_grid.addEventListener("click", onClick);
+/**
+ * @brief Handle clicks on grid cells and on the pen toggle (they are replaced
+ * after each move, so the listener is put on the document).
+ *
+ * @param {MouseEvent} event - The click event.
+ */
+function onGridClick(event) {
+    if (event.target.closest("#robot-grid") || event.target.id == "pen-toggle") {
+        onClick(event);
+    }
+}
+document.addEventListener("click", onGridClick);
+
+/**
+ * @brief Handle a change of the pen color.
+ *
+ * @param {Event} event - The change event.
+ */
+async function onPenColor(event) {
+    if (event.target.id != "pen-color") {
+        return;
+    }
+    try {
+        await fetch(
+            "/pen-color",
+            {
+                method: "POST",
+                body: new URLSearchParams({ color: event.target.value }),
+                headers: { "Content-Type": "application/x-www-form-urlencoded" }
+            }
+        );
+        await replaceGrid();
+    } catch {
+        alert("Could not reach server!");
+    }
+}
+document.addEventListener("change", onPenColor);
 
 const up_button = document.getElementById("up");
 up_button.addEventListener("click", onClick);
@@ -102,57 +142,23 @@ const button_form = document.getElementById("button-form");
 button_form.addEventListener("click", onClick);
 
 /**
- * @brief Reads cookies of document.
+ * @brief Replace an element of the page by a fragment rendered by the server.
+ *
+ * @param {string} id Id of the element to replace (also the id of the fragment's root).
+ * @param {string} url Route of the fragment.
  */
-function readCookies() {
-    console.log(document.cookie);
-    const cookies_array = document.cookie.split("; ");
-    const x = cookies_array.find((row) => row.startsWith("i=")).split("=")[1];
-    const y = cookies_array.find((row) => row.startsWith("j=")).split("=")[1];
-    const max_x = cookies_array.find((row) => row.startsWith("max-i=")).split("=")[1];
-    const max_y = cookies_array.find((row) => row.startsWith("max-j=")).split("=")[1];
-    return [x, y, max_x, max_y];
-}
-
-/**
- * @brief Generate grid given robot's coordinate and size of grid.
- * 
- * @param {int} x_coord Robot's x coordinate.
- * @param {int} y_coord Robot's y coordinate.
- * @param {int} x_max Value max for x (so number of lines in the grid - 1).
- * @param {int} y_max Value max for y (so number of columns in the grid - 1).
- */
-function generateGrid(x_coord, y_coord, max_x, max_y) {
-    let grid = "";
-    for (let x = 0; x < max_x; x++) {
-        grid += "<tr>";
-        for (let y = 0; y < max_y; y++) {
-            if (x == x_coord && y == y_coord) {
-                grid += `<td class="grid-cell" data-x='${x}' data-y='${y}'><img src='/static/robot.png' alt='Robot' class='image-responsive'></td>`;
-            } else {
-                grid += `<td class="grid-cell" data-x='${x}' data-y='${y}'></td>`;
-            }
-        }
-        grid += "</tr>";
-    }
-    document.getElementById("robot-grid").innerHTML = grid;
+async function replaceFragment(id, url) {
+    const response = await fetch(url);
+    document.getElementById(id).outerHTML = await response.text();
 }
 
 /**
- * @brief Updates coordinates written on top of page.
- * 
- * @param {int} x_coord Robot's x coordinate.
- * @param {int} y_coord Robot's y coordinate.
+ * @brief Replace grid, coordinates and pen controls by the new ones (with new robot's coordinates).
  */
-function updateCoords(x_coord, y_coord) {
-    document.getElementById("coords").outerHTML = `<p id="coords">Current coordinates: (${x_coord}, ${y_coord})</p>`;
+async function replaceGrid() {
+    await Promise.all([
+        replaceFragment("robot-grid", "/fragment/grid"),
+        replaceFragment("coords", "/fragment/coords"),
+        replaceFragment("pen", "/fragment/pen"),
+    ]);
 }
-
-/**
- * @brief Replace grid by newly generated one (with new robot's coordinates).
- */
-function replaceGrid() {
-    const [x_coord, y_coord, max_x, max_y] = readCookies();
-    generateGrid(x_coord, y_coord, max_x, max_y);
-    updateCoords(x_coord, y_coord);
-}
\ No newline at end of file